@@ -0,0 +1,106 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-19 15:52:10
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-19 17:06:33
+ * @FilePath: /layer-painter/cli/src/assets.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use image::RgbaImage;
+use layer_painter_wasm::canvas::{Canvas, Image};
+use layer_painter_wasm::compositor::Pixmap;
+
+/// Decode the selected image of every visible layer, or every image of an animated one,
+/// which is not in `pixmaps` yet, from the bytes stored in a bundle, or else from the resolved path of the image
+/// and then `<assets>/<image name>`.
+pub fn load_pixmaps(
+    canvas: &Canvas,
+    assets: &Path,
+    pixmaps: &mut HashMap<String, Pixmap>,
+) -> Result<(), String> {
+    // the depth of the hidden group being skipped.
+    let mut hidden: Option<usize> = None;
+
+    for (depth, layer) in canvas.walk_layers() {
+        match hidden {
+            Some(hidden_depth) if depth > hidden_depth => continue,
+            _ => hidden = None,
+        }
+
+        if !layer.show() {
+            hidden = Some(depth);
+            continue;
+        }
+
+        let images = if layer.animated() {
+            layer.get_images().iter().collect()
+        } else {
+            layer
+                .get_selected_image()
+                .into_iter()
+                .collect::<Vec<&Image>>()
+        };
+
+        for image in images {
+            if pixmaps.contains_key(image.name().as_str()) {
+                continue;
+            }
+
+            let path = image
+                .path()
+                .map(PathBuf::from)
+                .filter(|path| path.is_file())
+                .unwrap_or_else(|| assets.join(image.name()));
+            let decoded = match image.bytes() {
+                // images stored in a bundle.
+                Some(bytes) => image::load_from_memory(&bytes)
+                    .map_err(|err| format!("{} can't be loaded, {}", image.name(), err))?,
+                None => image::open(&path)
+                    .map_err(|err| format!("{} can't be loaded, {}", path.display(), err))?,
+            }
+            .to_rgba8();
+
+            let pixmap = Pixmap::from_rgba(
+                decoded.width() as usize,
+                decoded.height() as usize,
+                decoded.into_raw(),
+            )?;
+            pixmaps.insert(image.name(), pixmap);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the output format keeps the alpha channel, guessed from the extension.
+pub fn has_alpha(output: &Path) -> bool {
+    let extension = output
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    !matches!(extension.as_str(), "jpg" | "jpeg")
+}
+
+pub fn save_pixmap(pixmap: &Pixmap, output: &Path) -> Result<(), String> {
+    let buffer = RgbaImage::from_raw(
+        pixmap.width() as u32,
+        pixmap.height() as u32,
+        pixmap.data().to_vec(),
+    )
+    .ok_or_else(|| String::from("the rendered pixmap is broken"))?;
+
+    let result = if has_alpha(output) {
+        buffer.save(output)
+    } else {
+        image::DynamicImage::ImageRgba8(buffer)
+            .to_rgb8()
+            .save(output)
+    };
+
+    result.map_err(|err| format!("{} can't be written, {}", output.display(), err))
+}
@@ -0,0 +1,427 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-19 15:36:48
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-19 17:12:25
+ * @FilePath: /layer-painter/cli/src/main.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+mod assets;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use layer_painter_wasm::canvas::{
+    apply_batch_output, batch_outputs, combinations, is_bundle, read_bundle, resolve_image_paths,
+    variant_layers, variant_of, BatchManifest, Canvas, Variant, BATCH_TEMPLATE_DEFAULT,
+};
+use layer_painter_wasm::compositor::{
+    encode_animated, render_frames, render_onto, render_sprite_sheet, text_to_pixmap,
+    AnimatedFormat, AnimatedOptions, AtlasFormat, Dither, Packing, Pixmap, SheetOptions,
+    SheetSource,
+};
+
+/// Render a saved layer-painter project to an image.
+#[derive(Parser, Debug)]
+#[clap(name = "layer-painter", version)]
+struct Args {
+    /// The project json file or bundle.
+    project: PathBuf,
+
+    /// The directory of the source images without paths, or moved from them,
+    /// defaults to the directory of the project.
+    #[clap(short, long)]
+    assets: Option<PathBuf>,
+
+    /// The output image, png / jpeg by the extension,
+    /// or gif / apng for every frame of the animated layers.
+    #[clap(short, long, default_value = "output.png")]
+    output: PathBuf,
+
+    /// Override the canvas width.
+    #[clap(long)]
+    width: Option<usize>,
+
+    /// Override the canvas height.
+    #[clap(long)]
+    height: Option<usize>,
+
+    /// Override the selected image of a layer, `<layer name>=<image index>`, can be repeated.
+    #[clap(short, long = "select", value_name = "LAYER=INDEX")]
+    selects: Vec<String>,
+
+    /// Apply a saved scene before the selects. In a batch, render every one of them,
+    /// can be repeated.
+    #[clap(long = "scene", value_name = "SCENE")]
+    scenes: Vec<String>,
+
+    /// Render every saved scene in the batch.
+    #[clap(long)]
+    all_scenes: bool,
+
+    /// Render every combination of the images of the varied layers into this directory
+    /// with a `manifest.json`, instead of `output`.
+    #[clap(long, value_name = "DIR")]
+    batch: Option<PathBuf>,
+
+    /// A layer whose images are combined in the batch, can be repeated,
+    /// defaults to the visible layers with more than one image when there are no scenes.
+    #[clap(long = "vary", value_name = "LAYER")]
+    varies: Vec<String>,
+
+    /// Only render this combination in the batch, `<layer name>=<image index>,...`, can be repeated.
+    #[clap(long = "combination", value_name = "LAYER=INDEX,...")]
+    combinations: Vec<String>,
+
+    /// The file name of the batch outputs, with `{canvas}`, `{index}`, `{scene}`, `{variants}`
+    /// and `{<layer name>}` replaced.
+    #[clap(long, default_value = BATCH_TEMPLATE_DEFAULT)]
+    name_template: String,
+
+    /// The format of the batch outputs, png / jpeg.
+    #[clap(long, default_value = "png")]
+    format: String,
+
+    /// Show this frame of the animated layers in a still image.
+    #[clap(long)]
+    frame: Option<usize>,
+
+    /// The time of every frame of a gif / apng in milliseconds, defaults to the fps of the project.
+    #[clap(long, value_name = "MS")]
+    delay: Option<u32>,
+
+    /// How many times a gif / apng plays, 0 plays forever.
+    /// Defaults to once for the `once` playback mode, forever for the others.
+    #[clap(long)]
+    loops: Option<u32>,
+
+    /// The number of colors of a gif, 2 ~ 256.
+    #[clap(long, default_value = "256")]
+    colors: usize,
+
+    /// The dithering of a gif, none / floyd-steinberg.
+    #[clap(long, default_value = "floyd-steinberg")]
+    dither: String,
+
+    /// Pack every frame into this sprite sheet png, with a json atlas of the same name,
+    /// instead of `output`.
+    #[clap(long, value_name = "PNG")]
+    sheet: Option<PathBuf>,
+
+    /// The frames of the sheet, composition / layers.
+    #[clap(long, default_value = "composition")]
+    sheet_source: String,
+
+    /// The packing of the sheet, grid / max-rects.
+    #[clap(long, default_value = "max-rects")]
+    packing: String,
+
+    /// The pixels between the frames of the sheet and around them.
+    #[clap(long, default_value = "2")]
+    padding: usize,
+
+    /// Round the size of the sheet up to powers of two.
+    #[clap(long)]
+    power_of_two: bool,
+
+    /// Keep the transparent borders of the frames in the sheet.
+    #[clap(long)]
+    no_trim: bool,
+
+    /// The layout of the frames in the atlas, hash / array.
+    #[clap(long, default_value = "hash")]
+    atlas: String,
+}
+
+fn parse_select(select: &str) -> Result<(String, usize), String> {
+    let (layer_name, index) = select
+        .rsplit_once('=')
+        .ok_or_else(|| format!("select `{}` should be `<layer name>=<image index>`", select))?;
+
+    let index = index
+        .trim()
+        .parse::<usize>()
+        .map_err(|err| format!("select `{}` has a bad index, {}", select, err))?;
+
+    Ok((String::from(layer_name), index))
+}
+
+fn apply_select(canvas: &mut Canvas, layer_name: &str, index: usize) -> Result<(), String> {
+    let layer_index = canvas
+        .get_layer_index(layer_name)
+        .ok_or_else(|| format!("the layer `{}` doesn't exist", layer_name))?;
+
+    let layer = canvas.get_mut_layer(layer_index).unwrap();
+    if index >= layer.images().len() {
+        return Err(format!(
+            "the layer `{}` has {} images, can't select {}",
+            layer_name,
+            layer.images().len(),
+            index
+        ));
+    }
+    layer.set_selected(index);
+
+    Ok(())
+}
+
+fn parse_combination(canvas: &Canvas, combination: &str) -> Result<Vec<Variant>, String> {
+    combination
+        .split(',')
+        .map(|select| {
+            let (layer_name, index) = parse_select(select)?;
+            let layer_index = canvas
+                .get_layer_index(layer_name.as_str())
+                .ok_or_else(|| format!("the layer `{}` doesn't exist", layer_name))?;
+            variant_of(canvas, layer_index, index)
+        })
+        .collect()
+}
+
+fn apply_scene(canvas: &mut Canvas, scene_name: &str) -> Result<(), String> {
+    let scene_index = canvas
+        .get_scene_index(scene_name)
+        .ok_or_else(|| format!("the scene `{}` doesn't exist", scene_name))?;
+    canvas.apply_scene(scene_index);
+
+    Ok(())
+}
+
+// the texts are drawn in the bundled font only, the other fonts need a browser,
+// so why a text failed is told too.
+fn warn_failed(canvas: &Canvas, failed: Vec<String>) {
+    for layer_name in failed {
+        let text_error = canvas
+            .get_layer_index(layer_name.as_str())
+            .and_then(|index| canvas.get_layer(index))
+            .and_then(|layer| layer.get_text())
+            .and_then(|text| text_to_pixmap(text).err());
+        match text_error {
+            Some(err) => eprintln!(
+                "warning: the text layer: {} can't be rendered, {}.",
+                layer_name, err
+            ),
+            None => eprintln!("warning: the layer: {} can't be rendered.", layer_name),
+        }
+    }
+}
+
+fn render_to_file(
+    canvas: &Canvas,
+    pixmaps: &HashMap<String, Pixmap>,
+    output: &Path,
+) -> Result<(), String> {
+    let mut pixmap = if assets::has_alpha(output) {
+        Pixmap::new(canvas.width(), canvas.height())
+    } else {
+        Pixmap::filled(canvas.width(), canvas.height(), [255, 255, 255, 255])
+    };
+
+    let failed = render_onto(&mut pixmap, canvas, pixmaps);
+    warn_failed(canvas, failed);
+
+    assets::save_pixmap(&pixmap, output)
+}
+
+fn render_animated_to_file(
+    canvas: &Canvas,
+    pixmaps: &HashMap<String, Pixmap>,
+    format: AnimatedFormat,
+    args: &Args,
+) -> Result<(), String> {
+    let mut options = AnimatedOptions::new(format, &canvas.animation());
+    if let Some(delay) = args.delay {
+        options.delay = delay;
+    }
+    if let Some(loops) = args.loops {
+        options.loops = loops;
+    }
+    options.colors = args.colors;
+    options.dither = Dither::from_name(args.dither.as_str())
+        .ok_or_else(|| format!("the dither `{}` is not supported", args.dither))?;
+
+    let (frames, failed) = render_frames(canvas, pixmaps, options.transparent)?;
+    warn_failed(canvas, failed);
+
+    let bytes = encode_animated(&frames, &options)?;
+    std::fs::write(&args.output, bytes)
+        .map_err(|err| format!("{} can't be written, {}", args.output.display(), err))
+}
+
+fn render_sheet_to_file(
+    canvas: &Canvas,
+    pixmaps: &HashMap<String, Pixmap>,
+    args: &Args,
+    sheet: &Path,
+) -> Result<(), String> {
+    let options = SheetOptions {
+        source: SheetSource::from_name(args.sheet_source.as_str())
+            .ok_or_else(|| format!("the sheet source `{}` is not supported", args.sheet_source))?,
+        packing: Packing::from_name(args.packing.as_str())
+            .ok_or_else(|| format!("the packing `{}` is not supported", args.packing))?,
+        padding: args.padding,
+        power_of_two: args.power_of_two,
+        trim: !args.no_trim,
+        format: AtlasFormat::from_name(args.atlas.as_str())
+            .ok_or_else(|| format!("the atlas `{}` is not supported", args.atlas))?,
+    };
+
+    let image = sheet
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (sprite_sheet, failed) = render_sprite_sheet(canvas, pixmaps, &options, image.as_str())?;
+    warn_failed(canvas, failed);
+
+    assets::save_pixmap(&sprite_sheet.pixmap, sheet)?;
+    let atlas = sheet.with_extension("json");
+    std::fs::write(&atlas, sprite_sheet.atlas.to_json(options.format)?)
+        .map_err(|err| format!("{} can't be written, {}", atlas.display(), err))
+}
+
+fn run_batch(canvas: &Canvas, assets: &Path, args: &Args, dir: &Path) -> Result<(), String> {
+    let extension = match args.format.to_lowercase().as_str() {
+        "png" => "png",
+        "jpg" | "jpeg" => "jpg",
+        format => return Err(format!("the batch format `{}` is not supported", format)),
+    };
+
+    let scenes = if args.all_scenes {
+        canvas
+            .get_scenes()
+            .iter()
+            .map(|scene| scene.name())
+            .collect()
+    } else {
+        for scene_name in args.scenes.iter() {
+            if canvas.get_scene_index(scene_name).is_none() {
+                return Err(format!("the scene `{}` doesn't exist", scene_name));
+            }
+        }
+        args.scenes.clone()
+    };
+
+    let combinations = if !args.combinations.is_empty() {
+        args.combinations
+            .iter()
+            .map(|combination| parse_combination(canvas, combination))
+            .collect::<Result<Vec<Vec<Variant>>, String>>()?
+    } else if !scenes.is_empty() && args.varies.is_empty() {
+        vec![Vec::new()]
+    } else {
+        let layers = if args.varies.is_empty() {
+            variant_layers(canvas)
+        } else {
+            args.varies
+                .iter()
+                .map(|layer_name| {
+                    canvas
+                        .get_layer_index(layer_name)
+                        .ok_or_else(|| format!("the layer `{}` doesn't exist", layer_name))
+                })
+                .collect::<Result<Vec<usize>, String>>()?
+        };
+        combinations(canvas, &layers)?
+    };
+
+    std::fs::create_dir_all(dir)
+        .map_err(|err| format!("{} can't be created, {}", dir.display(), err))?;
+
+    let outputs = batch_outputs(
+        args.name_template.as_str(),
+        canvas,
+        &scenes,
+        &combinations,
+        extension,
+    );
+    let mut pixmaps = HashMap::new();
+    for output in outputs.iter() {
+        let mut canvas = canvas.clone();
+        apply_batch_output(&mut canvas, output);
+        assets::load_pixmaps(&canvas, assets, &mut pixmaps)?;
+        render_to_file(&canvas, &pixmaps, &dir.join(output.file.as_str()))?;
+    }
+
+    let manifest = BatchManifest {
+        canvas: canvas.name(),
+        width: canvas.width(),
+        height: canvas.height(),
+        outputs,
+    };
+    let path = dir.join("manifest.json");
+    std::fs::write(&path, manifest.to_json()?)
+        .map_err(|err| format!("{} can't be written, {}", path.display(), err))
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let data = std::fs::read(&args.project)
+        .map_err(|err| format!("{} can't be read, {}", args.project.display(), err))?;
+    let mut canvas = if is_bundle(&data) {
+        read_bundle(&data).map_err(|err| format!("Reading Project Bundle failed: {}", err))?
+    } else {
+        let data = String::from_utf8(data).map_err(|err| format!("{}", err))?;
+        Canvas::from_json(data.as_str())
+            .map_err(|err| format!("Reading Save Json faild: {}", err))?
+    };
+    resolve_image_paths(&mut canvas, args.project.to_string_lossy().as_ref());
+
+    if let Some(width) = args.width {
+        canvas.set_width(width);
+    }
+    if let Some(height) = args.height {
+        canvas.set_height(height);
+    }
+
+    if args.batch.is_none() {
+        for scene_name in args.scenes.iter() {
+            apply_scene(&mut canvas, scene_name)?;
+        }
+    }
+
+    if let Some(frame) = args.frame {
+        canvas.set_frame(frame);
+    }
+
+    for select in args.selects.iter() {
+        let (layer_name, index) = parse_select(select)?;
+        apply_select(&mut canvas, layer_name.as_str(), index)?;
+    }
+
+    let assets = match args.assets.clone() {
+        Some(assets) => assets,
+        None => args
+            .project
+            .parent()
+            .map(|parent| parent.to_path_buf())
+            .unwrap_or_default(),
+    };
+
+    if let Some(dir) = args.batch.as_ref() {
+        return run_batch(&canvas, &assets, &args, dir);
+    }
+
+    let mut pixmaps = HashMap::new();
+    assets::load_pixmaps(&canvas, &assets, &mut pixmaps)?;
+
+    if let Some(sheet) = args.sheet.as_ref() {
+        return render_sheet_to_file(&canvas, &pixmaps, &args, sheet);
+    }
+
+    let animated = args
+        .output
+        .extension()
+        .and_then(|extension| AnimatedFormat::from_extension(&extension.to_string_lossy()));
+    match animated {
+        Some(format) => render_animated_to_file(&canvas, &pixmaps, format, &args),
+        None => render_to_file(&canvas, &pixmaps, &args.output),
+    }
+}
+
+fn main() {
+    if let Err(err) = run(Args::parse()) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
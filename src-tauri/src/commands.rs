@@ -0,0 +1,575 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-06 20:14:37
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-06 23:28:51
+ * @FilePath: /layer-painter/src-tauri/src/commands.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use tauri::api::dialog::FileDialogBuilder;
+use tauri::{AppHandle, Manager, State};
+
+const PROJECT_EXTENSIONS: [&str; 2] = ["json", "zip"];
+// the project json in a bundle, the same as `BUNDLE_PROJECT` of the wasm part.
+const BUNDLE_PROJECT: &str = "project.json";
+// the same list as `IMAGE_EXTENSIONS` of the wasm part.
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "webp", "gif", "bmp"];
+// how deep the subfolders are searched for the missing images.
+const RELINK_DEPTH: usize = 8;
+// a changed file is read after it isn't written for the delay.
+const WATCH_DELAY: Duration = Duration::from_millis(500);
+// how often the watcher thread takes the new files to watch.
+const WATCH_INTERVAL: Duration = Duration::from_millis(200);
+/// Emitted with the `NativeFile` of a watched image which is changed.
+pub const IMAGE_CHANGED_EVENT: &str = "image-changed";
+
+/// The path of the project which is opened or saved last.
+#[derive(Default)]
+pub struct CurrentProject(Mutex<Option<PathBuf>>);
+
+impl CurrentProject {
+  fn get(&self) -> Result<Option<PathBuf>, String> {
+    self
+      .0
+      .lock()
+      .map(|path| path.clone())
+      .map_err(|err| format!("{}", err))
+  }
+
+  fn set(&self, path: Option<PathBuf>) -> Result<(), String> {
+    *self.0.lock().map_err(|err| format!("{}", err))? = path;
+    Ok(())
+  }
+}
+
+/// The files and folders picked in the dialogs or dropped on the window,
+/// and the images of the opened projects,
+/// the commands only touch them and the folder of the current project.
+#[derive(Default)]
+pub struct PickedPaths(Mutex<Vec<PathBuf>>);
+
+impl PickedPaths {
+  pub fn add(&self, path: &Path) -> Result<(), String> {
+    let mut picked = self.0.lock().map_err(|err| format!("{}", err))?;
+    if let Some(path) = canonical(path) {
+      if !picked.contains(&path) {
+        picked.push(path);
+      }
+    }
+    Ok(())
+  }
+
+  // whether `path` is a picked file or in a picked folder.
+  fn contains(&self, path: &Path) -> Result<bool, String> {
+    let picked = self.0.lock().map_err(|err| format!("{}", err))?;
+    Ok(picked.iter().any(|picked| path.starts_with(picked)))
+  }
+}
+
+// the image files to watch, with a sender for why they can't be watched.
+type WatchRequest = (Vec<(PathBuf, String)>, mpsc::Sender<Result<(), String>>);
+
+/// Sends the image files to watch to the watcher thread,
+/// the resolved paths with the paths from the app.
+pub struct WatchedImages(Mutex<mpsc::Sender<WatchRequest>>);
+
+impl WatchedImages {
+  fn set(&self, paths: Vec<(PathBuf, String)>) -> Result<(), String> {
+    let (result_sender, result) = mpsc::channel();
+    self
+      .0
+      .lock()
+      .map_err(|err| format!("{}", err))?
+      .send((paths, result_sender))
+      .map_err(|_| String::from("the image watcher is stopped"))?;
+    result
+      .recv()
+      .map_err(|_| String::from("the image watcher is stopped"))?
+  }
+}
+
+#[derive(Clone, Serialize)]
+pub struct NativeFile {
+  path: String,
+  name: String,
+  #[serde(serialize_with = "to_base64")]
+  bytes: Vec<u8>,
+}
+
+// the bytes go through json as base64, an array of numbers is a lot larger.
+fn to_base64<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_str(&base64::encode(bytes))
+}
+
+fn from_base64(data: &str) -> Result<Vec<u8>, String> {
+  base64::decode(data).map_err(|err| format!("the bytes aren't base64, {}", err))
+}
+
+#[derive(Deserialize)]
+pub struct MissingImage {
+  name: String,
+  hash: Option<String>,
+}
+
+// the same FNV-1a hash as `content_hash` of the wasm part.
+fn content_hash(bytes: &[u8]) -> String {
+  let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+  for byte in bytes.iter() {
+    hash ^= *byte as u64;
+    hash = hash.wrapping_mul(0x0100_0000_01b3);
+  }
+
+  format!("{:016x}", hash)
+}
+
+// the absolute path without `..` and links, a file to write may not exist yet.
+fn canonical(path: &Path) -> Option<PathBuf> {
+  if let Ok(path) = fs::canonicalize(path) {
+    return Some(path);
+  }
+
+  let parent = fs::canonicalize(path.parent()?).ok()?;
+  Some(parent.join(path.file_name()?))
+}
+
+// `path` if it's in the folder of the current project or picked, an error otherwise.
+fn allowed(current: &CurrentProject, picked: &PickedPaths, path: &Path) -> Result<PathBuf, String> {
+  let denied = || format!("{} isn't in the project folder or picked", path.display());
+  let path = canonical(path).ok_or_else(denied)?;
+
+  let folder = current
+    .get()?
+    .and_then(|project| canonical(&project))
+    .and_then(|project| project.parent().map(Path::to_path_buf));
+  if folder.map_or(false, |folder| path.starts_with(folder)) || picked.contains(&path)? {
+    Ok(path)
+  } else {
+    Err(denied())
+  }
+}
+
+fn extension_of(path: &Path) -> String {
+  path
+    .extension()
+    .map(|extension| extension.to_string_lossy().to_lowercase())
+    .unwrap_or_default()
+}
+
+fn read_file(path: &Path) -> Result<NativeFile, String> {
+  let bytes =
+    fs::read(path).map_err(|err| format!("{} can't be read, {}", path.display(), err))?;
+  let name = path
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+
+  Ok(NativeFile {
+    path: path.display().to_string(),
+    name,
+    bytes,
+  })
+}
+
+// the project json, or the one in a bundle.
+fn project_json(bytes: &[u8]) -> Option<Value> {
+  if !bytes.starts_with(b"PK\x03\x04") {
+    return serde_json::from_slice(bytes).ok();
+  }
+
+  let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).ok()?;
+  let mut file = archive.by_name(BUNDLE_PROJECT).ok()?;
+  let mut json = Vec::new();
+  file.read_to_end(&mut json).ok()?;
+  serde_json::from_slice(&json).ok()
+}
+
+// the paths of the images in the layers, and the layers of the groups.
+fn image_paths_of(value: &Value, paths: &mut Vec<String>) {
+  match value {
+    Value::Object(map) => {
+      if let Some(Value::Array(images)) = map.get("images") {
+        for image in images.iter() {
+          if let Some(path) = image.get("path").and_then(Value::as_str) {
+            paths.push(String::from(path));
+          }
+        }
+      }
+      for value in map.values() {
+        image_paths_of(value, paths);
+      }
+    }
+    Value::Array(values) => {
+      for value in values.iter() {
+        image_paths_of(value, paths);
+      }
+    }
+    _ => {}
+  }
+}
+
+// the images of the project at `project` are read and watched by the app,
+// their paths are relative to its folder, like `../art/x.png`, or absolute.
+fn add_project_images(picked: &PickedPaths, project: &Path, bytes: &[u8]) -> Result<(), String> {
+  let folder = match project.parent() {
+    Some(folder) => folder,
+    None => return Ok(()),
+  };
+
+  let mut paths = Vec::new();
+  if let Some(json) = project_json(bytes) {
+    image_paths_of(&json, &mut paths);
+  }
+  for path in paths.iter() {
+    picked.add(&folder.join(path))?;
+  }
+  Ok(())
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> Result<(), String> {
+  fs::write(path, bytes).map_err(|err| format!("{} can't be written, {}", path.display(), err))
+}
+
+fn image_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(_) => return,
+  };
+
+  for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+    if path.is_dir() {
+      if depth > 0 {
+        image_files(&path, depth - 1, files);
+      }
+    } else if IMAGE_EXTENSIONS.contains(&extension_of(&path).as_str()) {
+      files.push(path);
+    }
+  }
+}
+
+// the dialogs answer in callbacks on the main thread, the commands wait for them.
+fn pick_file(dialog: FileDialogBuilder) -> Option<PathBuf> {
+  let (sender, receiver) = mpsc::channel();
+  dialog.pick_file(move |path| sender.send(path).unwrap_or_default());
+  receiver.recv().ok().flatten()
+}
+
+fn pick_folder(dialog: FileDialogBuilder) -> Option<PathBuf> {
+  let (sender, receiver) = mpsc::channel();
+  dialog.pick_folder(move |path| sender.send(path).unwrap_or_default());
+  receiver.recv().ok().flatten()
+}
+
+fn pick_files(dialog: FileDialogBuilder) -> Vec<PathBuf> {
+  let (sender, receiver) = mpsc::channel();
+  dialog.pick_files(move |paths| sender.send(paths).unwrap_or_default());
+  receiver.recv().ok().flatten().unwrap_or_default()
+}
+
+fn pick_save_path(file_name: &str, current: Option<PathBuf>) -> Option<PathBuf> {
+  let extension = extension_of(Path::new(file_name));
+  let mut dialog = FileDialogBuilder::new().set_file_name(file_name);
+  if !extension.is_empty() {
+    dialog = dialog.add_filter(extension.to_uppercase(), &[extension.as_str()]);
+  }
+  if let Some(directory) = current.as_ref().and_then(|path| path.parent()) {
+    dialog = dialog.set_directory(directory);
+  }
+
+  let (sender, receiver) = mpsc::channel();
+  dialog.save_file(move |path| sender.send(path).unwrap_or_default());
+  let mut path = receiver.recv().ok().flatten()?;
+
+  // some platforms don't add the extension of the filter.
+  if !extension.is_empty() && extension_of(&path) != extension {
+    path.set_extension(extension);
+  }
+  Some(path)
+}
+
+/// Pick a project json or bundle, and remember it as the current project.
+#[tauri::command]
+pub async fn open_project(
+  current: State<'_, CurrentProject>,
+  picked: State<'_, PickedPaths>,
+) -> Result<Option<NativeFile>, String> {
+  let dialog = FileDialogBuilder::new().add_filter("Project", &PROJECT_EXTENSIONS);
+  let path = match pick_file(dialog) {
+    Some(path) => path,
+    None => return Ok(None),
+  };
+
+  picked.add(&path)?;
+  let file = read_file(&path)?;
+  add_project_images(&picked, &path, &file.bytes)?;
+  current.set(Some(path))?;
+  Ok(Some(file))
+}
+
+/// Read the project at `path` dropped on the window, and remember it as the current project.
+#[tauri::command]
+pub async fn open_project_file(
+  current: State<'_, CurrentProject>,
+  picked: State<'_, PickedPaths>,
+  path: String,
+) -> Result<NativeFile, String> {
+  let path = allowed(&current, &picked, Path::new(&path))?;
+  let file = read_file(&path)?;
+  add_project_images(&picked, &path, &file.bytes)?;
+  current.set(Some(path))?;
+  Ok(file)
+}
+
+/// Pick images for a layer.
+#[tauri::command]
+pub async fn open_images(picked: State<'_, PickedPaths>) -> Result<Vec<NativeFile>, String> {
+  let dialog = FileDialogBuilder::new().add_filter("Image", &IMAGE_EXTENSIONS);
+  let paths = pick_files(dialog);
+  // they are read again by their paths after the project is saved.
+  for path in paths.iter() {
+    picked.add(path)?;
+  }
+  paths.iter().map(|path| read_file(path)).collect()
+}
+
+/// Read the images at the resolved `paths`, `None` for the missing files
+/// and the ones outside of the project folder and the picked paths, which can be relinked.
+#[tauri::command]
+pub async fn read_images(
+  current: State<'_, CurrentProject>,
+  picked: State<'_, PickedPaths>,
+  paths: Vec<String>,
+) -> Result<Vec<Option<NativeFile>>, String> {
+  paths
+    .iter()
+    .map(|path| {
+      let resolved = match allowed(&current, &picked, Path::new(path)) {
+        Ok(resolved) if resolved.is_file() => resolved,
+        _ => return Ok(None),
+      };
+
+      let mut file = read_file(&resolved)?;
+      file.path = path.clone();
+      Ok(Some(file))
+    })
+    .collect()
+}
+
+/// Pick a folder and look for the missing images in it and its subfolders,
+/// by the file name first and then by the content hash.
+/// Returns the found files in the order of `images`, or `None` if the dialog is canceled.
+#[tauri::command]
+pub async fn relink_images(
+  picked: State<'_, PickedPaths>,
+  images: Vec<MissingImage>,
+) -> Result<Option<Vec<Option<NativeFile>>>, String> {
+  let dir = match pick_folder(FileDialogBuilder::new()) {
+    Some(dir) => dir,
+    None => return Ok(None),
+  };
+  picked.add(&dir)?;
+
+  let mut files = Vec::new();
+  image_files(&dir, RELINK_DEPTH, &mut files);
+
+  // hashed on demand, reading every file is slow.
+  let mut hashes: Vec<Option<String>> = vec![None; files.len()];
+  let mut found = Vec::with_capacity(images.len());
+  for image in images.iter() {
+    let by_name = files.iter().position(|file| {
+      file
+        .file_name()
+        .map_or(false, |name| name.to_string_lossy() == image.name.as_str())
+    });
+
+    let by_hash = || {
+      let hash = image.hash.as_ref()?;
+      (0..files.len()).find(|&index| {
+        if hashes[index].is_none() {
+          hashes[index] = fs::read(&files[index]).ok().map(|bytes| content_hash(&bytes));
+        }
+        hashes[index].as_ref() == Some(hash)
+      })
+    };
+
+    match by_name.or_else(by_hash) {
+      Some(index) => found.push(Some(read_file(&files[index])?)),
+      None => found.push(None),
+    }
+  }
+
+  Ok(Some(found))
+}
+
+/// The path to save the project, the current one,
+/// or a picked one for `save_as` or a current project of another extension.
+/// `None` if the dialog is canceled.
+#[tauri::command]
+pub async fn project_save_path(
+  current: State<'_, CurrentProject>,
+  picked: State<'_, PickedPaths>,
+  file_name: String,
+  save_as: bool,
+) -> Result<Option<String>, String> {
+  let current_path = current.get()?;
+  let extension = extension_of(Path::new(&file_name));
+  let path = match current_path.clone() {
+    Some(path) if !save_as && extension_of(&path) == extension => Some(path),
+    _ => pick_save_path(&file_name, current_path),
+  };
+  if let Some(path) = path.as_ref() {
+    picked.add(path)?;
+  }
+
+  Ok(path.map(|path| path.display().to_string()))
+}
+
+/// Write the project, and remember it as the current project.
+#[tauri::command]
+pub async fn save_project(
+  current: State<'_, CurrentProject>,
+  picked: State<'_, PickedPaths>,
+  path: String,
+  bytes: String,
+) -> Result<(), String> {
+  let path = allowed(&current, &picked, Path::new(&path))?;
+  write_file(&path, &from_base64(&bytes)?)?;
+  current.set(Some(path))
+}
+
+/// Write an export to a picked path, which doesn't change the current project.
+#[tauri::command]
+pub async fn save_file(
+  current: State<'_, CurrentProject>,
+  bytes: String,
+  file_name: String,
+) -> Result<Option<String>, String> {
+  let bytes = from_base64(&bytes)?;
+  let path = match pick_save_path(&file_name, current.get()?) {
+    Some(path) => path,
+    None => return Ok(None),
+  };
+
+  write_file(&path, &bytes)?;
+  Ok(Some(path.display().to_string()))
+}
+
+/// Watch the image files at `paths`, instead of the watched ones.
+/// The ones outside of the project folder and the picked paths are skipped,
+/// it fails if the watcher can't watch the others.
+#[tauri::command]
+pub async fn watch_images(
+  current: State<'_, CurrentProject>,
+  picked: State<'_, PickedPaths>,
+  watched: State<'_, WatchedImages>,
+  paths: Vec<String>,
+) -> Result<(), String> {
+  let paths = paths
+    .into_iter()
+    .filter_map(|path| {
+      let resolved = allowed(&current, &picked, Path::new(&path)).ok()?;
+      Some((resolved, path))
+    })
+    .collect();
+  watched.set(paths)
+}
+
+/// Watch the image files from `watch_images` in a thread,
+/// and emit the changed ones once they are written.
+pub fn watch_image_files(app: AppHandle) -> WatchedImages {
+  let (paths_sender, paths_receiver) = mpsc::channel::<WatchRequest>();
+
+  thread::spawn(move || {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = match notify::watcher(sender, WATCH_DELAY) {
+      Ok(watcher) => watcher,
+      Err(err) => {
+        // every request is answered with why.
+        let err = format!("the image files can't be watched, {}", err);
+        for (_, result_sender) in paths_receiver.iter() {
+          result_sender.send(Err(err.clone())).unwrap_or_default();
+        }
+        return;
+      }
+    };
+
+    // the folders are watched, an editor may replace a file instead of writing it.
+    let mut folders: Vec<PathBuf> = Vec::new();
+    let mut watched: HashMap<PathBuf, String> = HashMap::new();
+    loop {
+      match paths_receiver.try_recv() {
+        Ok((paths, result_sender)) => {
+          let mut failed = Vec::new();
+          for folder in folders.drain(..) {
+            match watcher.unwatch(&folder) {
+              // a removed folder isn't watched any more.
+              Ok(_) | Err(notify::Error::WatchNotFound) => {}
+              Err(err) => failed.push(format!("{}: {}", folder.display(), err)),
+            }
+          }
+          watched = paths.into_iter().collect();
+          for path in watched.keys() {
+            if let Some(folder) = path.parent().map(Path::to_path_buf) {
+              if folders.contains(&folder) {
+                continue;
+              }
+              match watcher.watch(&folder, RecursiveMode::NonRecursive) {
+                Ok(_) => folders.push(folder),
+                Err(err) => failed.push(format!("{}: {}", folder.display(), err)),
+              }
+            }
+          }
+
+          let result = if failed.is_empty() {
+            Ok(())
+          } else {
+            Err(format!(
+              "the folders can't be watched, {}",
+              failed.join(", ")
+            ))
+          };
+          // the app may be closed while waiting.
+          result_sender.send(result).unwrap_or_default();
+        }
+        Err(TryRecvError::Empty) => {}
+        Err(TryRecvError::Disconnected) => return,
+      }
+
+      let path = match events.recv_timeout(WATCH_INTERVAL) {
+        Ok(DebouncedEvent::Create(path))
+        | Ok(DebouncedEvent::Write(path))
+        | Ok(DebouncedEvent::Rename(_, path)) => path,
+        Ok(_) | Err(RecvTimeoutError::Timeout) => continue,
+        Err(RecvTimeoutError::Disconnected) => return,
+      };
+
+      if let Some(app_path) = watched.get(&path) {
+        if let Ok(mut file) = read_file(&path) {
+          file.path = app_path.clone();
+          app.emit_all(IMAGE_CHANGED_EVENT, file).unwrap_or_default();
+        }
+      }
+    }
+  });
+
+  WatchedImages(Mutex::new(paths_sender))
+}
+
+/// Forget the current project, the next save asks for a path.
+#[tauri::command]
+pub fn close_project(current: State<'_, CurrentProject>) -> Result<(), String> {
+  current.set(None)
+}
@@ -3,8 +3,39 @@
   windows_subsystem = "windows"
 )]
 
+mod commands;
+
+use tauri::{FileDropEvent, Manager, WindowEvent};
+
 fn main() {
   tauri::Builder::default()
+    .manage(commands::CurrentProject::default())
+    .manage(commands::PickedPaths::default())
+    .setup(|app| {
+      app.manage(commands::watch_image_files(app.handle()));
+      Ok(())
+    })
+    // the dropped files may be opened by the commands.
+    .on_window_event(|event| {
+      if let WindowEvent::FileDrop(FileDropEvent::Dropped(paths)) = event.event() {
+        let picked = event.window().state::<commands::PickedPaths>();
+        for path in paths.iter() {
+          picked.add(path).unwrap_or_default();
+        }
+      }
+    })
+    .invoke_handler(tauri::generate_handler![
+      commands::open_project,
+      commands::open_project_file,
+      commands::open_images,
+      commands::read_images,
+      commands::relink_images,
+      commands::project_save_path,
+      commands::save_project,
+      commands::save_file,
+      commands::watch_images,
+      commands::close_project
+    ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
@@ -0,0 +1,135 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-03 20:18:52
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-03 23:27:40
+ * @FilePath: /layer-painter/wasm/src/canvas/animation.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use serde::{Deserialize, Serialize};
+
+pub const FPS_DEFAULT: f64 = 12.0;
+pub const FPS_MAX: f64 = 60.0;
+
+/// How the frames go on after the last one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlaybackMode {
+    /// Stop at the last frame.
+    Once,
+    /// Start again from the first frame.
+    #[default]
+    Loop,
+    /// Go back to the first frame, then forward again.
+    PingPong,
+}
+
+impl PlaybackMode {
+    pub const ALL: [PlaybackMode; 3] = [
+        PlaybackMode::Once,
+        PlaybackMode::Loop,
+        PlaybackMode::PingPong,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PlaybackMode::Once => "once",
+            PlaybackMode::Loop => "loop",
+            PlaybackMode::PingPong => "ping-pong",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        PlaybackMode::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.name() == name)
+    }
+
+    /// The image of a layer with `len` images at the frame `frame` of the timeline,
+    /// `offset` is the image the layer starts with.
+    pub fn image_at(&self, frame: usize, offset: usize, len: usize) -> usize {
+        if len <= 1 {
+            return 0;
+        }
+
+        let frame = frame + offset;
+        match self {
+            PlaybackMode::Once => frame.min(len - 1),
+            PlaybackMode::Loop => frame % len,
+            PlaybackMode::PingPong => {
+                let period = 2 * len - 2;
+                let frame = frame % period;
+                if frame < len {
+                    frame
+                } else {
+                    period - frame
+                }
+            }
+        }
+    }
+
+    /// The number of frames until a layer with `len` images
+    /// shows the same as at the first frame again, or stops.
+    pub fn cycle_len(&self, offset: usize, len: usize) -> usize {
+        if len <= 1 {
+            return 1;
+        }
+
+        match self {
+            PlaybackMode::Once => len - offset.min(len - 1),
+            PlaybackMode::Loop => len,
+            PlaybackMode::PingPong => 2 * len - 2,
+        }
+    }
+}
+
+/// The timeline settings of a canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Animation {
+    #[serde(default = "fps_default")]
+    fps: f64,
+
+    #[serde(default)]
+    mode: PlaybackMode,
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            fps: FPS_DEFAULT,
+            mode: PlaybackMode::Loop,
+        }
+    }
+}
+
+impl Animation {
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    pub fn set_fps(&mut self, fps: f64) {
+        if fps.is_nan() {
+            return;
+        }
+        self.fps = fps.clamp(1.0, FPS_MAX);
+    }
+
+    pub fn mode(&self) -> PlaybackMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: PlaybackMode) {
+        self.mode = mode;
+    }
+
+    /// The frame of the timeline at `elapsed` milliseconds.
+    pub fn frame_at(&self, elapsed: f64) -> usize {
+        (elapsed.max(0.0) * self.fps / 1000.0).floor() as usize
+    }
+}
+
+fn fps_default() -> f64 {
+    FPS_DEFAULT
+}
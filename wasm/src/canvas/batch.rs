@@ -0,0 +1,369 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-01 20:05:33
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-01 23:14:08
+ * @FilePath: /layer-painter/wasm/src/canvas/batch.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+//! A batch export renders the project once for every combination
+//! of the images of some layers, e.g. outfits x faces x backgrounds,
+//! and of the scenes if there are any.
+
+use std::collections::HashSet;
+
+use crate::canvas::Canvas;
+
+use serde::Serialize;
+
+/// `{canvas}` is the canvas name, `{index}` the number of the combination, `{scene}` the scene,
+/// `{variants}` the scene and all the picked images, and `{<layer name>}` the image picked for the layer.
+pub const BATCH_TEMPLATE_DEFAULT: &str = "{canvas}_{variants}";
+
+/// The image picked for a layer in a combination.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Variant {
+    pub layer_index: usize,
+    pub layer: String,
+    pub image_index: usize,
+    pub image: String,
+}
+
+/// One rendered file of a batch, the scene is applied before the variants.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchOutput {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scene: Option<String>,
+    pub variants: Vec<Variant>,
+}
+
+/// Describes every file of a batch, saved next to them as `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchManifest {
+    pub canvas: String,
+    pub width: usize,
+    pub height: usize,
+    pub outputs: Vec<BatchOutput>,
+}
+
+impl BatchManifest {
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|err| format!("{}", err))
+    }
+}
+
+/// The visible layers which have more than one image, in render order.
+pub fn variant_layers(canvas: &Canvas) -> Vec<usize> {
+    // the depth of the hidden group being skipped.
+    let mut hidden: Option<usize> = None;
+    let mut layers = Vec::new();
+
+    for (index, (depth, layer)) in canvas.walk_layers().into_iter().enumerate() {
+        match hidden {
+            Some(hidden_depth) if depth > hidden_depth => continue,
+            _ => hidden = None,
+        }
+
+        if !layer.show() {
+            hidden = Some(depth);
+        } else if !layer.is_group() && layer.get_images().len() > 1 {
+            layers.push(index);
+        }
+    }
+
+    layers
+}
+
+/// The image `image_index` of the layer `layer_index`.
+pub fn variant_of(
+    canvas: &Canvas,
+    layer_index: usize,
+    image_index: usize,
+) -> Result<Variant, String> {
+    let layer = canvas
+        .get_layer(layer_index)
+        .ok_or_else(|| format!("the layer {} doesn't exist", layer_index))?;
+    let image = layer.get_images().get(image_index).ok_or_else(|| {
+        format!(
+            "the layer `{}` has {} images, can't select {}",
+            layer.name(),
+            layer.get_images().len(),
+            image_index
+        )
+    })?;
+
+    Ok(Variant {
+        layer_index,
+        layer: layer.name(),
+        image_index,
+        image: image.name(),
+    })
+}
+
+/// The cartesian product of the images of `layer_indexes`,
+/// the last layer changes the fastest.
+pub fn combinations(canvas: &Canvas, layer_indexes: &[usize]) -> Result<Vec<Vec<Variant>>, String> {
+    let mut combinations = vec![Vec::new()];
+
+    for layer_index in layer_indexes.iter() {
+        let images_len = canvas
+            .get_layer(*layer_index)
+            .ok_or_else(|| format!("the layer {} doesn't exist", layer_index))?
+            .get_images()
+            .len();
+
+        let mut next = Vec::with_capacity(combinations.len() * images_len);
+        for combination in combinations.iter() {
+            for image_index in 0..images_len {
+                let mut combination: Vec<Variant> = combination.clone();
+                combination.push(variant_of(canvas, *layer_index, image_index)?);
+                next.push(combination);
+            }
+        }
+        combinations = next;
+    }
+
+    Ok(combinations)
+}
+
+/// Select the images of `combination` in their layers.
+pub fn apply_combination(canvas: &mut Canvas, combination: &[Variant]) {
+    for variant in combination.iter() {
+        if let Some(layer) = canvas.get_mut_layer(variant.layer_index) {
+            layer.set_selected(variant.image_index);
+        }
+    }
+}
+
+/// Apply the scene and then the variants of `output`.
+pub fn apply_batch_output(canvas: &mut Canvas, output: &BatchOutput) {
+    let scene_index = output
+        .scene
+        .as_ref()
+        .and_then(|scene| canvas.get_scene_index(scene.as_str()));
+    if let Some(scene_index) = scene_index {
+        canvas.apply_scene(scene_index);
+    }
+
+    apply_combination(canvas, &output.variants);
+}
+
+// the name of an image without its extension.
+fn stem(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => name,
+    }
+}
+
+/// Fill `template` for the combination, see `BATCH_TEMPLATE_DEFAULT`.
+/// Characters which can't be in a file name are replaced with `_`.
+pub fn batch_file_name(
+    template: &str,
+    canvas: &Canvas,
+    index: usize,
+    scene: Option<&str>,
+    combination: &[Variant],
+) -> String {
+    let variants = scene
+        .into_iter()
+        .chain(
+            combination
+                .iter()
+                .map(|variant| stem(variant.image.as_str())),
+        )
+        .collect::<Vec<&str>>()
+        .join("_");
+
+    let mut name = template
+        .replace("{canvas}", canvas.name().as_str())
+        .replace("{index}", index.to_string().as_str())
+        .replace("{scene}", scene.unwrap_or_default())
+        .replace("{variants}", variants.as_str());
+    for variant in combination.iter() {
+        name = name.replace(
+            format!("{{{}}}", variant.layer).as_str(),
+            stem(variant.image.as_str()),
+        );
+    }
+
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// The files of every scene of `scenes` with every combination of `combinations`,
+/// or only the combinations without scenes, with the extension.
+/// A name used twice gets the number of its combination appended, or the next free one.
+pub fn batch_outputs(
+    template: &str,
+    canvas: &Canvas,
+    scenes: &[String],
+    combinations: &[Vec<Variant>],
+    extension: &str,
+) -> Vec<BatchOutput> {
+    let scenes: Vec<Option<&String>> = if scenes.is_empty() {
+        vec![None]
+    } else {
+        scenes.iter().map(Some).collect()
+    };
+    let mut used = HashSet::new();
+
+    scenes
+        .iter()
+        .flat_map(|scene| {
+            combinations
+                .iter()
+                .map(move |combination| (*scene, combination))
+        })
+        .enumerate()
+        .map(|(index, (scene, combination))| {
+            let scene = scene.map(|scene| scene.as_str());
+            let name = batch_file_name(template, canvas, index, scene, combination);
+            let mut file = format!("{}.{}", name, extension);
+            // the number may be in a name of the template already.
+            let mut number = index;
+            while !used.insert(file.clone()) {
+                file = format!("{}-{}.{}", name, number, extension);
+                number += 1;
+            }
+
+            BatchOutput {
+                file,
+                scene: scene.map(String::from),
+                variants: combination.clone(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Image, Layer};
+
+    fn layer(name: &str, images: &[&str]) -> Layer {
+        let mut layer = Layer::empty(name);
+        layer.append_images(
+            images
+                .iter()
+                .map(|image| Image::new(image, None, 1.0, 1.0))
+                .collect(),
+        );
+        layer
+    }
+
+    fn canvas(layers: Vec<Layer>) -> Canvas {
+        let mut canvas = Canvas::new();
+        for layer in layers {
+            canvas.insert_layer(usize::MAX, layer);
+        }
+        canvas
+    }
+
+    fn images(combination: &[Variant]) -> Vec<&str> {
+        combination
+            .iter()
+            .map(|variant| variant.image.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn variant_layers_skip_the_hidden_ones() {
+        let mut outfits = Layer::group("outfits", vec![layer("shirt", &["a.png", "b.png"])]);
+        outfits.set_show(false);
+        let mut cap = layer("cap", &["a.png", "b.png"]);
+        cap.set_show(false);
+        let canvas = canvas(vec![
+            layer("background", &["day.png", "night.png"]),
+            outfits,
+            Layer::group("hats", vec![layer("hat", &["red.png", "blue.png"])]),
+            layer("face", &["smile.png", "cry.png", "wink.png"]),
+            layer("body", &["body.png"]),
+            cap,
+        ]);
+
+        // background, outfits, shirt, hats, hat, face, body, cap.
+        assert_eq!(variant_layers(&canvas), vec![0, 4, 5]);
+    }
+
+    #[test]
+    fn combinations_are_the_cartesian_product() {
+        let canvas = canvas(vec![
+            layer("background", &["day.png", "night.png"]),
+            layer("face", &["smile.png", "cry.png", "wink.png"]),
+        ]);
+
+        let combinations = combinations(&canvas, &[0, 1]).unwrap();
+        assert_eq!(combinations.len(), 6);
+        assert_eq!(images(&combinations[0]), vec!["day.png", "smile.png"]);
+        assert_eq!(images(&combinations[1]), vec!["day.png", "cry.png"]);
+        assert_eq!(images(&combinations[5]), vec!["night.png", "wink.png"]);
+        assert_eq!(combinations[5][1].layer_index, 1);
+        assert_eq!(combinations[5][1].image_index, 2);
+
+        assert_eq!(super::combinations(&canvas, &[]).unwrap(), vec![Vec::new()]);
+        assert!(super::combinations(&canvas, &[2]).is_err());
+    }
+
+    #[test]
+    fn file_names_are_sanitized() {
+        let mut canvas = canvas(vec![layer("face", &["smile.v2.png", "a|b.png"])]);
+        canvas.set_name("hero: a/b");
+        let combinations = combinations(&canvas, &[0]).unwrap();
+
+        let name = |index: usize| {
+            batch_file_name(
+                "{canvas}_{scene}_{face}_{index}",
+                &canvas,
+                index,
+                Some("x*y"),
+                &combinations[index],
+            )
+        };
+        assert_eq!(name(0), "hero_ a_b_x_y_smile.v2_0");
+        assert_eq!(name(1), "hero_ a_b_x_y_a_b_1");
+        assert_eq!(
+            batch_file_name(BATCH_TEMPLATE_DEFAULT, &canvas, 0, None, &combinations[0]),
+            "hero_ a_b_smile.v2"
+        );
+    }
+
+    #[test]
+    fn used_names_are_numbered_until_free() {
+        let canvas = canvas(vec![layer("face", &["a-2.png", "a.png", "a.png"])]);
+        let combinations = combinations(&canvas, &[0]).unwrap();
+
+        let files = |template: &str, scenes: &[String]| -> Vec<String> {
+            batch_outputs(template, &canvas, scenes, &combinations, "png")
+                .into_iter()
+                .map(|output| output.file)
+                .collect()
+        };
+        assert_eq!(
+            files("same", &[]),
+            vec!["same.png", "same-1.png", "same-2.png"]
+        );
+        // `a-2` is taken by an image, the third one is numbered 3.
+        assert_eq!(files("{face}", &[]), vec!["a-2.png", "a.png", "a-3.png"]);
+
+        let scenes = vec![String::from("x"), String::from("y")];
+        assert_eq!(
+            files("{face}", &scenes),
+            vec![
+                "a-2.png",
+                "a.png",
+                "a-3.png",
+                "a-2-3.png",
+                "a-4.png",
+                "a-5.png"
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-20 14:03:29
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-20 16:48:51
+ * @FilePath: /layer-painter/wasm/src/canvas/bundle.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+//! A project bundle is a zip file with the project json
+//! and the original files of its images:
+//!
+//! ```text
+//! project.json
+//! images/<image name>
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Write};
+use std::rc::Rc;
+
+use crate::canvas::Canvas;
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const BUNDLE_PROJECT: &str = "project.json";
+pub const BUNDLE_IMAGES_DIR: &str = "images/";
+
+pub fn is_bundle(data: &[u8]) -> bool {
+    data.starts_with(b"PK\x03\x04")
+}
+
+/// Write `canvas` and the bytes of its images into a bundle,
+/// returns the bundle and the names of the images which have no bytes to store.
+pub fn write_bundle(canvas: &Canvas) -> Result<(Vec<u8>, Vec<String>), String> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut written = HashSet::new();
+    let mut missing = Vec::new();
+
+    let json = serde_json::to_string(canvas).map_err(|err| format!("{}", err))?;
+    zip.start_file(
+        BUNDLE_PROJECT,
+        FileOptions::default().compression_method(CompressionMethod::Deflated),
+    )
+    .map_err(|err| format!("{}", err))?;
+    zip.write_all(json.as_bytes())
+        .map_err(|err| format!("{}", err))?;
+
+    for (_, layer) in canvas.walk_layers() {
+        for image in layer.images().iter() {
+            if written.contains(&image.name()) || missing.contains(&image.name()) {
+                continue;
+            }
+
+            match image.bytes() {
+                Some(bytes) => {
+                    // images are compressed already.
+                    zip.start_file(
+                        format!("{}{}", BUNDLE_IMAGES_DIR, image.name()),
+                        FileOptions::default().compression_method(CompressionMethod::Stored),
+                    )
+                    .map_err(|err| format!("{}", err))?;
+                    zip.write_all(&bytes).map_err(|err| format!("{}", err))?;
+                    written.insert(image.name());
+                }
+                None => missing.push(image.name()),
+            }
+        }
+    }
+
+    let cursor = zip.finish().map_err(|err| format!("{}", err))?;
+    Ok((cursor.into_inner(), missing))
+}
+
+/// Read a bundle, the images of the returned canvas have their bytes set
+/// but no bitmap data, which should be decoded by the caller.
+pub fn read_bundle(data: &[u8]) -> Result<Canvas, String> {
+    let mut zip = ZipArchive::new(Cursor::new(data)).map_err(|err| format!("{}", err))?;
+
+    let json = {
+        let mut file = zip
+            .by_name(BUNDLE_PROJECT)
+            .map_err(|err| format!("{}: {}", BUNDLE_PROJECT, err))?;
+        let mut json = String::new();
+        file.read_to_string(&mut json)
+            .map_err(|err| format!("{}: {}", BUNDLE_PROJECT, err))?;
+        json
+    };
+
+    let mut canvas = Canvas::from_json(json.as_str())?;
+
+    let mut files: HashMap<String, Rc<Vec<u8>>> = HashMap::new();
+    for (_, layer) in canvas.walk_layers() {
+        for image in layer.images().iter() {
+            if files.contains_key(&image.name()) {
+                continue;
+            }
+
+            let path = format!("{}{}", BUNDLE_IMAGES_DIR, image.name());
+            let mut file = match zip.by_name(path.as_str()) {
+                Ok(file) => file,
+                Err(_) => continue,
+            };
+
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)
+                .map_err(|err| format!("{}: {}", path, err))?;
+
+            files.insert(image.name(), Rc::new(bytes));
+        }
+    }
+
+    canvas.for_each_layer_mut(|layer| {
+        for image in layer.get_mut_images().iter_mut() {
+            if let Some(bytes) = files.get(&image.name()) {
+                image.set_bytes(bytes.clone());
+            }
+        }
+    });
+
+    Ok(canvas)
+}
@@ -7,12 +7,15 @@
  * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
  */
 
-use crate::canvas::Layer;
+use crate::canvas::{migrate, Animation, Layer, PlaybackMode, Scene, CURRENT_VERSION};
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Canvas {
+    #[serde(default = "u64_default")]
+    version: u64,
+
     #[serde(default = "string_default")]
     name: String,
 
@@ -24,18 +27,44 @@ pub struct Canvas {
 
     #[serde(default = "vec_default")]
     layers: Vec<Layer>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    scenes: Vec<Scene>,
+
+    #[serde(default)]
+    animation: Animation,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Canvas {
     pub fn new() -> Self {
         Canvas {
+            version: CURRENT_VERSION,
             name: String::from(""),
             width: 800,
             height: 600,
             layers: vec![],
+            scenes: vec![],
+            animation: Animation::default(),
         }
     }
 
+    /// Parse a project json of any supported version.
+    pub fn from_json(data: &str) -> Result<Self, String> {
+        let project = serde_json::from_str(data).map_err(|err| format!("{}", err))?;
+        let project = migrate(project)?;
+        serde_json::from_value(project).map_err(|err| format!("{}", err))
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
     pub fn name(&self) -> String {
         self.name.clone()
     }
@@ -60,10 +89,15 @@ impl Canvas {
         self.height = height;
     }
 
+    /// The top level layers, in render order.
     pub fn layers(&self) -> Vec<Layer> {
         self.layers.clone()
     }
 
+    pub fn get_layers(&self) -> &Vec<Layer> {
+        &self.layers
+    }
+
     pub fn get_mut_layers(&mut self) -> &mut Vec<Layer> {
         &mut self.layers
     }
@@ -72,38 +106,397 @@ impl Canvas {
         self.layers = layers;
     }
 
+    pub fn animation(&self) -> Animation {
+        self.animation
+    }
+
+    pub fn get_mut_animation(&mut self) -> &mut Animation {
+        &mut self.animation
+    }
+
+    /// The number of frames until the animated layers show the same as
+    /// at the first frame again, or until all of them stop.
+    pub fn timeline_len(&self) -> usize {
+        fn gcd(a: usize, b: usize) -> usize {
+            if b == 0 {
+                a
+            } else {
+                gcd(b, a % b)
+            }
+        }
+
+        let mode = self.animation.mode();
+        self.walk_layers()
+            .into_iter()
+            .filter(|(_, layer)| layer.animated())
+            .map(|(_, layer)| mode.cycle_len(layer.frame_offset(), layer.get_images().len()))
+            .fold(1, |len, cycle| match mode {
+                PlaybackMode::Once => len.max(cycle),
+                _ => len / gcd(len, cycle) * cycle,
+            })
+    }
+
+    /// Select the image of every animated layer at the frame `frame` of the timeline.
+    pub fn set_frame(&mut self, frame: usize) {
+        let mode = self.animation.mode();
+        self.for_each_layer_mut(|layer| {
+            if layer.animated() && !layer.get_images().is_empty() {
+                let len = layer.get_images().len();
+                layer.set_selected(mode.image_at(frame, layer.frame_offset(), len));
+            }
+        });
+    }
+
+    pub fn scenes(&self) -> Vec<Scene> {
+        self.scenes.clone()
+    }
+
+    pub fn get_scenes(&self) -> &Vec<Scene> {
+        &self.scenes
+    }
+
+    pub fn get_scene_index(&self, name: &str) -> Option<usize> {
+        self.scenes.iter().position(|scene| scene.name() == name)
+    }
+
+    /// Record the layers as the scene `name`, replacing the scene with the same name.
+    pub fn save_scene(&mut self, name: &str) -> usize {
+        let scene = Scene::capture(name, self);
+        match self.get_scene_index(name) {
+            Some(index) => {
+                self.scenes[index] = scene;
+                index
+            }
+            None => {
+                self.scenes.push(scene);
+                self.scenes.len() - 1
+            }
+        }
+    }
+
+    pub fn apply_scene(&mut self, index: usize) -> bool {
+        match self.scenes.get(index).cloned() {
+            Some(scene) => {
+                scene.apply_to(self);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn delete_scene(&mut self, index: usize) -> Option<Scene> {
+        if index < self.scenes.len() {
+            Some(self.scenes.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Rename the layer at `index`, the scenes follow it.
+    pub fn rename_layer(&mut self, index: usize, name: &str) -> bool {
+        let before = match self.get_mut_layer(index) {
+            Some(layer) => {
+                let before = layer.name();
+                layer.set_name(name);
+                before
+            }
+            None => return false,
+        };
+
+        for scene in self.scenes.iter_mut() {
+            scene.rename_layer(before.as_str(), name);
+        }
+        true
+    }
+
+    // Layers in groups are addressed by their index in the pre-order walk
+    // of the layer tree, a group comes right before its own layers.
+
+    /// Every layer in the tree with its depth, in the pre-order walk.
+    pub fn walk_layers(&self) -> Vec<(usize, &Layer)> {
+        fn walk<'a>(layers: &'a [Layer], depth: usize, result: &mut Vec<(usize, &'a Layer)>) {
+            for layer in layers.iter() {
+                result.push((depth, layer));
+                walk(layer.get_layers(), depth + 1, result);
+            }
+        }
+
+        let mut result = Vec::new();
+        walk(&self.layers, 0, &mut result);
+        result
+    }
+
+    /// Call `f` on every layer in the tree, in the pre-order walk.
+    pub fn for_each_layer_mut<F: FnMut(&mut Layer)>(&mut self, mut f: F) {
+        fn walk<F: FnMut(&mut Layer)>(layers: &mut [Layer], f: &mut F) {
+            for layer in layers.iter_mut() {
+                f(layer);
+                if let Some(layers) = layer.get_mut_layers() {
+                    walk(layers, f);
+                }
+            }
+        }
+
+        walk(&mut self.layers, &mut f);
+    }
+
+    /// The path of child indices from the top level to the layer.
+    pub fn get_layer_path(&self, index: usize) -> Option<Vec<usize>> {
+        fn find(layers: &[Layer], mut index: usize, path: &mut Vec<usize>) -> bool {
+            for (i, layer) in layers.iter().enumerate() {
+                let size = layer.get_tree_len();
+                if index < size {
+                    path.push(i);
+                    return index == 0 || find(layer.get_layers(), index - 1, path);
+                }
+                index -= size;
+            }
+            false
+        }
+
+        let mut path = Vec::new();
+        if find(&self.layers, index, &mut path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_layer_index_by_path(&self, path: &[usize]) -> Option<usize> {
+        let mut index = 0;
+        let mut layers = self.layers.as_slice();
+
+        for (depth, i) in path.iter().enumerate() {
+            let layer = layers.get(*i)?;
+            index += layers[..*i]
+                .iter()
+                .map(|layer| layer.get_tree_len())
+                .sum::<usize>();
+            if depth > 0 {
+                // the group itself.
+                index += 1;
+            }
+            layers = layer.get_layers();
+        }
+
+        Some(index)
+    }
+
+    fn get_siblings_mut(&mut self, parent_path: &[usize]) -> Option<&mut Vec<Layer>> {
+        let mut layers = &mut self.layers;
+        for i in parent_path.iter() {
+            layers = layers.get_mut(*i)?.get_mut_layers()?;
+        }
+        Some(layers)
+    }
+
     pub fn append_layer(&mut self, layer: Layer) -> &Self {
         self.layers.push(layer);
         self
     }
 
+    /// Insert `layer` before the layer at `index`, in the same group.
     pub fn insert_layer(&mut self, index: usize, layer: Layer) -> &Self {
-        self.layers.insert(index, layer);
+        match self.get_layer_path(index) {
+            Some(path) => {
+                let (i, parent_path) = path.split_last().unwrap();
+                let siblings = self.get_siblings_mut(parent_path).unwrap();
+                siblings.insert(*i, layer);
+            }
+            None => self.layers.push(layer),
+        }
+        self
+    }
+
+    /// Insert `layer` right above the layer at `index`, in the same group.
+    pub fn insert_layer_above(&mut self, index: usize, layer: Layer) -> &Self {
+        match self.get_layer_path(index) {
+            Some(path) => {
+                let (i, parent_path) = path.split_last().unwrap();
+                let siblings = self.get_siblings_mut(parent_path).unwrap();
+                siblings.insert(*i + 1, layer);
+            }
+            None => self.layers.push(layer),
+        }
         self
     }
 
     pub fn get_layer_index(&self, name: &str) -> Option<usize> {
-        self.layers.iter().position(|layer| layer.name() == name)
+        self.walk_layers()
+            .iter()
+            .position(|(_, layer)| layer.name() == name)
     }
 
     pub fn get_layer(&self, index: usize) -> Option<&Layer> {
-        self.layers.get(index)
+        let path = self.get_layer_path(index)?;
+        let mut layer = self.layers.get(path[0])?;
+        for i in path[1..].iter() {
+            layer = layer.get_layers().get(*i)?;
+        }
+        Some(layer)
     }
 
     pub fn get_mut_layer(&mut self, index: usize) -> Option<&mut Layer> {
-        self.layers.get_mut(index)
+        let path = self.get_layer_path(index)?;
+        let mut layer = self.layers.get_mut(path[0])?;
+        for i in path[1..].iter() {
+            layer = layer.get_mut_layers()?.get_mut(*i)?;
+        }
+        Some(layer)
+    }
+
+    /// The index of the group which contains the layer at `index`.
+    pub fn get_parent_index(&self, index: usize) -> Option<usize> {
+        let path = self.get_layer_path(index)?;
+        if path.len() < 2 {
+            return None;
+        }
+        self.get_layer_index_by_path(&path[..path.len() - 1])
     }
 
-    pub fn delete_layer(&mut self, index: usize) {
-        self.layers.remove(index);
+    /// Remove the layer at `index` with all its layers if it's a group.
+    pub fn delete_layer(&mut self, index: usize) -> Option<Layer> {
+        let path = self.get_layer_path(index)?;
+        let (i, parent_path) = path.split_last().unwrap();
+        let siblings = self.get_siblings_mut(parent_path)?;
+        Some(siblings.remove(*i))
     }
 
+    /// The count of all the layers in the tree.
     pub fn get_layers_len(&self) -> usize {
-        self.layers.len()
+        self.layers.iter().map(|layer| layer.get_tree_len()).sum()
     }
 
+    pub fn is_same_group(&self, src_layer_index: usize, dst_layer_index: usize) -> bool {
+        match (
+            self.get_layer_path(src_layer_index),
+            self.get_layer_path(dst_layer_index),
+        ) {
+            (Some(src), Some(dst)) => src[..src.len() - 1] == dst[..dst.len() - 1],
+            _ => false,
+        }
+    }
+
+    /// Whether the layer at `index` is `ancestor_index` or inside it.
+    pub fn is_in_layer(&self, index: usize, ancestor_index: usize) -> bool {
+        match (
+            self.get_layer_path(index),
+            self.get_layer_path(ancestor_index),
+        ) {
+            (Some(path), Some(ancestor)) => path.starts_with(&ancestor),
+            _ => false,
+        }
+    }
+
+    /// Swap two layers in the same group, with their own layers if they are groups.
     pub fn swap_layer(&mut self, src_layer_index: usize, dst_layer_index: usize) {
-        self.layers.swap(src_layer_index, dst_layer_index);
+        if !self.is_same_group(src_layer_index, dst_layer_index) {
+            return;
+        }
+
+        let src = self.get_layer_path(src_layer_index).unwrap();
+        let dst = self.get_layer_path(dst_layer_index).unwrap();
+        let (src_i, parent_path) = src.split_last().unwrap();
+        let dst_i = dst.last().unwrap();
+
+        let siblings = self.get_siblings_mut(parent_path).unwrap();
+        siblings.swap(*src_i, *dst_i);
+    }
+
+    /// Move the layer at `src_layer_index` to the top of the group at `group_index`.
+    pub fn move_layer_into_group(&mut self, src_layer_index: usize, group_index: usize) -> bool {
+        match self.get_layer(group_index) {
+            Some(group) if group.is_group() => {}
+            _ => return false,
+        }
+
+        self.move_layer(src_layer_index, group_index, |group_path| {
+            let mut path = group_path.to_vec();
+            path.push(usize::MAX);
+            path
+        })
+    }
+
+    /// Move the layer at `src_layer_index` right above the layer at `dst_layer_index`,
+    /// into the group of the latter.
+    pub fn move_layer_above(&mut self, src_layer_index: usize, dst_layer_index: usize) -> bool {
+        self.move_layer(src_layer_index, dst_layer_index, |dst_path| {
+            let mut path = dst_path.to_vec();
+            *path.last_mut().unwrap() += 1;
+            path
+        })
+    }
+
+    /// Replace the layer at `index` by a new group which contains it.
+    pub fn group_layer(&mut self, index: usize, group_name: &str) -> bool {
+        let path = match self.get_layer_path(index) {
+            Some(path) => path,
+            None => return false,
+        };
+
+        let (i, parent_path) = path.split_last().unwrap();
+        let siblings = self.get_siblings_mut(parent_path).unwrap();
+        let layer = siblings.remove(*i);
+        siblings.insert(*i, Layer::group(group_name, vec![layer]));
+
+        true
+    }
+
+    /// Replace the group at `index` by its own layers.
+    pub fn ungroup_layer(&mut self, index: usize) -> bool {
+        match self.get_layer(index) {
+            Some(layer) if layer.is_group() => {}
+            _ => return false,
+        }
+
+        let path = self.get_layer_path(index).unwrap();
+        let (i, parent_path) = path.split_last().unwrap();
+        let siblings = self.get_siblings_mut(parent_path).unwrap();
+        let group = siblings.remove(*i);
+        for (offset, layer) in group.layers().into_iter().enumerate() {
+            siblings.insert(i + offset, layer);
+        }
+
+        true
+    }
+
+    // Remove the layer at `src_layer_index` and insert it at the path made by `to`
+    // from the path of `dst_layer_index`, a last part of `usize::MAX` appends it.
+    fn move_layer<F: Fn(&[usize]) -> Vec<usize>>(
+        &mut self,
+        src_layer_index: usize,
+        dst_layer_index: usize,
+        to: F,
+    ) -> bool {
+        if self.is_in_layer(dst_layer_index, src_layer_index) {
+            return false;
+        }
+
+        let (src_path, mut dst_path) = match (
+            self.get_layer_path(src_layer_index),
+            self.get_layer_path(dst_layer_index),
+        ) {
+            (Some(src), Some(dst)) => (src, dst),
+            _ => return false,
+        };
+
+        // the removal shifts the later siblings of the source forward.
+        let depth = src_path.len() - 1;
+        if dst_path.len() > depth
+            && dst_path[..depth] == src_path[..depth]
+            && dst_path[depth] > src_path[depth]
+        {
+            dst_path[depth] -= 1;
+        }
+
+        let layer = self.delete_layer(src_layer_index).unwrap();
+
+        let path = to(&dst_path);
+        let (i, parent_path) = path.split_last().unwrap();
+        let siblings = self.get_siblings_mut(parent_path).unwrap();
+        siblings.insert((*i).min(siblings.len()), layer);
+
+        true
     }
 }
 
@@ -118,3 +511,153 @@ fn vec_default() -> Vec<Layer> {
 fn usize_default() -> usize {
     0
 }
+
+fn u64_default() -> u64 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Shape, ShapeKind, Text};
+
+    fn names(canvas: &Canvas) -> Vec<(usize, String)> {
+        canvas
+            .walk_layers()
+            .into_iter()
+            .map(|(depth, layer)| (depth, layer.name()))
+            .collect()
+    }
+
+    // a, group [b, c], d
+    fn tree() -> Canvas {
+        let mut canvas = Canvas::new();
+        canvas.append_layer(Layer::empty("a"));
+        canvas.append_layer(Layer::group(
+            "group",
+            vec![Layer::empty("b"), Layer::empty("c")],
+        ));
+        canvas.append_layer(Layer::empty("d"));
+        canvas
+    }
+
+    #[test]
+    fn walk_is_pre_order() {
+        let canvas = tree();
+        assert_eq!(canvas.get_layers_len(), 5);
+        assert_eq!(
+            names(&canvas),
+            vec![
+                (0, String::from("a")),
+                (0, String::from("group")),
+                (1, String::from("b")),
+                (1, String::from("c")),
+                (0, String::from("d")),
+            ]
+        );
+    }
+
+    #[test]
+    fn paths_and_indexes() {
+        let canvas = tree();
+        for index in 0..canvas.get_layers_len() {
+            let path = canvas.get_layer_path(index).unwrap();
+            assert_eq!(canvas.get_layer_index_by_path(&path), Some(index));
+        }
+        assert_eq!(canvas.get_layer_path(3), Some(vec![1, 1]));
+        assert_eq!(canvas.get_layer_path(5), None);
+        assert_eq!(canvas.get_layer(3).unwrap().name(), "c");
+        assert_eq!(canvas.get_parent_index(3), Some(1));
+        assert_eq!(canvas.get_parent_index(4), None);
+        assert!(canvas.is_same_group(2, 3));
+        assert!(!canvas.is_same_group(0, 2));
+        assert!(canvas.is_in_layer(3, 1));
+    }
+
+    #[test]
+    fn insert_and_delete_in_groups() {
+        let mut canvas = tree();
+        canvas.insert_layer(3, Layer::empty("e"));
+        assert_eq!(canvas.get_layer(3).unwrap().name(), "e");
+        assert_eq!(canvas.get_parent_index(3), Some(1));
+
+        // above the group, not inside it.
+        canvas.insert_layer_above(1, Layer::empty("f"));
+        assert_eq!(canvas.get_layer(5).unwrap().name(), "f");
+        assert_eq!(canvas.get_parent_index(5), None);
+        canvas.insert_layer_above(4, Layer::empty("g"));
+        assert_eq!(canvas.get_layer(5).unwrap().name(), "g");
+        assert_eq!(canvas.get_parent_index(5), Some(1));
+
+        let group = canvas.delete_layer(1).unwrap();
+        assert_eq!(group.get_tree_len(), 5);
+        assert_eq!(canvas.get_layers_len(), 3);
+    }
+
+    #[test]
+    fn move_layers_between_groups() {
+        let mut canvas = tree();
+        assert!(canvas.move_layer_into_group(0, 1));
+        assert_eq!(canvas.get_layer_index("a"), Some(3));
+        assert_eq!(canvas.get_parent_index(3), Some(0));
+
+        // a group can't move into itself.
+        assert!(!canvas.move_layer_into_group(0, 0));
+
+        let d = canvas.get_layer_index("d").unwrap();
+        let b = canvas.get_layer_index("b").unwrap();
+        assert!(canvas.move_layer_above(d, b));
+        assert_eq!(
+            names(&canvas),
+            vec![
+                (0, String::from("group")),
+                (1, String::from("b")),
+                (1, String::from("d")),
+                (1, String::from("c")),
+                (1, String::from("a")),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_and_ungroup() {
+        let mut canvas = tree();
+        assert!(canvas.group_layer(4, "outer"));
+        assert_eq!(canvas.get_layer(4).unwrap().name(), "outer");
+        assert_eq!(canvas.get_layer(5).unwrap().name(), "d");
+
+        assert!(canvas.ungroup_layer(1));
+        assert_eq!(canvas.get_layers_len(), 5);
+        assert_eq!(canvas.get_layer(1).unwrap().name(), "b");
+        assert!(!canvas.ungroup_layer(0));
+    }
+
+    #[test]
+    fn kinds_of_layers_roundtrip() {
+        let mut canvas = tree();
+        canvas.append_layer(Layer::text("text", Text::default()));
+        canvas.append_layer(Layer::shape("shape", Shape::new(ShapeKind::Ellipse)));
+        canvas.get_mut_layer(1).unwrap().set_collapsed(true);
+
+        let json = serde_json::to_string(&canvas).unwrap();
+        let loaded = Canvas::from_json(json.as_str()).unwrap();
+        assert_eq!(names(&loaded), names(&canvas));
+        assert!(loaded.get_layer(1).unwrap().collapsed());
+        assert_eq!(
+            loaded.get_layer(5).unwrap().get_text(),
+            Some(&Text::default())
+        );
+        assert_eq!(
+            loaded
+                .get_layer(6)
+                .unwrap()
+                .get_shape()
+                .map(|shape| shape.kind),
+            Some(ShapeKind::Ellipse)
+        );
+        // only the groups are collapsed.
+        let mut text = loaded.get_layer(5).unwrap().clone();
+        text.set_collapsed(true);
+        assert!(!text.collapsed() && text.get_mut_layers().is_none());
+    }
+}
@@ -8,15 +8,24 @@
  */
 
 use std::fmt;
+use std::rc::Rc;
 
 use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
 use serde::ser::{Serialize, SerializeStruct, Serializer};
 use web_sys::ImageBitmap;
 
+use crate::canvas::content_hash;
+
 #[derive(Debug, Clone)]
 pub struct Image {
     name: String,
     data: Option<ImageBitmap>,
+    // the original file, kept to be stored in a project bundle.
+    bytes: Option<Rc<Vec<u8>>>,
+    // the source file, relative to the project file in the json.
+    path: Option<String>,
+    // the hash of the bytes, to find the file after it's moved.
+    hash: Option<String>,
     sx: f64,
     sy: f64,
     sw: f64,
@@ -28,6 +37,9 @@ impl Image {
         Image {
             name: String::new(),
             data: None,
+            bytes: None,
+            path: None,
+            hash: None,
             sx: 0.0,
             sy: 0.0,
             sw: 0.0,
@@ -39,6 +51,9 @@ impl Image {
         Image {
             name: String::from(name),
             data,
+            bytes: None,
+            path: None,
+            hash: None,
             sx: 0.0,
             sy: 0.0,
             sw,
@@ -62,6 +77,27 @@ impl Image {
         self.data = Some(data.clone());
     }
 
+    pub fn bytes(&self) -> Option<Rc<Vec<u8>>> {
+        self.bytes.clone()
+    }
+
+    pub fn set_bytes(&mut self, bytes: Rc<Vec<u8>>) {
+        self.hash = Some(content_hash(&bytes));
+        self.bytes = Some(bytes);
+    }
+
+    pub fn path(&self) -> Option<String> {
+        self.path.clone()
+    }
+
+    pub fn set_path(&mut self, path: Option<String>) {
+        self.path = path;
+    }
+
+    pub fn hash(&self) -> Option<String> {
+        self.hash.clone()
+    }
+
     pub fn sx(&self) -> f64 {
         self.sx
     }
@@ -100,8 +136,10 @@ impl Serialize for Image {
     where
         S: Serializer,
     {
-        let mut s = serializer.serialize_struct("Image", 5)?;
+        let mut s = serializer.serialize_struct("Image", 7)?;
         s.serialize_field("name", &self.name)?;
+        s.serialize_field("path", &self.path)?;
+        s.serialize_field("hash", &self.hash)?;
         s.serialize_field("sx", &self.sx)?;
         s.serialize_field("sy", &self.sy)?;
         s.serialize_field("sw", &self.sw)?;
@@ -117,6 +155,8 @@ impl<'de> Deserialize<'de> for Image {
     {
         enum Field {
             Name,
+            Path,
+            Hash,
             Sx,
             Sy,
             Sw,
@@ -133,7 +173,7 @@ impl<'de> Deserialize<'de> for Image {
                     type Value = Field;
 
                     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                        formatter.write_str("`name`, `sx`, `sy`, `sw` or `sh`")
+                        formatter.write_str("`name`, `path`, `hash`, `sx`, `sy`, `sw` or `sh`")
                     }
 
                     fn visit_str<E>(self, value: &str) -> Result<Field, E>
@@ -142,6 +182,8 @@ impl<'de> Deserialize<'de> for Image {
                     {
                         match value {
                             "name" => Ok(Field::Name),
+                            "path" => Ok(Field::Path),
+                            "hash" => Ok(Field::Hash),
                             "sx" => Ok(Field::Sx),
                             "sy" => Ok(Field::Sy),
                             "sw" => Ok(Field::Sw),
@@ -168,33 +210,25 @@ impl<'de> Deserialize<'de> for Image {
             where
                 V: SeqAccess<'de>,
             {
-                let name = match seq.next_element().unwrap() {
-                    Some(name) => name,
-                    None => String::from("")
-                };
-
-                let sx = match seq.next_element().unwrap() {
-                    Some(sx) => sx,
-                    None => 0.0
-                };
-
-                let sy = match seq.next_element().unwrap() {
-                    Some(sy) => sy,
-                    None => 0.0
-                };
-
-                let sw = match seq.next_element().unwrap() {
-                    Some(sw) => sw,
-                    None => 0.0
-                };
-                let sh = match seq.next_element().unwrap() {
-                    Some(sh) => sh,
-                    None => 0.0
-                };
+                let name = seq.next_element().unwrap().unwrap_or_default();
+
+                let path = seq.next_element().unwrap().unwrap_or_default();
+
+                let hash = seq.next_element().unwrap().unwrap_or_default();
+
+                let sx = seq.next_element().unwrap().unwrap_or(0.0);
+
+                let sy = seq.next_element().unwrap().unwrap_or(0.0);
+
+                let sw = seq.next_element().unwrap().unwrap_or(0.0);
+                let sh = seq.next_element().unwrap().unwrap_or(0.0);
 
                 Ok(Image {
                     name,
                     data: None,
+                    bytes: None,
+                    path,
+                    hash,
                     sx,
                     sy,
                     sw,
@@ -207,6 +241,8 @@ impl<'de> Deserialize<'de> for Image {
                 V: MapAccess<'de>,
             {
                 let mut name = None;
+                let mut path = None;
+                let mut hash = None;
                 let mut sx = None;
                 let mut sy = None;
                 let mut sw = None;
@@ -221,6 +257,20 @@ impl<'de> Deserialize<'de> for Image {
                             name = Some(map.next_value()?);
                         }
 
+                        Field::Path => {
+                            if path.is_some() {
+                                return Err(de::Error::duplicate_field("path"));
+                            }
+                            path = Some(map.next_value()?);
+                        }
+
+                        Field::Hash => {
+                            if hash.is_some() {
+                                return Err(de::Error::duplicate_field("hash"));
+                            }
+                            hash = Some(map.next_value()?);
+                        }
+
                         Field::Sx => {
                             if sx.is_some() {
                                 return Err(de::Error::duplicate_field("sx"));
@@ -248,34 +298,26 @@ impl<'de> Deserialize<'de> for Image {
                         }
                     }
                 }
-                let name = match name {
-                    Some(name) => name,
-                    None => String::from("")
-                };
-
-                let sx = match sx {
-                    Some(sx) => sx,
-                    None => 0.0
-                };
-
-                let sy = match sy {
-                    Some(sy) => sy,
-                    None => 0.0
-                };
-                
-                let sw = match sw {
-                    Some(sw) => sw,
-                    None => 0.0
-                };
-                
-                let sh = match sh {
-                    Some(sh) => sh,
-                    None => 0.0
-                };
-                
+                let name = name.unwrap_or_default();
+
+                let path = path.unwrap_or_default();
+
+                let hash = hash.unwrap_or_default();
+
+                let sx = sx.unwrap_or(0.0);
+
+                let sy = sy.unwrap_or(0.0);
+
+                let sw = sw.unwrap_or(0.0);
+
+                let sh = sh.unwrap_or(0.0);
+
                 Ok(Image {
                     name,
                     data: None,
+                    bytes: None,
+                    path,
+                    hash,
                     sx,
                     sy,
                     sw,
@@ -284,7 +326,7 @@ impl<'de> Deserialize<'de> for Image {
             }
         }
 
-        const FIELDS: &'static [&'static str] = &["name", "sx", "sy", "sw", "sh"];
+        const FIELDS: &[&str] = &["name", "path", "hash", "sx", "sy", "sw", "sh"];
         deserializer.deserialize_struct("Image", FIELDS, ImageVisitor)
     }
 }
@@ -7,10 +7,31 @@
  * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
  */
 
-use crate::canvas::Image;
+use crate::canvas::{BlendMode, Image, Mask, ScaleMode, Shape, Text, Transform};
 
 use serde::{Deserialize, Serialize};
 
+/// What a layer draws, its selected image, its own layers, a text or a shape.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum LayerKind {
+    #[default]
+    Images,
+    Group(Group),
+    Text(Text),
+    Shape(Shape),
+}
+
+/// The layers of a group, in render order.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Group {
+    #[serde(default)]
+    pub collapsed: bool,
+
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Layer {
     #[serde(default = "string_default")]
@@ -25,12 +46,18 @@ pub struct Layer {
     #[serde(default = "bool_default")]
     show: bool,
 
-    #[serde(default = "bool_default")]
-    fit: bool,
+    #[serde(default)]
+    scale: ScaleMode,
 
     #[serde(default = "f64_default")]
     opacity: f64,
 
+    #[serde(default)]
+    blend: BlendMode,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mask: Option<Mask>,
+
     #[serde(default = "f64_default")]
     dx: f64,
 
@@ -42,6 +69,30 @@ pub struct Layer {
 
     #[serde(default = "f64_default")]
     dh: f64,
+
+    #[serde(default = "f64_default")]
+    rotation: f64,
+
+    #[serde(default)]
+    flip_h: bool,
+
+    #[serde(default)]
+    flip_v: bool,
+
+    #[serde(default = "anchor_default")]
+    anchor_x: f64,
+
+    #[serde(default = "anchor_default")]
+    anchor_y: f64,
+
+    #[serde(default)]
+    animated: bool,
+
+    #[serde(default = "usize_default")]
+    frame_offset: usize,
+
+    #[serde(default)]
+    kind: LayerKind,
 }
 
 impl Layer {
@@ -51,15 +102,51 @@ impl Layer {
             selected: 0,
             images: Vec::new(),
             show: true,
-            fit: true,
+            scale: ScaleMode::Contain,
             opacity: 1.0,
+            blend: BlendMode::Normal,
+            mask: None,
             dx: 0.0,
             dy: 0.0,
             dw: 0.0,
             dh: 0.0,
+            rotation: 0.0,
+            flip_h: false,
+            flip_v: false,
+            anchor_x: 0.5,
+            anchor_y: 0.5,
+            animated: false,
+            frame_offset: 0,
+            kind: LayerKind::Images,
         }
     }
 
+    /// A group of layers, rendered together and then composited as one layer.
+    pub fn group(name: &str, layers: Vec<Layer>) -> Self {
+        let mut group = Self::empty(name);
+        group.kind = LayerKind::Group(Group {
+            collapsed: false,
+            layers,
+        });
+        group
+    }
+
+    /// A layer drawing `text` instead of images, in its own size.
+    pub fn text(name: &str, text: Text) -> Self {
+        let mut layer = Self::empty(name);
+        layer.scale = ScaleMode::None;
+        layer.kind = LayerKind::Text(text);
+        layer
+    }
+
+    /// A layer drawing `shape` instead of images, in its own size.
+    pub fn shape(name: &str, shape: Shape) -> Self {
+        let mut layer = Self::empty(name);
+        layer.scale = ScaleMode::None;
+        layer.kind = LayerKind::Shape(shape);
+        layer
+    }
+
     pub fn name(&self) -> String {
         self.name.clone()
     }
@@ -85,8 +172,8 @@ impl Layer {
     }
 
     pub fn prev(&mut self) {
-        if self.images().len() == 0 {
-            return
+        if self.images().is_empty() {
+            return;
         }
 
         if self.selected == 0 {
@@ -97,10 +184,10 @@ impl Layer {
     }
 
     pub fn next(&mut self) {
-        if self.images().len() == 0 {
-            return
+        if self.images().is_empty() {
+            return;
         }
-        
+
         if self.selected == self.images.len() - 1 {
             self.selected = 0;
         } else {
@@ -112,6 +199,10 @@ impl Layer {
         self.images.clone()
     }
 
+    pub fn get_images(&self) -> &Vec<Image> {
+        &self.images
+    }
+
     pub fn get_mut_images(&mut self) -> &mut Vec<Image> {
         &mut self.images
     }
@@ -123,12 +214,12 @@ impl Layer {
         self.show = show;
     }
 
-    pub fn fit(&self) -> bool {
-        self.fit
+    pub fn scale(&self) -> ScaleMode {
+        self.scale
     }
 
-    pub fn set_fit(&mut self, fit: bool) {
-        self.fit = fit;
+    pub fn set_scale(&mut self, scale: ScaleMode) {
+        self.scale = scale;
     }
 
     pub fn opacity(&self) -> f64 {
@@ -145,8 +236,28 @@ impl Layer {
         }
     }
 
+    pub fn blend(&self) -> BlendMode {
+        self.blend
+    }
+
+    pub fn set_blend(&mut self, blend: BlendMode) {
+        self.blend = blend;
+    }
+
+    pub fn mask(&self) -> Option<&Mask> {
+        self.mask.as_ref()
+    }
+
+    pub fn get_mut_mask(&mut self) -> Option<&mut Mask> {
+        self.mask.as_mut()
+    }
+
+    pub fn set_mask(&mut self, mask: Option<Mask>) {
+        self.mask = mask;
+    }
+
     pub fn dx(&self) -> f64 {
-        return self.dx;
+        self.dx
     }
 
     pub fn dy(&self) -> f64 {
@@ -177,6 +288,136 @@ impl Layer {
         self.dh = dh;
     }
 
+    /// Whether the images are played as frames on the timeline.
+    pub fn animated(&self) -> bool {
+        self.animated
+    }
+
+    pub fn set_animated(&mut self, animated: bool) {
+        self.animated = animated;
+    }
+
+    /// The image shown at the first frame of the timeline.
+    pub fn frame_offset(&self) -> usize {
+        self.frame_offset
+    }
+
+    pub fn set_frame_offset(&mut self, frame_offset: usize) {
+        self.frame_offset = frame_offset;
+    }
+
+    pub fn kind(&self) -> &LayerKind {
+        &self.kind
+    }
+
+    pub fn is_group(&self) -> bool {
+        matches!(self.kind, LayerKind::Group(_))
+    }
+
+    pub fn collapsed(&self) -> bool {
+        match &self.kind {
+            LayerKind::Group(group) => group.collapsed,
+            _ => false,
+        }
+    }
+
+    /// Only a group can be collapsed.
+    pub fn set_collapsed(&mut self, collapsed: bool) {
+        if let LayerKind::Group(group) = &mut self.kind {
+            group.collapsed = collapsed;
+        }
+    }
+
+    pub fn is_text(&self) -> bool {
+        matches!(self.kind, LayerKind::Text(_))
+    }
+
+    pub fn get_text(&self) -> Option<&Text> {
+        match &self.kind {
+            LayerKind::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut_text(&mut self) -> Option<&mut Text> {
+        match &mut self.kind {
+            LayerKind::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn is_shape(&self) -> bool {
+        matches!(self.kind, LayerKind::Shape(_))
+    }
+
+    pub fn get_shape(&self) -> Option<&Shape> {
+        match &self.kind {
+            LayerKind::Shape(shape) => Some(shape),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut_shape(&mut self) -> Option<&mut Shape> {
+        match &mut self.kind {
+            LayerKind::Shape(shape) => Some(shape),
+            _ => None,
+        }
+    }
+
+    /// The layers of a group, in render order, the other layers have none.
+    pub fn layers(&self) -> Vec<Layer> {
+        self.get_layers().to_vec()
+    }
+
+    pub fn get_layers(&self) -> &[Layer] {
+        match &self.kind {
+            LayerKind::Group(group) => &group.layers,
+            _ => &[],
+        }
+    }
+
+    pub fn get_mut_layers(&mut self) -> Option<&mut Vec<Layer>> {
+        match &mut self.kind {
+            LayerKind::Group(group) => Some(&mut group.layers),
+            _ => None,
+        }
+    }
+
+    /// The count of this layer and all the layers in it.
+    pub fn get_tree_len(&self) -> usize {
+        1 + self
+            .get_layers()
+            .iter()
+            .map(|layer| layer.get_tree_len())
+            .sum::<usize>()
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform {
+            dx: self.dx,
+            dy: self.dy,
+            dw: self.dw,
+            dh: self.dh,
+            rotation: self.rotation,
+            flip_h: self.flip_h,
+            flip_v: self.flip_v,
+            anchor_x: self.anchor_x,
+            anchor_y: self.anchor_y,
+        }
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.dx = transform.dx;
+        self.dy = transform.dy;
+        self.dw = transform.dw;
+        self.dh = transform.dh;
+        self.rotation = transform.rotation % 360.0;
+        self.flip_h = transform.flip_h;
+        self.flip_v = transform.flip_v;
+        self.anchor_x = transform.anchor_x;
+        self.anchor_y = transform.anchor_y;
+    }
+
     pub fn append_image(&mut self, image: Image) {
         self.images.push(image);
     }
@@ -190,8 +431,10 @@ impl Layer {
     }
 
     pub fn remove_selected_image(&mut self) -> Result<Image, String> {
-        if self.images().len() == 0 {
-            return Err(String::from("select layer can't remove image, images len is 0"));
+        if self.images().is_empty() {
+            return Err(String::from(
+                "select layer can't remove image, images len is 0",
+            ));
         }
 
         let image = self.images.remove(self.selected);
@@ -222,3 +465,7 @@ fn bool_default() -> bool {
 fn f64_default() -> f64 {
     0.0
 }
+
+fn anchor_default() -> f64 {
+    0.5
+}
@@ -0,0 +1,304 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-25 19:12:40
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-25 23:06:17
+ * @FilePath: /layer-painter/wasm/src/canvas/mask.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use std::convert::TryFrom;
+
+use serde::{Deserialize, Serialize};
+
+/// Which channel of an imported image is used as the coverage of a mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MaskMode {
+    Luminance,
+    Alpha,
+}
+
+impl MaskMode {
+    pub const ALL: [MaskMode; 2] = [MaskMode::Luminance, MaskMode::Alpha];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MaskMode::Luminance => "luminance",
+            MaskMode::Alpha => "alpha",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        MaskMode::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.name() == name)
+    }
+}
+
+/// The coverage of a layer, 0 hides and 255 shows.
+/// It's stretched over the drawn rect of the selected image,
+/// or over the whole canvas for a group.
+/// Saved in the project as a base64 grayscale png.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "MaskJson", into = "MaskJson")]
+pub struct Mask {
+    enabled: bool,
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl Mask {
+    /// A mask which shows everything.
+    pub fn new(width: usize, height: usize) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+
+        Self {
+            enabled: true,
+            width,
+            height,
+            data: vec![255; width * height],
+        }
+    }
+
+    /// Make a mask from straight rgba pixels.
+    pub fn from_rgba(
+        width: usize,
+        height: usize,
+        rgba: &[u8],
+        mode: MaskMode,
+    ) -> Result<Self, String> {
+        if width == 0 || height == 0 || rgba.len() != width * height * 4 {
+            return Err(format!(
+                "the mask data (len {}) doesn't match the size {}x{}",
+                rgba.len(),
+                width,
+                height
+            ));
+        }
+
+        let data = rgba
+            .chunks_exact(4)
+            .map(|pixel| match mode {
+                MaskMode::Luminance => {
+                    let lum =
+                        0.3 * pixel[0] as f32 + 0.59 * pixel[1] as f32 + 0.11 * pixel[2] as f32;
+                    // transparent pixels hide.
+                    (lum * pixel[3] as f32 / 255.0).round() as u8
+                }
+                MaskMode::Alpha => pixel[3],
+            })
+            .collect();
+
+        Ok(Self {
+            enabled: true,
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The bilinear sampled coverage in 0.0 ~ 1.0 at the normalized point (`nx`, `ny`),
+    /// points outside the mask are hidden.
+    pub fn coverage(&self, nx: f64, ny: f64) -> f32 {
+        if !(0.0..=1.0).contains(&nx) || !(0.0..=1.0).contains(&ny) {
+            return 0.0;
+        }
+
+        let x = (nx * self.width as f64 - 0.5).clamp(0.0, (self.width - 1) as f64);
+        let y = (ny * self.height as f64 - 0.5).clamp(0.0, (self.height - 1) as f64);
+        let x0 = x.floor() as usize;
+        let y0 = y.floor() as usize;
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+        let fx = (x - x0 as f64) as f32;
+        let fy = (y - y0 as f64) as f32;
+
+        let value = |x: usize, y: usize| self.data[y * self.width + x] as f32;
+        let top = value(x0, y0) * (1.0 - fx) + value(x1, y0) * fx;
+        let bottom = value(x0, y1) * (1.0 - fx) + value(x1, y1) * fx;
+
+        (top * (1.0 - fy) + bottom * fy) / 255.0
+    }
+
+    /// Paint a round dab at the normalized point (`nx`, `ny`),
+    /// `radius` is normalized by the width and the height of the mask.
+    pub fn paint(&mut self, nx: f64, ny: f64, radius: (f64, f64), value: u8) {
+        let cx = nx * self.width as f64;
+        let cy = ny * self.height as f64;
+        let rx = (radius.0 * self.width as f64).max(0.5);
+        let ry = (radius.1 * self.height as f64).max(0.5);
+
+        let x_start = (cx - rx).floor().max(0.0) as usize;
+        let y_start = (cy - ry).floor().max(0.0) as usize;
+        let x_end = ((cx + rx).ceil().max(0.0) as usize).min(self.width);
+        let y_end = ((cy + ry).ceil().max(0.0) as usize).min(self.height);
+
+        for y in y_start..y_end {
+            let dy = (y as f64 + 0.5 - cy) / ry;
+            for x in x_start..x_end {
+                let dx = (x as f64 + 0.5 - cx) / rx;
+                if dx * dx + dy * dy <= 1.0 {
+                    self.data[y * self.width + x] = value;
+                }
+            }
+        }
+    }
+
+    fn to_png(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().map_err(|err| format!("{}", err))?;
+            writer
+                .write_image_data(&self.data)
+                .map_err(|err| format!("{}", err))?;
+        }
+        Ok(bytes)
+    }
+
+    fn from_png(bytes: &[u8]) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(|err| format!("{}", err))?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buffer)
+            .map_err(|err| format!("{}", err))?;
+
+        let width = info.width as usize;
+        let height = info.height as usize;
+        let channels = info.color_type.samples();
+        let data = buffer[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|pixel| pixel[0])
+            .collect();
+
+        Ok(Self {
+            enabled: true,
+            width,
+            height,
+            data,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct MaskJson {
+    #[serde(default = "bool_default")]
+    enabled: bool,
+    data: String,
+}
+
+impl TryFrom<MaskJson> for Mask {
+    type Error = String;
+
+    fn try_from(json: MaskJson) -> Result<Self, Self::Error> {
+        let bytes = base64::decode(&json.data).map_err(|err| format!("mask: {}", err))?;
+        let mut mask = Mask::from_png(&bytes).map_err(|err| format!("mask: {}", err))?;
+        mask.enabled = json.enabled;
+        Ok(mask)
+    }
+}
+
+impl From<Mask> for MaskJson {
+    fn from(mask: Mask) -> Self {
+        // encoding an in-memory 8-bit grayscale png doesn't fail.
+        let bytes = mask.to_png().unwrap_or_default();
+        Self {
+            enabled: mask.enabled,
+            data: base64::encode(&bytes),
+        }
+    }
+}
+
+fn bool_default() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_rgba_of_each_mode() {
+        // white, half transparent white, transparent black.
+        let rgba = [255, 255, 255, 255, 255, 255, 255, 128, 0, 0, 0, 0];
+        let luminance = Mask::from_rgba(3, 1, &rgba, MaskMode::Luminance).unwrap();
+        assert_eq!(luminance.data(), &[255, 128, 0]);
+        let alpha = Mask::from_rgba(3, 1, &rgba, MaskMode::Alpha).unwrap();
+        assert_eq!(alpha.data(), &[255, 128, 0]);
+
+        let gray = [51, 51, 51, 255];
+        assert_eq!(
+            Mask::from_rgba(1, 1, &gray, MaskMode::Luminance)
+                .unwrap()
+                .data(),
+            &[51]
+        );
+        assert_eq!(
+            Mask::from_rgba(1, 1, &gray, MaskMode::Alpha)
+                .unwrap()
+                .data(),
+            &[255]
+        );
+
+        assert!(Mask::from_rgba(2, 1, &gray, MaskMode::Alpha).is_err());
+        assert!(Mask::from_rgba(0, 0, &[], MaskMode::Alpha).is_err());
+    }
+
+    #[test]
+    fn coverage_is_bilinear_inside() {
+        let mask = Mask::from_rgba(2, 1, &[0, 0, 0, 0, 0, 0, 0, 255], MaskMode::Alpha).unwrap();
+        assert_eq!(mask.coverage(0.25, 0.5), 0.0);
+        assert_eq!(mask.coverage(0.5, 0.5), 0.5);
+        assert_eq!(mask.coverage(0.75, 0.5), 1.0);
+        assert_eq!(mask.coverage(1.5, 0.5), 0.0);
+        assert_eq!(mask.coverage(0.5, -0.1), 0.0);
+    }
+
+    #[test]
+    fn paint_a_round_dab() {
+        let mut mask = Mask::new(5, 5);
+        mask.paint(0.5, 0.5, (0.2, 0.2), 0);
+        assert_eq!(mask.data()[2 * 5 + 2], 0);
+        assert_eq!(mask.data()[2 * 5 + 1], 0);
+        assert_eq!(mask.data()[0], 255);
+        assert_eq!(mask.data()[4 * 5 + 4], 255);
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let mut mask =
+            Mask::from_rgba(2, 1, &[0, 0, 0, 10, 0, 0, 0, 200], MaskMode::Alpha).unwrap();
+        mask.set_enabled(false);
+        let json = serde_json::to_string(&mask).unwrap();
+        assert_eq!(serde_json::from_str::<Mask>(&json).unwrap(), mask);
+        assert!(serde_json::from_str::<Mask>(r#"{"data":"not png"}"#).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-21 19:40:12
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-21 22:15:37
+ * @FilePath: /layer-painter/wasm/src/canvas/migrate.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+//! Upgrade the project json written by older versions step by step,
+//! `MIGRATIONS[n]` upgrades a project of version `n` to `n + 1`.
+//! The projects saved before the `version` field are version 0.
+
+use serde_json::{Map, Value};
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+// The version is bumped by every change of the format, even an optional field
+// with nothing to fill, so that older builds refuse the project instead of
+// dropping the field silently.
+const MIGRATIONS: &[Migration] = &[
+    v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5, v5_to_v6, v6_to_v7, v7_to_v8, v8_to_v9,
+    v9_to_v10, v10_to_v11, v11_to_v12,
+];
+
+/// The version of the projects written by this build.
+pub const CURRENT_VERSION: u64 = MIGRATIONS.len() as u64;
+
+pub fn project_version(project: &Value) -> Result<u64, String> {
+    match project.get("version") {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| format!("The project version should be an integer, not {}", version)),
+    }
+}
+
+pub fn migrate(mut project: Value) -> Result<Value, String> {
+    let version = project_version(&project)?;
+    if version > CURRENT_VERSION {
+        return Err(format!(
+            "The project is saved by a newer layer-painter (project version {}), this one only supports up to version {}.",
+            version, CURRENT_VERSION
+        ));
+    }
+
+    let map = project
+        .as_object_mut()
+        .ok_or_else(|| String::from("The project should be a json object."))?;
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(map).map_err(|err| {
+            format!(
+                "Upgrading the project from version {} failed: {}",
+                from, err
+            )
+        })?;
+        map.insert(String::from("version"), Value::from(from as u64 + 1));
+    }
+
+    Ok(project)
+}
+
+fn layers_of(map: &mut Map<String, Value>) -> Result<Vec<&mut Map<String, Value>>, String> {
+    match map.get_mut("layers") {
+        None => Ok(Vec::new()),
+        Some(Value::Array(layers)) => layers
+            .iter_mut()
+            .map(|layer| {
+                layer
+                    .as_object_mut()
+                    .ok_or_else(|| String::from("a layer should be a json object"))
+            })
+            .collect(),
+        Some(_) => Err(String::from("`layers` should be an array")),
+    }
+}
+
+/// Call `f` on every layer, with the layers in groups.
+fn walk_layers_of(
+    map: &mut Map<String, Value>,
+    f: &mut dyn FnMut(&mut Map<String, Value>),
+) -> Result<(), String> {
+    for layer in layers_of(map)? {
+        f(layer);
+        walk_layers_of(layer, f)?;
+    }
+
+    Ok(())
+}
+
+fn insert_missing(map: &mut Map<String, Value>, key: &str, value: Value) {
+    if !map.contains_key(key) {
+        map.insert(String::from(key), value);
+    }
+}
+
+/// Version 1 writes out the fields which were filled with serde defaults.
+fn v0_to_v1(map: &mut Map<String, Value>) -> Result<(), String> {
+    // the defaults left a layer without `opacity` invisible,
+    // use the same values as a new layer.
+    insert_missing(map, "name", Value::from(""));
+    insert_missing(map, "width", Value::from(0));
+    insert_missing(map, "height", Value::from(0));
+    insert_missing(map, "layers", Value::Array(Vec::new()));
+
+    for layer in layers_of(map)? {
+        insert_missing(layer, "name", Value::from(""));
+        insert_missing(layer, "selected", Value::from(0));
+        insert_missing(layer, "images", Value::Array(Vec::new()));
+        insert_missing(layer, "show", Value::from(true));
+        insert_missing(layer, "fit", Value::from(true));
+        insert_missing(layer, "opacity", Value::from(1.0));
+        insert_missing(layer, "dx", Value::from(0.0));
+        insert_missing(layer, "dy", Value::from(0.0));
+        insert_missing(layer, "dw", Value::from(0.0));
+        insert_missing(layer, "dh", Value::from(0.0));
+    }
+
+    Ok(())
+}
+
+/// Version 2 added the blend mode of layers.
+fn v1_to_v2(map: &mut Map<String, Value>) -> Result<(), String> {
+    for layer in layers_of(map)? {
+        insert_missing(layer, "blend", Value::from("normal"));
+    }
+
+    Ok(())
+}
+
+/// Version 3 added the layer groups, a group keeps its own layers in `layers`.
+fn v2_to_v3(map: &mut Map<String, Value>) -> Result<(), String> {
+    for layer in layers_of(map)? {
+        insert_missing(layer, "group", Value::from(false));
+        insert_missing(layer, "collapsed", Value::from(false));
+    }
+
+    Ok(())
+}
+
+/// Version 4 added the optional layer masks.
+fn v3_to_v4(_map: &mut Map<String, Value>) -> Result<(), String> {
+    Ok(())
+}
+
+/// Version 5 draws the layers with `dw` x `dh` and added their transforms.
+fn v4_to_v5(map: &mut Map<String, Value>) -> Result<(), String> {
+    walk_layers_of(map, &mut |layer| {
+        // the sizes were ignored before, auto keeps the old ones.
+        layer.insert(String::from("dw"), Value::from(0.0));
+        layer.insert(String::from("dh"), Value::from(0.0));
+        insert_missing(layer, "rotation", Value::from(0.0));
+        insert_missing(layer, "flip_h", Value::from(false));
+        insert_missing(layer, "flip_v", Value::from(false));
+        insert_missing(layer, "anchor_x", Value::from(0.5));
+        insert_missing(layer, "anchor_y", Value::from(0.5));
+    })
+}
+
+/// Version 6 replaced `fit` of layers with the scale modes.
+fn v5_to_v6(map: &mut Map<String, Value>) -> Result<(), String> {
+    walk_layers_of(map, &mut |layer| {
+        let fit = layer
+            .remove("fit")
+            .and_then(|fit| fit.as_bool())
+            .unwrap_or(true);
+        if layer.contains_key("scale") {
+            return;
+        }
+
+        let size = |layer: &Map<String, Value>, key: &str| {
+            layer.get(key).and_then(|size| size.as_f64()).unwrap_or(0.0)
+        };
+        let (dw, dh) = (size(layer, "dw"), size(layer, "dh"));
+
+        // a fitted layer is contained in its box, the others were stretched
+        // to `dw` x `dh`, where an unset one was the size of the selected image.
+        let scale = if fit {
+            "contain"
+        } else if dw <= 0.0 && dh <= 0.0 {
+            "none"
+        } else {
+            let selected = layer
+                .get("selected")
+                .and_then(|selected| selected.as_u64())
+                .unwrap_or(0) as usize;
+            let (sw, sh) = match layer
+                .get("images")
+                .and_then(|images| images.get(selected))
+                .and_then(|image| image.as_object())
+            {
+                Some(image) => (size(image, "sw"), size(image, "sh")),
+                None => (0.0, 0.0),
+            };
+
+            if dw <= 0.0 {
+                layer.insert(String::from("dw"), Value::from(sw));
+            }
+            if dh <= 0.0 {
+                layer.insert(String::from("dh"), Value::from(sh));
+            }
+            "stretch"
+        };
+        layer.insert(String::from("scale"), Value::from(scale));
+    })
+}
+
+/// Version 7 added the optional scenes of the canvas.
+fn v6_to_v7(_map: &mut Map<String, Value>) -> Result<(), String> {
+    Ok(())
+}
+
+/// Version 8 added the timeline, the animated layers and their frame offsets.
+fn v7_to_v8(map: &mut Map<String, Value>) -> Result<(), String> {
+    let mut animation = Map::new();
+    animation.insert(String::from("fps"), Value::from(12.0));
+    animation.insert(String::from("mode"), Value::from("loop"));
+    insert_missing(map, "animation", Value::Object(animation));
+
+    walk_layers_of(map, &mut |layer| {
+        insert_missing(layer, "animated", Value::from(false));
+        insert_missing(layer, "frame_offset", Value::from(0));
+    })
+}
+
+/// Version 9 added the optional paths and content hashes of images.
+fn v8_to_v9(_map: &mut Map<String, Value>) -> Result<(), String> {
+    Ok(())
+}
+
+/// Version 10 added the optional text of text layers.
+fn v9_to_v10(_map: &mut Map<String, Value>) -> Result<(), String> {
+    Ok(())
+}
+
+/// Version 11 added the optional shape of shape layers.
+fn v10_to_v11(_map: &mut Map<String, Value>) -> Result<(), String> {
+    Ok(())
+}
+
+/// Version 12 replaced `group`, `text` and `shape` of layers with their `kind`.
+fn v11_to_v12(map: &mut Map<String, Value>) -> Result<(), String> {
+    for layer in layers_of(map)? {
+        // the layers of a group first, they are moved into its kind.
+        v11_to_v12(layer)?;
+
+        let group = layer.remove("group").and_then(|group| group.as_bool());
+        let collapsed = layer.remove("collapsed").unwrap_or(Value::from(false));
+        let layers = layer.remove("layers").unwrap_or(Value::Array(Vec::new()));
+        let text = layer.remove("text");
+        let shape = layer.remove("shape");
+
+        // a layer with more than one of them was drawn as a group, then as a shape.
+        let mut kind = Map::new();
+        if group == Some(true) {
+            kind.insert(String::from("type"), Value::from("group"));
+            kind.insert(String::from("collapsed"), collapsed);
+            kind.insert(String::from("layers"), layers);
+        } else if let Some(Value::Object(shape)) = shape {
+            kind = shape;
+            kind.insert(String::from("type"), Value::from("shape"));
+        } else if let Some(Value::Object(text)) = text {
+            kind = text;
+            kind.insert(String::from("type"), Value::from("text"));
+        } else {
+            kind.insert(String::from("type"), Value::from("images"));
+        }
+        layer.insert(String::from("kind"), Value::Object(kind));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn scale_of(layer: Value) -> (String, f64, f64) {
+        let mut project = json!({ "layers": [layer] });
+        v5_to_v6(project.as_object_mut().unwrap()).unwrap();
+        let layer = &project["layers"][0];
+        assert!(layer.get("fit").is_none());
+        (
+            layer["scale"].as_str().unwrap().to_string(),
+            layer["dw"].as_f64().unwrap(),
+            layer["dh"].as_f64().unwrap(),
+        )
+    }
+
+    #[test]
+    fn fit_becomes_a_scale_mode() {
+        let image = json!({ "name": "a", "sw": 30.0, "sh": 20.0 });
+        let layer = |fit: bool, dw: f64, dh: f64| json!({ "fit": fit, "dw": dw, "dh": dh, "selected": 0, "images": [image.clone()] });
+
+        assert_eq!(
+            scale_of(layer(true, 0.0, 0.0)),
+            (String::from("contain"), 0.0, 0.0)
+        );
+        assert_eq!(
+            scale_of(layer(false, 0.0, 0.0)),
+            (String::from("none"), 0.0, 0.0)
+        );
+        // an unset size was the size of the image.
+        assert_eq!(
+            scale_of(layer(false, 60.0, 0.0)),
+            (String::from("stretch"), 60.0, 20.0)
+        );
+    }
+
+    #[test]
+    fn fit_of_grouped_layers() {
+        let mut project = json!({
+            "layers": [{ "fit": true, "dw": 0.0, "dh": 0.0, "layers": [
+                { "fit": false, "dw": 0.0, "dh": 0.0 }
+            ] }]
+        });
+        v5_to_v6(project.as_object_mut().unwrap()).unwrap();
+        assert_eq!(project["layers"][0]["scale"], "contain");
+        assert_eq!(project["layers"][0]["layers"][0]["scale"], "none");
+    }
+
+    #[test]
+    fn layers_get_their_kind() {
+        let mut project = json!({
+            "layers": [
+                { "group": true, "collapsed": true, "layers": [
+                    { "group": false, "text": { "content": "a" } }
+                ] },
+                { "group": false, "shape": { "kind": "ellipse" }, "text": { "content": "b" } },
+                { "group": false, "images": [] }
+            ]
+        });
+        v11_to_v12(project.as_object_mut().unwrap()).unwrap();
+
+        let group = &project["layers"][0];
+        assert!(group.get("layers").is_none() && group.get("group").is_none());
+        assert_eq!(group["kind"]["type"], "group");
+        assert_eq!(group["kind"]["collapsed"], true);
+        assert_eq!(
+            group["kind"]["layers"][0]["kind"],
+            json!({ "type": "text", "content": "a" })
+        );
+        assert_eq!(
+            project["layers"][1]["kind"],
+            json!({ "type": "shape", "kind": "ellipse" })
+        );
+        assert!(project["layers"][1].get("text").is_none());
+        assert_eq!(project["layers"][2]["kind"], json!({ "type": "images" }));
+    }
+}
@@ -7,10 +7,35 @@
  * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
  */
 
+mod animation;
+mod batch;
+mod blend;
+mod bundle;
+#[allow(clippy::module_inception)]
+mod canvas;
 mod image;
 mod layer;
-mod canvas;
+mod mask;
+mod migrate;
+mod paths;
+mod scale;
+mod scene;
+mod shape;
+mod text;
+mod transform;
 
-pub use image::Image;
-pub use layer::Layer;
+pub use animation::*;
+pub use batch::*;
+pub use blend::BlendMode;
+pub use bundle::*;
 pub use canvas::Canvas;
+pub use image::Image;
+pub use layer::{Group, Layer, LayerKind};
+pub use mask::{Mask, MaskMode};
+pub use migrate::*;
+pub use paths::*;
+pub use scale::ScaleMode;
+pub use scene::{Scene, SceneLayer};
+pub use shape::{parse_hex_color, Shape, ShapeKind};
+pub use text::{Text, TextAlign};
+pub use transform::Transform;
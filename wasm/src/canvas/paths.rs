@@ -0,0 +1,216 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-07 20:31:08
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-07 22:46:19
+ * @FilePath: /layer-painter/wasm/src/canvas/paths.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+//! The paths of the image files, relative to the project file in the json.
+//! They are handled as strings with `/` or `\` separators, since the wasm
+//! part has no file system, and written back with `/`, which works everywhere.
+
+use crate::canvas::Canvas;
+
+// the root, `/`, `C:/` or empty for a relative path, and the normalized components.
+fn split_path(path: &str) -> (String, Vec<String>) {
+    let path = path.replace('\\', "/");
+    let (root, rest) = if let Some(rest) = path.strip_prefix('/') {
+        (String::from("/"), rest)
+    } else if path.len() >= 2
+        && path.as_bytes()[1] == b':'
+        && path.as_bytes()[0].is_ascii_alphabetic()
+    {
+        (format!("{}:/", path[..1].to_uppercase()), &path[2..])
+    } else {
+        (String::new(), path.as_str())
+    };
+
+    let mut components: Vec<String> = Vec::new();
+    for component in rest.split('/') {
+        match component {
+            "" | "." => {}
+            ".." if matches!(components.last(), Some(last) if last.as_str() != "..") => {
+                components.pop();
+            }
+            // above the root is the root.
+            ".." if !root.is_empty() => {}
+            _ => components.push(String::from(component)),
+        }
+    }
+
+    (root, components)
+}
+
+fn join_components(root: &str, components: &[String]) -> String {
+    format!("{}{}", root, components.join("/"))
+}
+
+pub fn is_absolute_path(path: &str) -> bool {
+    !split_path(path).0.is_empty()
+}
+
+/// The directory of the file at `path`.
+pub fn parent_dir(path: &str) -> String {
+    let (root, mut components) = split_path(path);
+    components.pop();
+    join_components(&root, &components)
+}
+
+/// `path` resolved against `base_dir`, unless it's absolute already.
+pub fn join_path(base_dir: &str, path: &str) -> String {
+    if is_absolute_path(path) {
+        let (root, components) = split_path(path);
+        return join_components(&root, &components);
+    }
+
+    let (root, components) = split_path(format!("{}/{}", base_dir, path).as_str());
+    join_components(&root, &components)
+}
+
+/// `path` relative to `base_dir`, or absolute if they are on different roots.
+pub fn relative_path(base_dir: &str, path: &str) -> String {
+    let (base_root, base) = split_path(base_dir);
+    let (root, components) = split_path(path);
+    if base_root != root || root.is_empty() {
+        return join_components(&root, &components);
+    }
+
+    let common = base
+        .iter()
+        .zip(components.iter())
+        .take_while(|(base, component)| base == component)
+        .count();
+    let mut relative = vec![String::from(".."); base.len() - common];
+    relative.extend(components[common..].iter().cloned());
+
+    relative.join("/")
+}
+
+/// Resolve the relative image paths read from the project at `project_path`.
+pub fn resolve_image_paths(canvas: &mut Canvas, project_path: &str) {
+    let base_dir = parent_dir(project_path);
+    canvas.for_each_layer_mut(|layer| {
+        for image in layer.get_mut_images().iter_mut() {
+            if let Some(path) = image.path() {
+                image.set_path(Some(join_path(&base_dir, &path)));
+            }
+        }
+    });
+}
+
+/// Make the image paths relative to the project to be written at `project_path`.
+pub fn relativize_image_paths(canvas: &mut Canvas, project_path: &str) {
+    let base_dir = parent_dir(project_path);
+    canvas.for_each_layer_mut(|layer| {
+        for image in layer.get_mut_images().iter_mut() {
+            if let Some(path) = image.path() {
+                image.set_path(Some(relative_path(&base_dir, &path)));
+            }
+        }
+    });
+}
+
+/// The FNV-1a hash of the content of an image file, as 16 hex digits,
+/// to find a moved file. `src-tauri` hashes the files the same way.
+pub fn content_hash(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Image, Layer};
+
+    fn components(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| String::from(*name)).collect()
+    }
+
+    #[test]
+    fn split_and_normalize() {
+        assert_eq!(
+            split_path("c:\\p\\.\\img\\..\\a.png"),
+            (String::from("C:/"), components(&["p", "a.png"]))
+        );
+        assert_eq!(
+            split_path("/p//img/./a.png"),
+            (String::from("/"), components(&["p", "img", "a.png"]))
+        );
+        // a relative path keeps the `..` it can't take back.
+        assert_eq!(
+            split_path("img/../../a.png"),
+            (String::new(), components(&["..", "a.png"]))
+        );
+        assert!(is_absolute_path("D:/a.png"));
+        assert!(!is_absolute_path("img/a.png"));
+    }
+
+    #[test]
+    fn above_the_root_is_the_root() {
+        assert_eq!(
+            split_path("/../a.png"),
+            (String::from("/"), components(&["a.png"]))
+        );
+        assert_eq!(join_path("/p", "../../../a.png"), "/a.png");
+        assert_eq!(join_path("C:\\p", "..\\..\\a.png"), "C:/a.png");
+    }
+
+    #[test]
+    fn join_relative_and_absolute_paths() {
+        assert_eq!(join_path("/p/project", "../img/a.png"), "/p/img/a.png");
+        assert_eq!(join_path("/p/project", "/img/./a.png"), "/img/a.png");
+        assert_eq!(join_path("/p/project", "D:\\img\\a.png"), "D:/img/a.png");
+        assert_eq!(parent_dir("C:\\p\\project.json"), "C:/p");
+    }
+
+    #[test]
+    fn relativize_then_resolve() {
+        let base_dir = "/p/project";
+        for path in [
+            "/p/project/a.png",
+            "/p/img/a.png",
+            "/a.png",
+            "/p/project/img/a.png",
+        ] {
+            let relative = relative_path(base_dir, path);
+            assert!(!is_absolute_path(&relative), "{}", relative);
+            assert_eq!(join_path(base_dir, &relative), path);
+        }
+        assert_eq!(relative_path(base_dir, "/p/img/a.png"), "../img/a.png");
+        assert_eq!(relative_path("c:\\p", "C:\\p\\a.png"), "a.png");
+    }
+
+    #[test]
+    fn different_roots_stay_absolute() {
+        assert_eq!(relative_path("C:/p", "D:\\img\\a.png"), "D:/img/a.png");
+        assert_eq!(relative_path("/p", "C:/a.png"), "C:/a.png");
+        assert_eq!(relative_path("C:/p", "/a.png"), "/a.png");
+        assert_eq!(
+            join_path("C:/p", &relative_path("C:/p", "D:/a.png")),
+            "D:/a.png"
+        );
+    }
+
+    #[test]
+    fn image_paths_of_a_project() {
+        let mut image = Image::new("a", None, 1.0, 1.0);
+        image.set_path(Some(String::from("C:\\p\\img\\a.png")));
+        let mut layer = Layer::empty("a");
+        layer.append_images(vec![image]);
+        let mut canvas = Canvas::new();
+        canvas.insert_layer(0, layer);
+
+        let path = |canvas: &Canvas| canvas.get_layer(0).unwrap().images()[0].path();
+        relativize_image_paths(&mut canvas, "C:/p/project/a.json");
+        assert_eq!(path(&canvas).as_deref(), Some("../img/a.png"));
+        resolve_image_paths(&mut canvas, "C:/p/project/a.json");
+        assert_eq!(path(&canvas).as_deref(), Some("C:/p/img/a.png"));
+    }
+}
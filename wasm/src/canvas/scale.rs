@@ -0,0 +1,79 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-28 20:12:40
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-28 22:37:05
+ * @FilePath: /layer-painter/wasm/src/canvas/scale.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use serde::{Deserialize, Serialize};
+
+/// How the selected image of a layer is scaled into the box of the layer,
+/// which is `dw` x `dh`, or the canvas size where they are not set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScaleMode {
+    /// The size of the source rect, the box is ignored.
+    None,
+    /// As large as it fits in the box, at its top left.
+    #[default]
+    Contain,
+    /// As small as it covers the box, centered and cut to the box.
+    Cover,
+    /// The size of the box.
+    Stretch,
+    /// The size of the source rect, centered and cut to the box.
+    Center,
+    /// The size of the source rect, repeated over the box.
+    Tile,
+    /// The largest whole multiple of the source rect that fits in the box,
+    /// at least 1, drawn without smoothing.
+    IntegerScale,
+}
+
+impl ScaleMode {
+    pub const ALL: [ScaleMode; 7] = [
+        ScaleMode::None,
+        ScaleMode::Contain,
+        ScaleMode::Cover,
+        ScaleMode::Stretch,
+        ScaleMode::Center,
+        ScaleMode::Tile,
+        ScaleMode::IntegerScale,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScaleMode::None => "none",
+            ScaleMode::Contain => "contain",
+            ScaleMode::Cover => "cover",
+            ScaleMode::Stretch => "stretch",
+            ScaleMode::Center => "center",
+            ScaleMode::Tile => "tile",
+            ScaleMode::IntegerScale => "integer-scale",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ScaleMode::ALL
+            .iter()
+            .copied()
+            .find(|mode| mode.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_roundtrip() {
+        for mode in ScaleMode::ALL {
+            assert_eq!(ScaleMode::from_name(mode.name()), Some(mode));
+            let json = serde_json::to_string(&mode).unwrap();
+            assert_eq!(json, format!("\"{}\"", mode.name()));
+        }
+        assert_eq!(ScaleMode::default(), ScaleMode::Contain);
+    }
+}
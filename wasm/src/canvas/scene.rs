@@ -0,0 +1,89 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-02 20:31:47
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-02 23:05:19
+ * @FilePath: /layer-painter/wasm/src/canvas/scene.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use crate::canvas::Canvas;
+
+use serde::{Deserialize, Serialize};
+
+/// The state of a layer in a scene, found by the layer name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneLayer {
+    pub layer: String,
+    pub selected: usize,
+    pub show: bool,
+    pub opacity: f64,
+}
+
+/// A named look of the canvas, the selected image,
+/// the visibility and the opacity of every layer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    name: String,
+    layers: Vec<SceneLayer>,
+}
+
+impl Scene {
+    /// Record the current state of every layer of `canvas`.
+    pub fn capture(name: &str, canvas: &Canvas) -> Self {
+        let layers = canvas
+            .walk_layers()
+            .into_iter()
+            .map(|(_, layer)| SceneLayer {
+                layer: layer.name(),
+                selected: layer.selected(),
+                show: layer.show(),
+                opacity: layer.opacity(),
+            })
+            .collect();
+
+        Scene {
+            name: String::from(name),
+            layers,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = String::from(name);
+    }
+
+    pub fn get_layers(&self) -> &Vec<SceneLayer> {
+        &self.layers
+    }
+
+    /// Set the recorded state on the layers of `canvas`,
+    /// the layers which are not in the scene are kept as they are.
+    pub fn apply_to(&self, canvas: &mut Canvas) {
+        canvas.for_each_layer_mut(|layer| {
+            let name = layer.name();
+            let state = match self.layers.iter().find(|state| state.layer == name) {
+                Some(state) => state,
+                None => return,
+            };
+
+            if state.selected < layer.get_images().len() {
+                layer.set_selected(state.selected);
+            }
+            layer.set_show(state.show);
+            layer.set_opacity(state.opacity);
+        });
+    }
+
+    /// Follow a renamed layer.
+    pub fn rename_layer(&mut self, before: &str, after: &str) {
+        for state in self.layers.iter_mut() {
+            if state.layer == before {
+                state.layer = String::from(after);
+            }
+        }
+    }
+}
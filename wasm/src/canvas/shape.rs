@@ -0,0 +1,198 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-10 20:06:52
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-10 23:18:27
+ * @FilePath: /layer-painter/wasm/src/canvas/shape.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShapeKind {
+    #[default]
+    Rectangle,
+    Ellipse,
+    Line,
+    Polygon,
+}
+
+impl ShapeKind {
+    pub const ALL: [ShapeKind; 4] = [
+        ShapeKind::Rectangle,
+        ShapeKind::Ellipse,
+        ShapeKind::Line,
+        ShapeKind::Polygon,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShapeKind::Rectangle => "rectangle",
+            ShapeKind::Ellipse => "ellipse",
+            ShapeKind::Line => "line",
+            ShapeKind::Polygon => "polygon",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ShapeKind::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.name() == name)
+    }
+
+    /// The points of a new shape of the kind, a horizontal line or a triangle.
+    pub fn default_points(&self) -> Vec<(f64, f64)> {
+        match self {
+            ShapeKind::Line => vec![(0.0, 0.5), (1.0, 0.5)],
+            ShapeKind::Polygon => vec![(0.5, 0.0), (1.0, 1.0), (0.0, 1.0)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The geometry and the style of a shape layer.
+///
+/// The shape is drawn in a box of `width` x `height`, which is placed like an image
+/// of that size, see `ScaleMode` and `Transform`, and it's drawn again for every size,
+/// so it stays sharp. The stroke is inside the box.
+/// `points` are the ends of a line or the vertices of a polygon, normalized by the box,
+/// the colors are css hex colors, `None` isn't drawn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shape {
+    #[serde(default)]
+    pub kind: ShapeKind,
+
+    #[serde(default = "size_default")]
+    pub width: f64,
+
+    #[serde(default = "size_default")]
+    pub height: f64,
+
+    #[serde(default)]
+    pub fill: Option<String>,
+
+    #[serde(default)]
+    pub stroke: Option<String>,
+
+    #[serde(default)]
+    pub stroke_width: f64,
+
+    #[serde(default)]
+    pub corner_radius: f64,
+
+    #[serde(default)]
+    pub points: Vec<(f64, f64)>,
+}
+
+impl Shape {
+    pub fn new(kind: ShapeKind) -> Self {
+        Self {
+            kind,
+            width: size_default(),
+            height: size_default(),
+            fill: Some(String::from("#3b82f6")),
+            stroke: None,
+            stroke_width: 4.0,
+            corner_radius: 0.0,
+            points: kind.default_points(),
+        }
+    }
+
+    /// The points of the kind, the default ones if there are not enough of them.
+    pub fn kind_points(&self) -> Vec<(f64, f64)> {
+        match self.kind {
+            ShapeKind::Line if self.points.len() >= 2 => self.points[..2].to_vec(),
+            ShapeKind::Polygon if self.points.len() >= 3 => self.points.clone(),
+            kind => kind.default_points(),
+        }
+    }
+
+    /// `points` as `x,y` split by spaces.
+    pub fn points_text(points: &[(f64, f64)]) -> String {
+        points
+            .iter()
+            .map(|(x, y)| format!("{},{}", x, y))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Parse the points of `points_text`.
+    pub fn parse_points(text: &str) -> Result<Vec<(f64, f64)>, String> {
+        text.split_whitespace()
+            .map(|point| {
+                let (x, y) = point
+                    .split_once(',')
+                    .ok_or_else(|| format!("the point {} isn't x,y", point))?;
+                match (x.trim().parse(), y.trim().parse()) {
+                    (Ok(x), Ok(y)) => Ok((x, y)),
+                    _ => Err(format!("the point {} isn't x,y", point)),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Parse a css hex color, `#rgb`, `#rrggbb` or `#rrggbbaa`.
+pub fn parse_hex_color(color: &str) -> Option<[u8; 4]> {
+    let hex = color.trim().strip_prefix('#')?;
+    let digit = |index: usize| u8::from_str_radix(hex.get(index..index + 1)?, 16).ok();
+    let byte = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+
+    match hex.len() {
+        3 => Some([digit(0)? * 17, digit(1)? * 17, digit(2)? * 17, 255]),
+        6 => Some([byte(0)?, byte(2)?, byte(4)?, 255]),
+        8 => Some([byte(0)?, byte(2)?, byte(4)?, byte(6)?]),
+        _ => None,
+    }
+}
+
+fn size_default() -> f64 {
+    200.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors() {
+        assert_eq!(parse_hex_color("#f80"), Some([255, 136, 0, 255]));
+        assert_eq!(parse_hex_color(" #3b82f6 "), Some([59, 130, 246, 255]));
+        assert_eq!(parse_hex_color("#00000080"), Some([0, 0, 0, 128]));
+        assert_eq!(parse_hex_color("3b82f6"), None);
+        assert_eq!(parse_hex_color("#12345"), None);
+        assert_eq!(parse_hex_color("#gggggg"), None);
+    }
+
+    #[test]
+    fn points_roundtrip() {
+        let points = vec![(0.5, 0.0), (1.0, 1.0), (0.0, 0.25)];
+        let text = Shape::points_text(&points);
+        assert_eq!(text, "0.5,0 1,1 0,0.25");
+        assert_eq!(Shape::parse_points(&text), Ok(points));
+        assert!(Shape::parse_points("1,2 3").is_err());
+        assert!(Shape::parse_points("a,b").is_err());
+    }
+
+    #[test]
+    fn kind_points_fall_back() {
+        let mut line = Shape::new(ShapeKind::Line);
+        line.points = vec![(0.0, 0.0)];
+        assert_eq!(line.kind_points(), ShapeKind::Line.default_points());
+
+        let mut polygon = Shape::new(ShapeKind::Polygon);
+        polygon.points = vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        assert_eq!(polygon.kind_points().len(), 4);
+    }
+
+    #[test]
+    fn json_defaults() {
+        let shape: Shape = serde_json::from_str(r#"{"kind":"ellipse"}"#).unwrap();
+        assert_eq!(shape.kind, ShapeKind::Ellipse);
+        assert_eq!((shape.width, shape.height), (200.0, 200.0));
+        assert_eq!(shape.fill, None);
+    }
+}
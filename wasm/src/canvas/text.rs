@@ -0,0 +1,142 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-09 20:12:36
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-09 23:05:41
+ * @FilePath: /layer-painter/wasm/src/canvas/text.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use serde::{Deserialize, Serialize};
+
+use crate::canvas::content_hash;
+
+const KEY_PREFIX: &str = "text:";
+
+/// How the lines of a text layer are aligned in the box of the longest one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl TextAlign {
+    pub const ALL: [TextAlign; 3] = [TextAlign::Left, TextAlign::Center, TextAlign::Right];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextAlign::Left => "left",
+            TextAlign::Center => "center",
+            TextAlign::Right => "right",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        TextAlign::ALL
+            .iter()
+            .copied()
+            .find(|align| align.name() == name)
+    }
+}
+
+/// The content and the style of a text layer.
+///
+/// The text is drawn in the box of its lines, which is placed like an image
+/// of that size, see `ScaleMode` and `Transform`.
+/// `font_family` is a css font family list, `color` is a css color,
+/// `line_height` is a multiple of `font_size`, and `letter_spacing` is in pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Text {
+    #[serde(default)]
+    pub content: String,
+
+    #[serde(default = "font_family_default")]
+    pub font_family: String,
+
+    #[serde(default = "font_size_default")]
+    pub font_size: f64,
+
+    #[serde(default = "font_weight_default")]
+    pub font_weight: u16,
+
+    #[serde(default = "color_default")]
+    pub color: String,
+
+    #[serde(default)]
+    pub align: TextAlign,
+
+    #[serde(default = "line_height_default")]
+    pub line_height: f64,
+
+    #[serde(default)]
+    pub letter_spacing: f64,
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Self {
+            content: String::from("Text"),
+            font_family: font_family_default(),
+            font_size: font_size_default(),
+            font_weight: font_weight_default(),
+            color: color_default(),
+            align: TextAlign::Left,
+            line_height: line_height_default(),
+            letter_spacing: 0.0,
+        }
+    }
+}
+
+impl Text {
+    pub const WEIGHTS: [u16; 9] = [100, 200, 300, 400, 500, 600, 700, 800, 900];
+
+    pub fn lines(&self) -> Vec<&str> {
+        self.content.lines().collect()
+    }
+
+    /// The css `font` of the text.
+    pub fn css_font(&self) -> String {
+        format!(
+            "{} {}px {}",
+            self.font_weight, self.font_size, self.font_family
+        )
+    }
+
+    /// The height of a line in pixels.
+    pub fn line_pixels(&self) -> f64 {
+        self.font_size * self.line_height
+    }
+
+    /// The name of the rasterized text in the pixmaps, which changes with the text.
+    pub fn key(&self) -> String {
+        let json = serde_json::to_string(self).unwrap_or_default();
+        format!("{}{}", KEY_PREFIX, content_hash(json.as_bytes()))
+    }
+
+    pub fn is_key(name: &str) -> bool {
+        name.starts_with(KEY_PREFIX)
+    }
+}
+
+fn font_family_default() -> String {
+    String::from("sans-serif")
+}
+
+fn font_size_default() -> f64 {
+    32.0
+}
+
+fn font_weight_default() -> u16 {
+    400
+}
+
+fn color_default() -> String {
+    String::from("#000000")
+}
+
+fn line_height_default() -> f64 {
+    1.2
+}
@@ -0,0 +1,42 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-26 15:20:08
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-26 18:44:51
+ * @FilePath: /layer-painter/wasm/src/canvas/transform.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+/// Where and how a layer is drawn on the canvas.
+///
+/// `dw` or `dh` <= 0 is auto, the canvas size, see `ScaleMode`.
+/// The layer is flipped and then rotated (clockwise, in degrees) around the anchor,
+/// which is normalized by the drawn size, (0.5, 0.5) is the center.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub dx: f64,
+    pub dy: f64,
+    pub dw: f64,
+    pub dh: f64,
+    pub rotation: f64,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub anchor_x: f64,
+    pub anchor_y: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            dx: 0.0,
+            dy: 0.0,
+            dw: 0.0,
+            dh: 0.0,
+            rotation: 0.0,
+            flip_h: false,
+            flip_v: false,
+            anchor_x: 0.5,
+            anchor_y: 0.5,
+        }
+    }
+}
@@ -7,13 +7,27 @@
  * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
  */
 
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
 use crate::canvas::*;
 use crate::components::*;
+use crate::compositor::{
+    draw_pixmap, encode_animated, layer_at, layer_image, layer_mask_point, layer_mask_size,
+    layer_placement, render, render_frames, shape_to_pixmap, AnimatedOptions, Pixmap, Placement,
+    SheetOptions,
+};
 use crate::utils::*;
 
+use gloo_events::{EventListener, EventListenerOptions};
+use gloo_render::{request_animation_frame, AnimationFrame};
 use wasm_bindgen::JsCast;
-use web_sys::{CanvasRenderingContext2d, HtmlAnchorElement, HtmlCanvasElement, HtmlDivElement};
-use yew::{html, props, Component, Context, Html, NodeRef};
+use web_sys::{
+    CanvasRenderingContext2d, ClipboardEvent, DragEvent, HtmlAnchorElement, HtmlCanvasElement,
+    HtmlDivElement, HtmlElement, KeyboardEvent, MouseEvent,
+};
+use yew::{html, props, Callback, Component, Context, Html, NodeRef};
 
 #[derive(Debug)]
 pub enum State {
@@ -21,29 +35,158 @@ pub enum State {
     Failed(String),
 }
 
+/// Painting the mask of a layer by dragging on the canvas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaskBrush {
+    pub layer_index: usize,
+    // in canvas pixels.
+    pub radius: f64,
+    pub value: u8,
+}
+
+// Moving a layer by dragging on the canvas.
+#[derive(Debug, Clone, Copy)]
+struct MoveDrag {
+    layer_index: usize,
+    before: Transform,
+    start: (f64, f64),
+}
+
+// Editing the source rect of the selected image of a layer,
+// the image is only changed when it's applied.
+#[derive(Debug, Clone, Copy)]
+struct Crop {
+    layer_index: usize,
+    image_index: usize,
+    before: CropRect,
+    rect: CropRect,
+    // the width / height ratio kept while it's locked.
+    aspect: Option<f64>,
+    view: CropView,
+    // the dragged handle, the rect and the point when the drag started.
+    drag: Option<(CropHandle, CropRect, (f64, f64))>,
+}
+
+// Playing the animated layers, dropping the frame request stops it.
+struct Playback {
+    // the time of the frame 0, set by the first tick.
+    start: Option<f64>,
+    _request: AnimationFrame,
+}
+
 pub enum Msg {
     // io
     LoadImages(String, State),
-    LoadJson(String),
+    // the path of the project file in the app and its bytes
+    LoadProject(Option<String>, Vec<u8>),
+    LoadJson(Option<String>, String),
+    LoadBundle(Option<String>, Canvas),
+    // the path of the project file in the app
+    SetProjectPath(Option<String>),
+    // the images read from their files
+    LinkImages(Vec<Image>),
+    RelinkImages(Vec<MissingImage>),
+    // a watched image file is written
+    ImageFileChanged(NativeFile),
+    // the path and the image decoded from it
+    ReloadImage(String, Image),
+    // images appended to the layer (layer_index)
+    AppendImages(usize, Vec<Image>),
+
+    // drop
+    // files dropped on the pane of a layer, or on the canvas for a new layer
+    DropFiles(Option<usize>, Vec<gloo_file::File>),
+    // files dropped anywhere, the projects of them are opened
+    DropProject(Vec<gloo_file::File>),
+    // the paths dropped on the window of the app
+    NativeDrop(Vec<String>),
+
+    // clipboard
+    Paste(Vec<gloo_file::File>),
+    Copy,
+
+    // history
+    Undo,
+    Redo,
+    SetHistoryDepth(usize),
 
     // menu
     ToggleDisplayMenus,
 
     // canvas control message
     Render,
+    // the web fonts are loaded, the texts are drawn again
+    FontsLoaded,
     Reset,
-    Save(String),
+    // canvas name and whether it asks for a new path
+    Save(String, bool),
+    SaveBundle(String),
+    Export(String, ExportOptions),
+    // canvas name, options, the file name template and whether it's by scenes
+    ExportBatch(String, ExportOptions, String, bool),
+    ExportAnimated(AnimatedOptions),
+    ExportSheet(SheetOptions),
     Resize(usize, usize),
 
     // layer control message (layer_name)
     RenameLayer(usize, String),
     ToggleLayerShow(usize),
-    ToggleLayerFit(usize),
     MoveLayerIndex(usize, usize),
     SetLayerOpacity(usize, f64),
+    SetLayerBlend(usize, BlendMode),
+    SetLayerScale(usize, ScaleMode),
+    SetLayerTransform(usize, Transform),
     DuplicateLayer(usize),
     DeleteLayer(usize),
 
+    // text control message
+    AddTextLayer,
+    SetLayerText(usize, Text),
+    AddShapeLayer,
+    SetLayerShape(usize, Shape),
+
+    // group control message
+    ToggleGroupCollapsed(usize),
+    GroupLayer(usize),
+    UngroupLayer(usize),
+
+    // mask control message
+    LoadMask(usize, Mask),
+    ToggleLayerMask(usize),
+    DeleteMask(usize),
+    SetMaskBrush(Option<MaskBrush>),
+    // canvas x, y and whether it starts a stroke
+    PaintMask(f64, f64, bool),
+
+    // pointer on the canvas (canvas x, y)
+    PointerDown(f64, f64),
+    PointerMove(f64, f64),
+    PointerUp,
+    NudgeLayer(f64, f64),
+
+    // scene control message
+    SaveScene(String),
+    ApplyScene(usize),
+    UpdateScene(usize),
+    DeleteScene(usize),
+
+    // timeline control message
+    TogglePlayback,
+    StopPlayback,
+    SetAnimationFps(f64),
+    SetPlaybackMode(PlaybackMode),
+    // the time of the animation frame
+    Tick(f64),
+    ToggleLayerAnimated(usize),
+    SetLayerFrameOffset(usize, usize),
+
+    // crop control message
+    StartCrop(usize),
+    SetCropRect(CropRect),
+    ToggleCropAspectLock,
+    ApplyCrop,
+    CancelCrop,
+
     // image control message (layer_name)
     PrevImage(usize),
     NextImage(usize),
@@ -56,25 +199,417 @@ pub enum Msg {
 
 pub struct App {
     canvas: Canvas,
+    // decoded pixels of the loaded images by image name.
+    pixmaps: HashMap<String, Pixmap>,
     canvas_node: NodeRef,
     save_anchor_node: NodeRef,
     toolbar_div_node: NodeRef,
     layer_menu_div_node: NodeRef,
+    history: History,
+    mask_brush: Option<MaskBrush>,
+    // the layer picked by the move tool.
+    active_layer: Option<usize>,
+    // whether the arrow keys nudge the active layer, read by the keydown listener.
+    nudging: Rc<Cell<bool>>,
+    move_drag: Option<MoveDrag>,
+    crop: Option<Crop>,
+    playback: Option<Playback>,
+    // the frame of the timeline shown on the canvas.
+    frame: usize,
+    // the project file opened or saved in the app.
+    project_path: Option<String>,
+    _keydown_listener: EventListener,
+    _dragover_listener: EventListener,
+    _drop_listener: EventListener,
+    _paste_listener: EventListener,
+    _fonts_listener: EventListener,
+    // the changed image files and the dropped files in the app.
+    _image_listener: Option<NativeListener>,
+    _native_drop_listener: Option<NativeListener>,
 }
 
-impl App {}
+impl App {
+    // the tools keep layer indexes, which are changed by a lot of messages.
+    fn reset_tools(&mut self) {
+        self.mask_brush = None;
+        self.active_layer = None;
+        self.nudging.set(false);
+        self.move_drag = None;
+        self.crop = None;
+    }
+
+    // the window title shows the project file.
+    fn set_project_path(&mut self, path: Option<String>) {
+        let title = match path.as_ref() {
+            Some(path) => format!("Layer Painter - {}", path),
+            None => String::from("Layer Painter"),
+        };
+        gloo_utils::document().set_title(title.as_str());
+        self.project_path = path;
+    }
+
+    // stop and go back to the first frame, without changing the layers.
+    fn reset_playback(&mut self) {
+        self.playback = None;
+        self.frame = 0;
+    }
+
+    // a save dialog in the app, or a download in the browser.
+    fn save_bytes(
+        &self,
+        ctx: &Context<Self>,
+        bytes: Vec<u8>,
+        mime: &str,
+        filename: String,
+        action: &'static str,
+    ) {
+        if is_tauri() {
+            ctx.link().send_future_batch(async move {
+                match native_save_file(&bytes, filename.as_str()).await {
+                    Ok(_) => vec![],
+                    Err(err) => vec![Msg::Error(format!("{} Failed: {}", action, err))],
+                }
+            });
+            return;
+        }
+
+        let anchor = self.save_anchor_node.cast::<HtmlAnchorElement>().unwrap();
+        if let Err(err) = download_bytes(&anchor, &bytes, mime, filename.as_str()) {
+            ctx.link()
+                .send_message(Msg::Error(format!("{} Failed: {}", action, err)));
+        }
+    }
+
+    // a project saved in the app becomes the current one,
+    // and the paths of its images are written relative to it.
+    fn save_project(&self, ctx: &Context<Self>, filename: String, save_as: bool, bundle: bool) {
+        let mut canvas = self.canvas.clone();
+        ctx.link().send_future_batch(async move {
+            let path = match native_project_save_path(filename.as_str(), save_as).await {
+                Ok(Some(path)) => path,
+                Ok(None) => return vec![],
+                Err(err) => return vec![Msg::Error(format!("Save Failed: {}", err))],
+            };
+            relativize_image_paths(&mut canvas, path.as_str());
+
+            let mut msgs = Vec::new();
+            let bytes = if bundle {
+                match write_bundle(&canvas) {
+                    Ok((bundle, missing)) => {
+                        for image_name in missing {
+                            msgs.push(Msg::Warn(format!(
+                                "The image: {} is not loaded, it can't be saved in the bundle.",
+                                image_name
+                            )));
+                        }
+                        bundle
+                    }
+                    Err(err) => return vec![Msg::Error(format!("Save Bundle Failed: {}", err))],
+                }
+            } else {
+                serde_json::to_vec(&canvas).unwrap()
+            };
+
+            match native_save_project(path.as_str(), &bytes).await {
+                Ok(_) => msgs.push(Msg::SetProjectPath(Some(path))),
+                Err(err) => msgs.push(Msg::Error(format!("Save Failed: {}", err))),
+            }
+            msgs
+        });
+    }
+
+    fn append_images(&mut self, index: usize, images: Vec<Image>) {
+        let layer = self.canvas.get_mut_layer(index).unwrap();
+        if layer.is_text() || layer.is_shape() {
+            // they don't draw images, a new layer right above them.
+            let mut new_layer = Layer::empty(images.first().unwrap().name().as_str());
+            new_layer.append_images(images);
+            self.canvas.insert_layer_above(index, new_layer);
+        } else if let Some(layers) = layer.get_mut_layers() {
+            // a new layer on the top of the group.
+            let mut new_layer = Layer::empty(images.first().unwrap().name().as_str());
+            new_layer.append_images(images);
+            layers.push(new_layer);
+        } else {
+            layer.append_images(images);
+        }
+    }
+
+    // watch the files of the images for changes.
+    fn watch_image_files(&self, ctx: &Context<Self>) {
+        if !is_tauri() {
+            return;
+        }
+
+        let mut paths: Vec<String> = Vec::new();
+        for (_, layer) in self.canvas.walk_layers() {
+            for image in layer.images().iter() {
+                if let Some(path) = image.path() {
+                    if !paths.contains(&path) {
+                        paths.push(path);
+                    }
+                }
+            }
+        }
+
+        ctx.link().send_future_batch(async move {
+            match native_watch_images(&paths).await {
+                Ok(_) => vec![],
+                Err(err) => vec![Msg::Warn(format!("Watching Images Failed: {}", err))],
+            }
+        });
+    }
+
+    // read the images of the project which aren't loaded from their files.
+    fn read_image_files(&self, ctx: &Context<Self>) {
+        let mut names: Vec<String> = Vec::new();
+        let mut paths = Vec::new();
+        let mut hashes = HashMap::new();
+        for (_, layer) in self.canvas.walk_layers() {
+            for image in layer.images().iter() {
+                if image.data().is_some() || names.contains(&image.name()) {
+                    continue;
+                }
+                if let Some(path) = image.path() {
+                    names.push(image.name());
+                    paths.push(path);
+                    hashes.insert(image.name(), image.hash());
+                }
+            }
+        }
+        if paths.is_empty() {
+            return;
+        }
+
+        ctx.link().send_future_batch(async move {
+            let files = match native_read_images(&paths).await {
+                Ok(files) => files,
+                Err(err) => return vec![Msg::Error(format!("Read Images Failed: {}", err))],
+            };
+
+            let (images, missing, errors) = load_found_images(names, files).await;
+            let mut msgs: Vec<Msg> = errors.into_iter().map(Msg::Warn).collect();
+            if !images.is_empty() {
+                msgs.push(Msg::LinkImages(images));
+            }
+            if !missing.is_empty() {
+                let missing = missing
+                    .into_iter()
+                    .map(|name| MissingImage {
+                        hash: hashes.remove(&name).flatten(),
+                        name,
+                    })
+                    .collect();
+                msgs.push(Msg::RelinkImages(missing));
+            }
+            msgs
+        });
+    }
+
+    // set the images of the file at `path` to the reloaded one,
+    // returns the names of them.
+    fn reload_image(canvas: &mut Canvas, path: &str, reloaded: &Image) -> Vec<String> {
+        let (data, bytes) = match (reloaded.data(), reloaded.bytes()) {
+            (Some(data), Some(bytes)) => (data, bytes),
+            _ => return Vec::new(),
+        };
+
+        let mut image_names = Vec::new();
+        canvas.for_each_layer_mut(|layer| {
+            for image in layer.get_mut_images().iter_mut() {
+                if image.path().as_deref() != Some(path) {
+                    continue;
+                }
+
+                // a source rect of the whole image follows its new size,
+                // a custom crop is kept inside it.
+                let whole = match image.data() {
+                    Some(old) => {
+                        image.sx() == 0.0
+                            && image.sy() == 0.0
+                            && image.sw() == old.width() as f64
+                            && image.sh() == old.height() as f64
+                    }
+                    None => image.sw() <= 0.0 || image.sh() <= 0.0,
+                };
+                image.set_data(&data);
+                image.set_bytes(bytes.clone());
+                if whole {
+                    image.set_sw(reloaded.sw());
+                    image.set_sh(reloaded.sh());
+                } else {
+                    CropRect::of_image(image)
+                        .clamp(reloaded.sw(), reloaded.sh())
+                        .apply_to(image);
+                }
+
+                if !image_names.contains(&image.name()) {
+                    image_names.push(image.name());
+                }
+            }
+        });
+
+        image_names
+    }
+
+    fn request_tick(ctx: &Context<Self>) -> AnimationFrame {
+        let link = ctx.link().clone();
+        request_animation_frame(move |time| link.send_message(Msg::Tick(time)))
+    }
+}
 
 impl Component for App {
     type Message = Msg;
     type Properties = ();
 
-    fn create(_ctx: &Context<Self>) -> Self {
+    fn create(ctx: &Context<Self>) -> Self {
+        let nudging = Rc::new(Cell::new(false));
+        let can_nudge = nudging.clone();
+        let onkeydown = ctx.link().batch_callback(move |event: KeyboardEvent| {
+            // inputs have their own undo.
+            let tag_name = event
+                .target()
+                .and_then(|target| target.dyn_into::<HtmlElement>().ok())
+                .map(|element| element.tag_name())
+                .unwrap_or_default();
+            if matches!(tag_name.as_str(), "INPUT" | "TEXTAREA" | "SELECT") {
+                return None;
+            }
+
+            // nudge the layer picked by the move tool, instead of scrolling.
+            let step = if event.shift_key() { 10.0 } else { 1.0 };
+            let nudge = match event.key().as_str() {
+                "ArrowLeft" => Some((-step, 0.0)),
+                "ArrowRight" => Some((step, 0.0)),
+                "ArrowUp" => Some((0.0, -step)),
+                "ArrowDown" => Some((0.0, step)),
+                _ => None,
+            };
+            if let Some((dx, dy)) = nudge {
+                if !can_nudge.get() {
+                    return None;
+                }
+
+                event.prevent_default();
+                return Some(Msg::NudgeLayer(dx, dy));
+            }
+
+            let key = event.key().to_lowercase();
+            if (event.ctrl_key() || event.meta_key()) && key == "c" && !has_text_selection() {
+                event.prevent_default();
+                return Some(Msg::Copy);
+            }
+
+            if !(event.ctrl_key() || event.meta_key()) || key != "z" {
+                return None;
+            }
+
+            event.prevent_default();
+            if event.shift_key() {
+                Some(Msg::Redo)
+            } else {
+                Some(Msg::Undo)
+            }
+        });
+
+        let keydown_listener = EventListener::new_with_options(
+            &gloo_utils::window(),
+            "keydown",
+            EventListenerOptions::enable_prevent_default(),
+            move |event| {
+                onkeydown.emit(event.clone().dyn_into::<KeyboardEvent>().unwrap());
+            },
+        );
+
+        // drop files anywhere without opening them in the browser.
+        let window = gloo_utils::window();
+        let dragover_listener = EventListener::new_with_options(
+            &window,
+            "dragover",
+            EventListenerOptions::enable_prevent_default(),
+            |event| event.prevent_default(),
+        );
+        let ondrop = ctx.link().callback(Msg::DropProject);
+        let drop_listener = EventListener::new_with_options(
+            &window,
+            "drop",
+            EventListenerOptions::enable_prevent_default(),
+            move |event| {
+                event.prevent_default();
+                if let Some(event) = event.dyn_ref::<DragEvent>() {
+                    ondrop.emit(dropped_files(event));
+                }
+            },
+        );
+
+        // paste the images, the inputs paste their text.
+        let onpaste = ctx.link().callback(Msg::Paste);
+        let paste_listener = EventListener::new_with_options(
+            &window,
+            "paste",
+            EventListenerOptions::enable_prevent_default(),
+            move |event| {
+                let event = match event.dyn_ref::<ClipboardEvent>() {
+                    Some(event) => event,
+                    None => return,
+                };
+                let images = pasted_images(event);
+                if !images.is_empty() {
+                    event.prevent_default();
+                    onpaste.emit(images);
+                }
+            },
+        );
+
+        // the texts drawn before were drawn in the fallback fonts.
+        let fonts_listener = listen_fonts_loaded(ctx.link().callback(|_| Msg::FontsLoaded));
+
+        let (image_listener, native_drop_listener) = if is_tauri() {
+            let link = ctx.link().clone();
+            let image_listener = native_listen(IMAGE_CHANGED_EVENT, move |file: NativeFile| {
+                link.send_message(Msg::ImageFileChanged(file))
+            })
+            .map_err(|err| {
+                ctx.link()
+                    .send_message(Msg::Warn(format!("Watching Images Failed: {}", err)))
+            })
+            .ok();
+
+            let link = ctx.link().clone();
+            let native_drop_listener = native_listen(FILE_DROP_EVENT, move |paths: Vec<String>| {
+                link.send_message(Msg::NativeDrop(paths))
+            })
+            .map_err(|err| log::warn!("Dropping Files Failed: {}", err))
+            .ok();
+
+            (image_listener, native_drop_listener)
+        } else {
+            (None, None)
+        };
+
         Self {
             canvas: Canvas::new(),
+            pixmaps: HashMap::new(),
             canvas_node: NodeRef::default(),
             save_anchor_node: NodeRef::default(),
             toolbar_div_node: NodeRef::default(),
             layer_menu_div_node: NodeRef::default(),
+            history: History::new(HISTORY_DEPTH_DEFAULT),
+            mask_brush: None,
+            active_layer: None,
+            nudging,
+            move_drag: None,
+            crop: None,
+            playback: None,
+            frame: 0,
+            project_path: None,
+            _keydown_listener: keydown_listener,
+            _dragover_listener: dragover_listener,
+            _drop_listener: drop_listener,
+            _paste_listener: paste_listener,
+            _fonts_listener: fonts_listener,
+            _image_listener: image_listener,
+            _native_drop_listener: native_drop_listener,
         }
     }
 
@@ -83,18 +618,20 @@ impl Component for App {
             // io
             Msg::LoadImages(layer_name, state) => match state {
                 State::Success(images) => {
-                    if images.len() != 0 {
+                    if !images.is_empty() {
+                        self.history.record_snapshot(&self.canvas);
+                        for image in images.iter() {
+                            self.pixmaps.remove(image.name().as_str());
+                        }
+
                         let index = self.canvas.get_layer_index(layer_name.as_str());
                         match index {
-                            Some(index) => {
-                                let layer = self.canvas.get_mut_layer(index).unwrap();
-                                layer.append_images(images);
-                            }
+                            Some(index) => self.append_images(index, images),
 
                             None => {
                                 let mut layer_name = layer_name;
                                 if layer_name.as_str() == "" {
-                                    layer_name = images.get(0).unwrap().name().clone();
+                                    layer_name = images.first().unwrap().name();
                                 }
                                 canvas_append_new_fit_layer(&mut self.canvas, layer_name.as_str());
 
@@ -105,6 +642,7 @@ impl Component for App {
                                 layer.append_images(images);
                             }
                         }
+                        self.watch_image_files(ctx);
                         ctx.link().send_future(async { Msg::Render });
                     }
                     true
@@ -117,11 +655,41 @@ impl Component for App {
                 }
             },
 
-            Msg::LoadJson(data) => match serde_json::from_str::<Canvas>(data.as_str()) {
+            Msg::LoadProject(path, data) => {
+                if is_bundle(&data) {
+                    ctx.link().send_future(async move {
+                        match load_bundle(&data).await {
+                            Ok(canvas) => Msg::LoadBundle(path, canvas),
+                            Err(err) => {
+                                Msg::Error(format!("Reading Project Bundle failed: {}", err))
+                            }
+                        }
+                    });
+                } else {
+                    match String::from_utf8(data) {
+                        Ok(data) => ctx.link().send_message(Msg::LoadJson(path, data)),
+                        Err(err) => ctx.link().send_message(Msg::Error(format!("{:#?}", err))),
+                    }
+                }
+
+                false
+            }
+
+            Msg::LoadJson(path, data) => match Canvas::from_json(data.as_str()) {
                 Ok(canvas) => {
+                    // the image paths are relative to it.
+                    self.set_project_path(path);
                     let mut canvas = canvas;
                     copy_image_data_from_canvas(&mut canvas, self.canvas.clone());
                     self.canvas = canvas;
+                    self.history.clear();
+                    self.reset_tools();
+                    self.reset_playback();
+                    if let Some(project_path) = self.project_path.clone().filter(|_| is_tauri()) {
+                        resolve_image_paths(&mut self.canvas, project_path.as_str());
+                        self.read_image_files(ctx);
+                    }
+                    self.watch_image_files(ctx);
                     ctx.link().send_future(async { Msg::Render });
 
                     true
@@ -129,12 +697,324 @@ impl Component for App {
 
                 Err(err) => {
                     ctx.link()
-                        .send_message(Msg::Error(format!("Reading Save Json faild: {:#?}", err)));
+                        .send_message(Msg::Error(format!("Reading Save Json faild: {}", err)));
 
                     false
                 }
             },
 
+            Msg::LoadBundle(path, canvas) => {
+                self.set_project_path(path);
+                self.canvas = canvas;
+                if let Some(project_path) = self.project_path.clone().filter(|_| is_tauri()) {
+                    resolve_image_paths(&mut self.canvas, project_path.as_str());
+                }
+                self.watch_image_files(ctx);
+                self.pixmaps.clear();
+                self.history.clear();
+                self.reset_tools();
+                self.reset_playback();
+                ctx.link().send_future(async { Msg::Render });
+
+                true
+            }
+
+            Msg::SetProjectPath(path) => {
+                self.set_project_path(path);
+                false
+            }
+
+            Msg::LinkImages(images) => {
+                for linked in images.iter() {
+                    self.pixmaps.remove(linked.name().as_str());
+                }
+
+                self.canvas.for_each_layer_mut(|layer| {
+                    for image in layer.get_mut_images().iter_mut() {
+                        let linked = images.iter().find(|linked| linked.name() == image.name());
+                        if let Some(linked) = linked {
+                            if let Some(data) = linked.data() {
+                                image.set_data(&data);
+                            }
+                            if let Some(bytes) = linked.bytes() {
+                                image.set_bytes(bytes);
+                            }
+                            image.set_path(linked.path());
+                        }
+                    }
+                });
+                self.watch_image_files(ctx);
+                ctx.link().send_message(Msg::Render);
+
+                true
+            }
+
+            Msg::ImageFileChanged(file) => {
+                let image_name = self
+                    .canvas
+                    .walk_layers()
+                    .into_iter()
+                    .find_map(|(_, layer)| {
+                        layer
+                            .images()
+                            .iter()
+                            .find(|image| image.path().as_ref() == Some(&file.path))
+                            .map(|image| image.name())
+                    });
+                let image_name = match image_name {
+                    Some(image_name) => image_name,
+                    None => return false,
+                };
+
+                ctx.link().send_future(async move {
+                    match load_image(&image_name, file.bytes).await {
+                        Ok(image) => Msg::ReloadImage(file.path, image),
+                        Err(err) => Msg::Warn(format!("Reload Image Failed: {}", err)),
+                    }
+                });
+
+                false
+            }
+
+            Msg::AppendImages(index, images) => {
+                if images.is_empty() || self.canvas.get_layer(index).is_none() {
+                    return false;
+                }
+
+                self.history.record_snapshot(&self.canvas);
+                for image in images.iter() {
+                    self.pixmaps.remove(image.name().as_str());
+                }
+                self.append_images(index, images);
+                self.watch_image_files(ctx);
+                ctx.link().send_message(Msg::Render);
+
+                true
+            }
+
+            // drop
+            Msg::DropFiles(target, files) => {
+                // the projects are opened by `DropProject`.
+                let files: Vec<gloo_file::File> = files.into_iter().filter(is_image_blob).collect();
+                if files.is_empty() {
+                    return false;
+                }
+
+                ctx.link().send_future(async move {
+                    match load_images(files).await {
+                        Ok(images) => match target {
+                            Some(index) => Msg::AppendImages(index, images),
+                            None => Msg::LoadImages(String::new(), State::Success(images)),
+                        },
+                        Err(err) => Msg::LoadImages(String::new(), State::Failed(err)),
+                    }
+                });
+
+                false
+            }
+
+            Msg::DropProject(files) => {
+                let file = match files.into_iter().find(|file| is_project_file(&file.name())) {
+                    Some(file) => file,
+                    None => return false,
+                };
+
+                ctx.link().send_future(async move {
+                    match load_project(vec![file]).await {
+                        Ok(data) => Msg::LoadProject(None, data),
+                        Err(err) => Msg::Error(err),
+                    }
+                });
+
+                false
+            }
+
+            Msg::NativeDrop(paths) => {
+                if let Some(path) = paths.iter().find(|path| is_project_file(path)).cloned() {
+                    ctx.link().send_future(async move {
+                        match native_open_project_file(path.as_str()).await {
+                            Ok(file) => Msg::LoadProject(Some(file.path), file.bytes),
+                            Err(err) => Msg::Error(err),
+                        }
+                    });
+                    return false;
+                }
+
+                let paths: Vec<String> = paths
+                    .into_iter()
+                    .filter(|path| is_image_file(path))
+                    .collect();
+                if paths.is_empty() {
+                    return false;
+                }
+
+                // `fileDropEnabled` keeps the html drops from the panes, and the native drop
+                // has no position, so the images always become a new layer.
+                ctx.link().send_future(async move {
+                    let images = match native_read_images(&paths).await {
+                        Ok(files) => {
+                            load_native_images(files.into_iter().flatten().collect()).await
+                        }
+                        Err(err) => Err(err),
+                    };
+
+                    match images {
+                        Ok(images) => Msg::LoadImages(String::new(), State::Success(images)),
+                        Err(err) => Msg::LoadImages(String::new(), State::Failed(err)),
+                    }
+                });
+
+                false
+            }
+
+            // clipboard
+            Msg::Paste(files) => {
+                // the pasted images are all named like `image.png`.
+                let names = canvas_new_image_names(&self.canvas, "pasted", files.len());
+                // a variant of the layer picked by the move tool, or a new layer.
+                let target = self.active_layer;
+
+                ctx.link().send_future(async move {
+                    match load_images_as(files, names).await {
+                        Ok(images) => match target {
+                            Some(index) => Msg::AppendImages(index, images),
+                            None => Msg::LoadImages(String::new(), State::Success(images)),
+                        },
+                        Err(err) => Msg::Error(format!("Paste Images Failed: {}", err)),
+                    }
+                });
+
+                false
+            }
+
+            Msg::Copy => {
+                for err in sync_pixmaps(&self.canvas, &mut self.pixmaps) {
+                    ctx.link().send_message(Msg::Warn(err));
+                }
+
+                // the image of the layer picked by the move tool, or the composition.
+                let layer = self
+                    .active_layer
+                    .and_then(|layer_index| self.canvas.get_layer(layer_index));
+                let bytes = match layer {
+                    Some(layer) if layer.is_shape() => shape_to_pixmap(layer.get_shape().unwrap())
+                        .and_then(|pixmap| encode_png(&pixmap)),
+                    Some(layer) => match layer_image(layer, &self.pixmaps)
+                        .and_then(|image| self.pixmaps.get(image.name().as_str()))
+                    {
+                        Some(pixmap) => encode_png(pixmap),
+                        None => Err(format!("the layer {} has no loaded image", layer.name())),
+                    },
+                    None => {
+                        let options = ExportOptions::new(ImageFormat::Png, 1.0, true);
+                        export_canvas_data_url(&self.canvas, &self.pixmaps, &options)
+                            .and_then(|data_url| data_url_bytes(&data_url))
+                    }
+                };
+
+                match bytes {
+                    Ok(bytes) => ctx.link().send_future_batch(async move {
+                        match write_clipboard_png(&bytes).await {
+                            Ok(_) => vec![],
+                            Err(err) => vec![Msg::Error(format!("Copy Image Failed: {}", err))],
+                        }
+                    }),
+                    Err(err) => ctx
+                        .link()
+                        .send_message(Msg::Error(format!("Copy Image Failed: {}", err))),
+                }
+
+                false
+            }
+
+            Msg::ReloadImage(path, reloaded) => {
+                // a change of the file isn't an edit to undo,
+                // the canvases to undo to are reloaded too.
+                let image_names = Self::reload_image(&mut self.canvas, &path, &reloaded);
+                if image_names.is_empty() {
+                    return false;
+                }
+                self.history.for_each_snapshot_mut(|canvas| {
+                    Self::reload_image(canvas, &path, &reloaded);
+                });
+
+                for image_name in image_names.iter() {
+                    self.pixmaps.remove(image_name.as_str());
+                }
+                ctx.link().send_message(Msg::Render);
+
+                true
+            }
+
+            Msg::RelinkImages(missing) => {
+                let names: Vec<String> = missing.iter().map(|image| image.name.clone()).collect();
+                let relink = gloo_dialogs::confirm(
+                    format!(
+                        "The images: {} are not found.\nLook for them in a folder?",
+                        names.join(", ")
+                    )
+                    .as_str(),
+                );
+                if !relink {
+                    return false;
+                }
+
+                ctx.link().send_future_batch(async move {
+                    let files = match native_relink_images(&missing).await {
+                        Ok(Some(files)) => files,
+                        Ok(None) => return vec![],
+                        Err(err) => {
+                            return vec![Msg::Error(format!("Relink Images Failed: {}", err))]
+                        }
+                    };
+
+                    let (images, missing, errors) = load_found_images(names, files).await;
+                    let mut msgs: Vec<Msg> = errors.into_iter().map(Msg::Warn).collect();
+                    for image_name in missing {
+                        msgs.push(Msg::Warn(format!(
+                            "The image: {} is not found.",
+                            image_name
+                        )));
+                    }
+                    if !images.is_empty() {
+                        msgs.push(Msg::LinkImages(images));
+                    }
+                    msgs
+                });
+
+                false
+            }
+
+            // history
+            Msg::Undo => {
+                let hashes = image_hashes(&self.canvas);
+                if self.history.undo(&mut self.canvas) {
+                    drop_changed_pixmaps(&hashes, &self.canvas, &mut self.pixmaps);
+                    self.reset_tools();
+                    ctx.link().send_message(Msg::Render);
+                    true
+                } else {
+                    false
+                }
+            }
+
+            Msg::Redo => {
+                let hashes = image_hashes(&self.canvas);
+                if self.history.redo(&mut self.canvas) {
+                    drop_changed_pixmaps(&hashes, &self.canvas, &mut self.pixmaps);
+                    self.reset_tools();
+                    ctx.link().send_message(Msg::Render);
+                    true
+                } else {
+                    false
+                }
+            }
+
+            Msg::SetHistoryDepth(depth) => {
+                self.history.set_depth(depth);
+                true
+            }
+
             // menu
             Msg::ToggleDisplayMenus => {
                 let canvas_menu = self.toolbar_div_node.cast::<HtmlDivElement>().unwrap();
@@ -151,6 +1031,11 @@ impl Component for App {
             }
 
             // canvas
+            Msg::FontsLoaded => {
+                self.pixmaps.retain(|name, _| !Text::is_key(name));
+                ctx.link().send_message(Msg::Render);
+                false
+            }
             Msg::Render => {
                 let canvas = self.canvas_node.cast::<HtmlCanvasElement>().unwrap();
                 let cctx = canvas
@@ -160,80 +1045,83 @@ impl Component for App {
                     .dyn_into::<CanvasRenderingContext2d>()
                     .unwrap();
 
-                cctx.clear_rect(
-                    0.0,
-                    0.0,
-                    self.canvas.width() as f64,
-                    self.canvas.height() as f64,
-                );
-
                 canvas.set_width(self.canvas.width() as u32);
                 canvas.set_height(self.canvas.height() as u32);
 
-                for layer in self.canvas.layers().iter() {
-                    if layer.show() {
-                        match layer.images().get(layer.selected()) {
-                            Some(image) => match image.data() {
-                                Some(data) => {
-                                    if layer.opacity() < 1.0 {
-                                        cctx.save();
-                                        cctx.set_global_alpha(layer.opacity());
-                                    }
-
-                                    let sx = image.sx();
-                                    let sy = image.sy();
-                                    let sw = image.sw();
-                                    let sh = image.sh();
-                                    let dx = layer.dx();
-                                    let dy = layer.dy();
-                                    let dw = self.canvas.width() as f64;
-                                    let dh = self.canvas.height() as f64;
-
-                                    let mut scale = 1.0;
-
-                                    let max = |x, y| {
-                                        if x > y {
-                                            x
-                                        } else {
-                                            y
-                                        }
-                                    };
-
-                                    if layer.fit() {
-                                        scale = 1.0 / max(sw / dw, sh / dh);
-                                    }
-
-                                    cctx.draw_image_with_image_bitmap_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
-                                            &data,
-                                            sx,
-                                            sy,
-                                            sw,
-                                            sh,
-                                            dx,
-                                            dy,
-                                            sw * scale,
-                                            sh * scale
-                                        )
-                                        .unwrap();
-                                    if layer.opacity() < 1.0 {
-                                        cctx.restore();
-                                    }
-                                }
-                                _ => {
-                                    ctx.link().send_message(Msg::Warn(format!(
-                                        "The layer: {} can't be rendered.",
-                                        layer.name()
-                                    )));
-                                }
-                            },
+                for err in sync_pixmaps(&self.canvas, &mut self.pixmaps) {
+                    ctx.link().send_message(Msg::Warn(err));
+                }
 
-                            None => {
-                                ctx.link().send_message(Msg::Warn(format!(
-                                    "The layer: {} can't be rendered.",
-                                    layer.name()
-                                )));
-                            }
-                        }
+                // the whole image and the crop rect instead of the layers.
+                if let Some(crop) = self.crop {
+                    let source = self
+                        .canvas
+                        .get_layer(crop.layer_index)
+                        .and_then(|layer| layer.images().get(crop.image_index).cloned())
+                        .and_then(|image| self.pixmaps.get(image.name().as_str()));
+                    let source = match source {
+                        Some(source) => source,
+                        None => return false,
+                    };
+
+                    let view = crop.view;
+                    let mut pixmap = Pixmap::new(self.canvas.width(), self.canvas.height());
+                    let placement = Placement {
+                        sx: 0.0,
+                        sy: 0.0,
+                        sw: view.image_width,
+                        sh: view.image_height,
+                        dx: view.offset_x,
+                        dy: view.offset_y,
+                        dw: view.image_width * view.scale,
+                        dh: view.image_height * view.scale,
+                        rotation: 0.0,
+                        flip_h: false,
+                        flip_v: false,
+                        anchor_x: 0.5,
+                        anchor_y: 0.5,
+                        tile: None,
+                        smooth: true,
+                    };
+                    draw_pixmap(
+                        &mut pixmap,
+                        source,
+                        &placement,
+                        1.0,
+                        BlendMode::Normal,
+                        None,
+                    );
+
+                    if let Err(err) = put_pixmap(&cctx, &pixmap) {
+                        ctx.link()
+                            .send_message(Msg::Error(format!("Render Failed: {}", err)));
+                    }
+                    draw_crop_overlay(&cctx, &view, &crop.rect);
+
+                    return true;
+                }
+
+                let (pixmap, failed) = render(&self.canvas, &self.pixmaps);
+                for layer_name in failed {
+                    ctx.link().send_message(Msg::Warn(format!(
+                        "The layer: {} can't be rendered.",
+                        layer_name
+                    )));
+                }
+
+                if let Err(err) = put_pixmap(&cctx, &pixmap) {
+                    ctx.link()
+                        .send_message(Msg::Error(format!("Render Failed: {}", err)));
+                }
+
+                // the layer picked by the move tool.
+                let active_layer = self
+                    .active_layer
+                    .and_then(|layer_index| self.canvas.get_layer(layer_index));
+                if let Some(layer) = active_layer {
+                    if let Some(image) = layer_image(layer, &self.pixmaps) {
+                        let placement = layer_placement(&self.canvas, layer, &image);
+                        draw_placement_outline(&cctx, &placement);
                     }
                 }
 
@@ -242,6 +1130,19 @@ impl Component for App {
 
             Msg::Reset => {
                 self.canvas = Canvas::new();
+                self.pixmaps.clear();
+                self.history.clear();
+                self.reset_tools();
+                self.reset_playback();
+                self.watch_image_files(ctx);
+                if is_tauri() {
+                    ctx.link().send_future_batch(async {
+                        match native_close_project().await {
+                            Ok(_) => vec![Msg::SetProjectPath(None)],
+                            Err(err) => vec![Msg::Error(err)],
+                        }
+                    });
+                }
                 let canvas = self.canvas_node.cast::<HtmlCanvasElement>().unwrap();
                 let cctx = canvas
                     .get_context("2d")
@@ -260,25 +1161,194 @@ impl Component for App {
                 true
             }
 
-            Msg::Save(canvas_name) => {
+            Msg::Save(canvas_name, save_as) => {
                 self.canvas.set_name(&canvas_name);
 
+                if is_tauri() {
+                    self.save_project(ctx, format!("{}.json", canvas_name), save_as, false);
+                    return false;
+                }
+
                 let prefix = "data:application/JSON;charset=utf-8,";
                 let save_data =
                     format!("{}{}", prefix, serde_json::to_string(&self.canvas).unwrap());
 
-                let anchor = self.save_anchor_node.cast::<HtmlAnchorElement>().unwrap();
+                let anchor = self.save_anchor_node.cast::<HtmlAnchorElement>().unwrap();
+
+                anchor.set_href(save_data.as_str());
+
+                anchor.set_download(format!("{}{}", canvas_name, ".json").as_str());
+
+                anchor.click();
+
+                false
+            }
+
+            Msg::SaveBundle(canvas_name) => {
+                self.canvas.set_name(&canvas_name);
+
+                if is_tauri() {
+                    self.save_project(ctx, format!("{}.zip", canvas_name), false, true);
+                    return false;
+                }
+
+                match write_bundle(&self.canvas) {
+                    Ok((bundle, missing)) => {
+                        for image_name in missing {
+                            ctx.link().send_message(Msg::Warn(format!(
+                                "The image: {} is not loaded, it can't be saved in the bundle.",
+                                image_name
+                            )));
+                        }
+
+                        let filename = format!("{}{}", canvas_name, ".zip");
+                        self.save_bytes(ctx, bundle, "application/zip", filename, "Save Bundle");
+                    }
+
+                    Err(err) => {
+                        ctx.link()
+                            .send_message(Msg::Error(format!("Save Bundle Failed: {}", err)));
+                    }
+                }
+
+                false
+            }
+
+            Msg::Export(canvas_name, options) => {
+                self.canvas.set_name(&canvas_name);
+
+                for err in sync_pixmaps(&self.canvas, &mut self.pixmaps) {
+                    ctx.link().send_message(Msg::Warn(err));
+                }
+
+                match export_canvas_data_url(&self.canvas, &self.pixmaps, &options) {
+                    Ok(data_url) if is_tauri() => match data_url_bytes(&data_url) {
+                        Ok(bytes) => self.save_bytes(
+                            ctx,
+                            bytes,
+                            options.format.mime(),
+                            options.filename(&canvas_name),
+                            "Export Image",
+                        ),
+                        Err(err) => ctx
+                            .link()
+                            .send_message(Msg::Error(format!("Export Image Failed: {}", err))),
+                    },
+
+                    Ok(data_url) => {
+                        let anchor = self.save_anchor_node.cast::<HtmlAnchorElement>().unwrap();
+                        anchor.set_href(data_url.as_str());
+                        anchor.set_download(options.filename(&canvas_name).as_str());
+                        anchor.click();
+                    }
+
+                    Err(err) => {
+                        ctx.link()
+                            .send_message(Msg::Error(format!("Export Image Failed: {}", err)));
+                    }
+                }
+
+                false
+            }
+
+            Msg::ExportBatch(canvas_name, options, template, by_scenes) => {
+                self.canvas.set_name(&canvas_name);
+
+                for err in sync_pixmaps(&self.canvas, &mut self.pixmaps) {
+                    ctx.link().send_message(Msg::Warn(err));
+                }
+
+                match export_batch(
+                    &self.canvas,
+                    &self.pixmaps,
+                    &options,
+                    template.as_str(),
+                    by_scenes,
+                ) {
+                    Ok(zip) => {
+                        let filename = format!("{}-batch.zip", canvas_name);
+                        self.save_bytes(ctx, zip, "application/zip", filename, "Export Batch");
+                    }
+
+                    Err(err) => {
+                        ctx.link()
+                            .send_message(Msg::Error(format!("Export Batch Failed: {}", err)));
+                    }
+                }
+
+                false
+            }
+
+            Msg::ExportAnimated(options) => {
+                for err in sync_pixmaps(&self.canvas, &mut self.pixmaps) {
+                    ctx.link().send_message(Msg::Warn(err));
+                }
+
+                let exported = render_frames(&self.canvas, &self.pixmaps, options.transparent)
+                    .and_then(|(frames, failed)| {
+                        for layer_name in failed {
+                            log::warn!("The layer: {} can't be exported.", layer_name);
+                        }
+                        encode_animated(&frames, &options)
+                    });
+                let mut canvas_name = self.canvas.name();
+                if canvas_name.as_str() == "" {
+                    canvas_name = String::from("untitled");
+                }
+                let filename = options.filename(canvas_name.as_str());
+                match exported {
+                    Ok(bytes) => self.save_bytes(
+                        ctx,
+                        bytes,
+                        options.format.mime(),
+                        filename,
+                        "Export Animation",
+                    ),
+                    Err(err) => ctx
+                        .link()
+                        .send_message(Msg::Error(format!("Export Animation Failed: {}", err))),
+                }
+
+                false
+            }
 
-                anchor.set_href(save_data.as_str());
+            Msg::ExportSheet(options) => {
+                for err in sync_pixmaps(&self.canvas, &mut self.pixmaps) {
+                    ctx.link().send_message(Msg::Warn(err));
+                }
 
-                anchor.set_download(format!("{}{}", canvas_name, ".json").as_str());
+                let mut canvas_name = self.canvas.name();
+                if canvas_name.as_str() == "" {
+                    canvas_name = String::from("untitled");
+                }
 
-                anchor.click();
+                let filename = format!("{}-sheet.zip", canvas_name);
+                match export_sprite_sheet(
+                    &self.canvas,
+                    &self.pixmaps,
+                    &options,
+                    canvas_name.as_str(),
+                ) {
+                    Ok(zip) => self.save_bytes(
+                        ctx,
+                        zip,
+                        "application/zip",
+                        filename,
+                        "Export Sprite Sheet",
+                    ),
+                    Err(err) => ctx
+                        .link()
+                        .send_message(Msg::Error(format!("Export Sprite Sheet Failed: {}", err))),
+                }
 
                 false
             }
 
             Msg::Resize(width, height) => {
+                if (width, height) == (self.canvas.width(), self.canvas.height()) {
+                    return false;
+                }
+
                 let canvas = self.canvas_node.cast::<HtmlCanvasElement>().unwrap();
                 let cctx = canvas
                     .get_context("2d")
@@ -293,8 +1363,12 @@ impl Component for App {
                     self.canvas.height() as f64,
                 );
 
-                self.canvas.set_width(width as usize);
-                self.canvas.set_height(height as usize);
+                self.history.record_command(Command::Resize {
+                    before: (self.canvas.width(), self.canvas.height()),
+                    after: (width, height),
+                });
+                self.canvas.set_width(width);
+                self.canvas.set_height(height);
 
                 ctx.link().send_future(async { Msg::Render });
 
@@ -307,101 +1381,749 @@ impl Component for App {
                     return false;
                 }
 
+                let before = match self.canvas.get_layer(layer_index) {
+                    Some(layer) => layer.name(),
+                    None => return false,
+                };
+
+                self.history.record_command(Command::RenameLayer {
+                    index: layer_index,
+                    before,
+                    after: new_layer_name.clone(),
+                });
+                self.canvas
+                    .rename_layer(layer_index, new_layer_name.as_str());
+
+                true
+            }
+
+            Msg::ToggleLayerShow(layer_index) => {
                 let layer = self.canvas.get_mut_layer(layer_index);
+                if let Some(layer) = layer {
+                    layer.set_show(!layer.show());
+                    self.history
+                        .record_command(Command::ToggleLayerShow(layer_index));
+                    ctx.link().send_message(Msg::Render);
+                }
 
-                match layer {
-                    Some(layer) => {
-                        layer.set_name(new_layer_name.as_str());
+                true
+            }
+
+            Msg::MoveLayerIndex(src_layer_index, dst_layer_index) => {
+                self.reset_tools();
+                let (src_layer, dst_layer) = match (
+                    self.canvas.get_layer(src_layer_index),
+                    self.canvas.get_layer(dst_layer_index),
+                ) {
+                    (Some(src_layer), Some(dst_layer)) => (src_layer, dst_layer),
+                    _ => return false,
+                };
+
+                // a layer dropped on a group goes into the group,
+                // else it's swapped with a layer of the same group,
+                // or moved above a layer of another group.
+                if dst_layer.is_group() && !src_layer.is_group() {
+                    let snapshot = self.canvas.clone();
+                    if self
+                        .canvas
+                        .move_layer_into_group(src_layer_index, dst_layer_index)
+                    {
+                        self.history.record(Change::Snapshot(snapshot));
+                    }
+                } else if self.canvas.is_same_group(src_layer_index, dst_layer_index) {
+                    if src_layer.get_tree_len() == dst_layer.get_tree_len() {
+                        self.history
+                            .record_command(Command::SwapLayers(src_layer_index, dst_layer_index));
+                    } else {
+                        // the indexes of the layers between them are changed.
+                        self.history.record_snapshot(&self.canvas);
+                    }
+                    self.canvas.swap_layer(src_layer_index, dst_layer_index);
+                } else {
+                    let snapshot = self.canvas.clone();
+                    if self
+                        .canvas
+                        .move_layer_above(src_layer_index, dst_layer_index)
+                    {
+                        self.history.record(Change::Snapshot(snapshot));
+                    } else {
+                        ctx.link().send_message(Msg::Warn(String::from(
+                            "A group can't be moved into itself.",
+                        )));
                     }
+                }
+
+                ctx.link().send_message(Msg::Render);
+                true
+            }
 
-                    None => {}
+            Msg::SetLayerOpacity(layer_index, opacity) => {
+                let layer = self.canvas.get_mut_layer(layer_index);
+                if let Some(layer) = layer {
+                    let before = layer.opacity();
+                    layer.set_opacity(opacity);
+                    if layer.opacity() != before {
+                        self.history.record_command(Command::SetLayerOpacity {
+                            index: layer_index,
+                            before,
+                            after: layer.opacity(),
+                        });
+                    }
+                    ctx.link().send_message(Msg::Render);
                 }
+                true
+            }
 
+            Msg::SetLayerBlend(layer_index, blend) => {
+                let layer = self.canvas.get_mut_layer(layer_index);
+                if let Some(layer) = layer {
+                    if layer.blend() != blend {
+                        self.history.record_command(Command::SetLayerBlend {
+                            index: layer_index,
+                            before: layer.blend(),
+                            after: blend,
+                        });
+                        layer.set_blend(blend);
+                        ctx.link().send_message(Msg::Render);
+                    }
+                }
                 true
             }
 
-            Msg::ToggleLayerShow(layer_index) => {
+            Msg::SetLayerScale(layer_index, scale) => {
                 let layer = self.canvas.get_mut_layer(layer_index);
-                match layer {
-                    Some(layer) => {
-                        layer.set_show(!layer.show());
+                if let Some(layer) = layer {
+                    if layer.scale() != scale {
+                        self.history.record_command(Command::SetLayerScale {
+                            index: layer_index,
+                            before: layer.scale(),
+                            after: scale,
+                        });
+                        layer.set_scale(scale);
                         ctx.link().send_message(Msg::Render);
                     }
+                }
+                true
+            }
 
-                    None => {}
+            Msg::SetLayerTransform(layer_index, transform) => {
+                let layer = self.canvas.get_mut_layer(layer_index);
+                if let Some(layer) = layer {
+                    let before = layer.transform();
+                    layer.set_transform(transform);
+                    if layer.transform() != before {
+                        self.history.record_command(Command::SetLayerTransform {
+                            index: layer_index,
+                            before,
+                            after: layer.transform(),
+                        });
+                        ctx.link().send_message(Msg::Render);
+                    }
                 }
+                true
+            }
 
+            // text
+            Msg::AddTextLayer => {
+                self.history.record_snapshot(&self.canvas);
+                self.canvas
+                    .append_layer(Layer::text("Text", Text::default()));
+                ctx.link().send_message(Msg::Render);
                 true
             }
 
-            Msg::ToggleLayerFit(layer_index) => {
-                let layer = self.canvas.get_mut_layer(layer_index);
-                match layer {
+            Msg::SetLayerText(layer_index, text) => {
+                let before = self
+                    .canvas
+                    .get_layer(layer_index)
+                    .and_then(|layer| layer.get_text().cloned());
+                if let Some(before) = before.filter(|before| *before != text) {
+                    self.history.record_command(Command::SetLayerText {
+                        index: layer_index,
+                        before,
+                        after: text.clone(),
+                    });
+                    let layer = self.canvas.get_mut_layer(layer_index).unwrap();
+                    *layer.get_mut_text().unwrap() = text;
+                    ctx.link().send_message(Msg::Render);
+                }
+                true
+            }
+
+            // shape
+            Msg::AddShapeLayer => {
+                self.history.record_snapshot(&self.canvas);
+                self.canvas
+                    .append_layer(Layer::shape("Shape", Shape::new(ShapeKind::Rectangle)));
+                ctx.link().send_message(Msg::Render);
+                true
+            }
+
+            Msg::SetLayerShape(layer_index, shape) => {
+                let before = self
+                    .canvas
+                    .get_layer(layer_index)
+                    .and_then(|layer| layer.get_shape().cloned());
+                if let Some(before) = before.filter(|before| *before != shape) {
+                    self.history.record_command(Command::SetLayerShape {
+                        index: layer_index,
+                        before,
+                        after: shape.clone(),
+                    });
+                    let layer = self.canvas.get_mut_layer(layer_index).unwrap();
+                    *layer.get_mut_shape().unwrap() = shape;
+                    ctx.link().send_message(Msg::Render);
+                }
+                true
+            }
+
+            Msg::DuplicateLayer(layer_index) => {
+                self.history.record_snapshot(&self.canvas);
+                canvas_duplicate_layer(&mut self.canvas, layer_index);
+                ctx.link().send_message(Msg::Render);
+                true
+            }
+
+            Msg::DeleteLayer(layer_index) => {
+                self.history.record_snapshot(&self.canvas);
+                self.reset_tools();
+                self.canvas.delete_layer(layer_index);
+                ctx.link().send_message(Msg::Render);
+                true
+            }
+
+            // group
+            Msg::ToggleGroupCollapsed(layer_index) => {
+                match self.canvas.get_mut_layer(layer_index) {
+                    Some(layer) => {
+                        layer.set_collapsed(!layer.collapsed());
+                        true
+                    }
+
+                    None => false,
+                }
+            }
+
+            Msg::GroupLayer(layer_index) => {
+                let snapshot = self.canvas.clone();
+                if self.canvas.group_layer(layer_index, "group") {
+                    self.history.record(Change::Snapshot(snapshot));
+                    self.reset_tools();
+                    ctx.link().send_message(Msg::Render);
+                }
+                true
+            }
+
+            Msg::UngroupLayer(layer_index) => {
+                let snapshot = self.canvas.clone();
+                if self.canvas.ungroup_layer(layer_index) {
+                    self.history.record(Change::Snapshot(snapshot));
+                    self.reset_tools();
+                    ctx.link().send_message(Msg::Render);
+                }
+                true
+            }
+
+            // mask
+            Msg::LoadMask(layer_index, mask) => {
+                let snapshot = self.canvas.clone();
+                match self.canvas.get_mut_layer(layer_index) {
                     Some(layer) => {
-                        layer.set_fit(!layer.fit());
+                        layer.set_mask(Some(mask));
+                        self.history.record(Change::Snapshot(snapshot));
+                        ctx.link().send_message(Msg::Render);
+                        true
+                    }
+
+                    None => false,
+                }
+            }
+
+            Msg::ToggleLayerMask(layer_index) => {
+                let mask = self
+                    .canvas
+                    .get_mut_layer(layer_index)
+                    .and_then(|layer| layer.get_mut_mask());
+                match mask {
+                    Some(mask) => {
+                        mask.set_enabled(!mask.enabled());
+                        self.history
+                            .record_command(Command::ToggleLayerMask(layer_index));
+                        ctx.link().send_message(Msg::Render);
+                        true
+                    }
+
+                    None => false,
+                }
+            }
+
+            Msg::DeleteMask(layer_index) => {
+                let snapshot = self.canvas.clone();
+                match self.canvas.get_mut_layer(layer_index) {
+                    Some(layer) if layer.mask().is_some() => {
+                        layer.set_mask(None);
+                        self.history.record(Change::Snapshot(snapshot));
                         ctx.link().send_message(Msg::Render);
+                        true
                     }
 
-                    None => {}
+                    _ => false,
                 }
+            }
 
+            Msg::SetMaskBrush(brush) => {
+                self.mask_brush = brush;
                 true
             }
 
-            Msg::MoveLayerIndex(src_layer_index, dst_layer_index) => {
-                self.canvas.swap_layer(src_layer_index, dst_layer_index);
+            Msg::PaintMask(x, y, start) => {
+                let brush = match self.mask_brush {
+                    Some(brush) => brush,
+                    None => return false,
+                };
+
+                let layer = match self.canvas.get_layer(brush.layer_index) {
+                    Some(layer) => layer,
+                    None => return false,
+                };
+                let (point, edge) = match (
+                    layer_mask_point(&self.canvas, layer, &self.pixmaps, x, y),
+                    layer_mask_point(
+                        &self.canvas,
+                        layer,
+                        &self.pixmaps,
+                        x + brush.radius,
+                        y + brush.radius,
+                    ),
+                ) {
+                    (Some(point), Some(edge)) => (point, edge),
+                    _ => return false,
+                };
+                let radius = ((edge.0 - point.0).abs(), (edge.1 - point.1).abs());
+                let (width, height) = layer_mask_size(&self.canvas, layer);
+
+                // one stroke is one change.
+                if start {
+                    self.history.record_snapshot(&self.canvas);
+                }
+
+                let layer = self.canvas.get_mut_layer(brush.layer_index).unwrap();
+                if layer.mask().is_none() {
+                    layer.set_mask(Some(Mask::new(width, height)));
+                }
+                layer
+                    .get_mut_mask()
+                    .unwrap()
+                    .paint(point.0, point.1, radius, brush.value);
+
+                ctx.link().send_message(Msg::Render);
+                start
+            }
+
+            // pointer
+            Msg::PointerDown(x, y) => {
+                if let Some(crop) = self.crop.as_mut() {
+                    crop.drag = crop
+                        .view
+                        .handle_at(&crop.rect, x, y)
+                        .map(|handle| (handle, crop.rect, (x, y)));
+                    return false;
+                }
+
+                if self.mask_brush.is_some() {
+                    ctx.link().send_message(Msg::PaintMask(x, y, true));
+                    return false;
+                }
+
+                self.active_layer = layer_at(&self.canvas, &self.pixmaps, x, y);
+                self.nudging.set(self.active_layer.is_some());
+                self.move_drag = self.active_layer.map(|layer_index| MoveDrag {
+                    layer_index,
+                    before: self.canvas.get_layer(layer_index).unwrap().transform(),
+                    start: (x, y),
+                });
                 ctx.link().send_message(Msg::Render);
+                false
+            }
+
+            Msg::PointerMove(x, y) => {
+                if let Some(crop) = self.crop.as_mut() {
+                    let (handle, before, start) = match crop.drag {
+                        Some(drag) => drag,
+                        None => return false,
+                    };
+                    let dx = (x - start.0) / crop.view.scale;
+                    let dy = (y - start.1) / crop.view.scale;
+                    crop.rect = drag_crop(&before, handle, dx, dy, crop.aspect, &crop.view);
+                    ctx.link().send_message(Msg::Render);
+                    return false;
+                }
+
+                if self.mask_brush.is_some() {
+                    ctx.link().send_message(Msg::PaintMask(x, y, false));
+                    return false;
+                }
+
+                let drag = match self.move_drag {
+                    Some(drag) => drag,
+                    None => return false,
+                };
+                if let Some(layer) = self.canvas.get_mut_layer(drag.layer_index) {
+                    let mut transform = drag.before;
+                    transform.dx += (x - drag.start.0).round();
+                    transform.dy += (y - drag.start.1).round();
+                    layer.set_transform(transform);
+                    ctx.link().send_message(Msg::Render);
+                }
+                false
+            }
+
+            Msg::PointerUp => {
+                if let Some(crop) = self.crop.as_mut() {
+                    crop.drag = None;
+                    return false;
+                }
+
+                let drag = match self.move_drag.take() {
+                    Some(drag) => drag,
+                    None => return false,
+                };
+                let after = match self.canvas.get_layer(drag.layer_index) {
+                    Some(layer) => layer.transform(),
+                    None => return false,
+                };
+
+                // one drag is one change.
+                if after != drag.before {
+                    self.history.record_command(Command::SetLayerTransform {
+                        index: drag.layer_index,
+                        before: drag.before,
+                        after,
+                    });
+                }
                 true
             }
 
-            Msg::SetLayerOpacity(layer_index, opacity) => {
-                let layer = self.canvas.get_mut_layer(layer_index);
-                match layer {
+            Msg::NudgeLayer(dx, dy) => {
+                let layer_index = match self.active_layer {
+                    Some(layer_index) => layer_index,
+                    None => return false,
+                };
+
+                match self.canvas.get_mut_layer(layer_index) {
                     Some(layer) => {
-                        layer.set_opacity(opacity);
+                        let before = layer.transform();
+                        let mut after = before;
+                        after.dx += dx;
+                        after.dy += dy;
+                        layer.set_transform(after);
+                        self.history.record_command(Command::SetLayerTransform {
+                            index: layer_index,
+                            before,
+                            after,
+                        });
                         ctx.link().send_message(Msg::Render);
+                        true
                     }
 
-                    None => {}
+                    None => false,
                 }
+            }
+
+            // scene
+            Msg::SaveScene(scene_name) => {
+                self.history.record_snapshot(&self.canvas);
+                self.canvas.save_scene(scene_name.as_str());
                 true
             }
 
-            Msg::DuplicateLayer(layer_index) => {
-                canvas_duplicate_layer(&mut self.canvas, layer_index);
+            Msg::ApplyScene(scene_index) => {
+                let snapshot = self.canvas.clone();
+                if self.canvas.apply_scene(scene_index) {
+                    self.history.record(Change::Snapshot(snapshot));
+                    ctx.link().send_message(Msg::Render);
+                }
+                true
+            }
+
+            Msg::UpdateScene(scene_index) => {
+                let scene_name = match self.canvas.get_scenes().get(scene_index) {
+                    Some(scene) => scene.name(),
+                    None => return false,
+                };
+
+                self.history.record_snapshot(&self.canvas);
+                self.canvas.save_scene(scene_name.as_str());
+                true
+            }
+
+            Msg::DeleteScene(scene_index) => {
+                let snapshot = self.canvas.clone();
+                if self.canvas.delete_scene(scene_index).is_some() {
+                    self.history.record(Change::Snapshot(snapshot));
+                }
+                true
+            }
+
+            // timeline
+            Msg::TogglePlayback => {
+                if self.playback.take().is_some() {
+                    return true;
+                }
+
+                // a finished animation starts again.
+                let animation = self.canvas.animation();
+                if animation.mode() == PlaybackMode::Once
+                    && self.frame + 1 >= self.canvas.timeline_len()
+                {
+                    self.frame = 0;
+                }
+                self.playback = Some(Playback {
+                    start: None,
+                    _request: Self::request_tick(ctx),
+                });
+                true
+            }
+
+            Msg::StopPlayback => {
+                self.reset_playback();
+                self.canvas.set_frame(0);
                 ctx.link().send_message(Msg::Render);
                 true
             }
 
-            Msg::DeleteLayer(layer_index) => {
-                self.canvas.delete_layer(layer_index);
+            Msg::SetAnimationFps(fps) => {
+                let before = self.canvas.animation();
+                self.canvas.get_mut_animation().set_fps(fps);
+                let after = self.canvas.animation();
+                if before != after {
+                    self.history
+                        .record_command(Command::SetAnimation { before, after });
+                    // go on from the shown frame.
+                    if let Some(playback) = self.playback.as_mut() {
+                        playback.start = None;
+                    }
+                }
+                true
+            }
+
+            Msg::SetPlaybackMode(mode) => {
+                let before = self.canvas.animation();
+                self.canvas.get_mut_animation().set_mode(mode);
+                let after = self.canvas.animation();
+                if before != after {
+                    self.history
+                        .record_command(Command::SetAnimation { before, after });
+                    self.frame %= self.canvas.timeline_len();
+                    if let Some(playback) = self.playback.as_mut() {
+                        playback.start = None;
+                    }
+                }
+                true
+            }
+
+            Msg::Tick(time) => {
+                let animation = self.canvas.animation();
+                let timeline_len = self.canvas.timeline_len();
+                let shown = self.frame;
+                let playback = match self.playback.as_mut() {
+                    Some(playback) => playback,
+                    None => return false,
+                };
+
+                let start = *playback
+                    .start
+                    .get_or_insert(time - shown as f64 * 1000.0 / animation.fps());
+                let mut frame = animation.frame_at(time - start);
+                let finished = animation.mode() == PlaybackMode::Once && frame + 1 >= timeline_len;
+                if finished {
+                    frame = timeline_len - 1;
+                    self.playback = None;
+                } else {
+                    frame %= timeline_len;
+                    playback._request = Self::request_tick(ctx);
+                }
+
+                if frame == shown {
+                    return finished;
+                }
+                self.frame = frame;
+                self.canvas.set_frame(frame);
                 ctx.link().send_message(Msg::Render);
                 true
             }
 
-            // image
-            Msg::PrevImage(layer_index) => {
-                let layer = self.canvas.get_mut_layer(layer_index);
-                match layer {
+            Msg::ToggleLayerAnimated(layer_index) => match self.canvas.get_mut_layer(layer_index) {
+                Some(layer) => {
+                    self.history
+                        .record_command(Command::ToggleLayerAnimated(layer_index));
+                    layer.set_animated(!layer.animated());
+                    true
+                }
+
+                None => false,
+            },
+
+            Msg::SetLayerFrameOffset(layer_index, offset) => {
+                match self.canvas.get_mut_layer(layer_index) {
                     Some(layer) => {
-                        layer.prev();
-                        ctx.link().send_message(Msg::Render);
+                        if layer.frame_offset() != offset {
+                            self.history.record_command(Command::SetLayerFrameOffset {
+                                index: layer_index,
+                                before: layer.frame_offset(),
+                                after: offset,
+                            });
+                            layer.set_frame_offset(offset);
+                        }
+                        true
+                    }
+
+                    None => false,
+                }
+            }
+
+            // crop
+            Msg::StartCrop(layer_index) => {
+                self.reset_tools();
+                self.playback = None;
+                for err in sync_pixmaps(&self.canvas, &mut self.pixmaps) {
+                    ctx.link().send_message(Msg::Warn(err));
+                }
+
+                let layer = match self.canvas.get_layer(layer_index) {
+                    Some(layer) => layer,
+                    None => return false,
+                };
+                let image = match layer.get_selected_image() {
+                    Some(image) => image,
+                    None => {
+                        ctx.link()
+                            .send_message(Msg::Warn(String::from("No image to crop.")));
+                        return false;
+                    }
+                };
+                let pixmap = match self.pixmaps.get(image.name().as_str()) {
+                    Some(pixmap) => pixmap,
+                    None => {
+                        ctx.link().send_message(Msg::Warn(format!(
+                            "The image: {} is not loaded.",
+                            image.name()
+                        )));
+                        return false;
                     }
+                };
+
+                let (width, height) = (pixmap.width() as f64, pixmap.height() as f64);
+                let before = CropRect::of_image(image);
+                self.crop = Some(Crop {
+                    layer_index,
+                    image_index: layer.selected(),
+                    before,
+                    rect: before.clamp(width, height),
+                    aspect: None,
+                    view: CropView::new(
+                        width,
+                        height,
+                        self.canvas.width() as f64,
+                        self.canvas.height() as f64,
+                    ),
+                    drag: None,
+                });
+                ctx.link().send_message(Msg::Render);
+                true
+            }
 
-                    None => {}
+            Msg::SetCropRect(rect) => {
+                let crop = match self.crop.as_mut() {
+                    Some(crop) => crop,
+                    None => return false,
+                };
+
+                let mut rect = rect;
+                if let Some(aspect) = crop.aspect {
+                    if rect.w != crop.rect.w {
+                        rect.h = rect.w / aspect;
+                    } else {
+                        rect.w = rect.h * aspect;
+                    }
                 }
+                crop.rect = rect.clamp(crop.view.image_width, crop.view.image_height);
+                ctx.link().send_message(Msg::Render);
                 true
             }
 
-            Msg::NextImage(layer_index) => {
+            Msg::ToggleCropAspectLock => match self.crop.as_mut() {
+                Some(crop) => {
+                    crop.aspect = match crop.aspect {
+                        Some(_) => None,
+                        None => Some(crop.rect.w / crop.rect.h),
+                    };
+                    true
+                }
+                None => false,
+            },
+
+            Msg::ApplyCrop => {
+                let crop = match self.crop.take() {
+                    Some(crop) => crop,
+                    None => return false,
+                };
+
+                let image = self
+                    .canvas
+                    .get_mut_layer(crop.layer_index)
+                    .and_then(|layer| layer.get_mut_images().get_mut(crop.image_index));
+                if let Some(image) = image {
+                    if crop.rect != crop.before {
+                        crop.rect.apply_to(image);
+                        self.history.record_command(Command::SetImageCrop {
+                            index: crop.layer_index,
+                            image: crop.image_index,
+                            before: crop.before,
+                            after: crop.rect,
+                        });
+                    }
+                }
+                ctx.link().send_message(Msg::Render);
+                true
+            }
+
+            Msg::CancelCrop => {
+                self.crop = None;
+                ctx.link().send_message(Msg::Render);
+                true
+            }
+
+            Msg::PrevImage(layer_index) => {
                 let layer = self.canvas.get_mut_layer(layer_index);
-                match layer {
-                    Some(layer) => {
-                        layer.next();
-                        ctx.link().send_message(Msg::Render);
+                if let Some(layer) = layer {
+                    let before = layer.selected();
+                    layer.prev();
+                    if layer.selected() != before {
+                        self.history.record_command(Command::SelectImage {
+                            index: layer_index,
+                            before,
+                            after: layer.selected(),
+                        });
                     }
+                    ctx.link().send_message(Msg::Render);
+                }
+                true
+            }
 
-                    None => {}
+            Msg::NextImage(layer_index) => {
+                let layer = self.canvas.get_mut_layer(layer_index);
+                if let Some(layer) = layer {
+                    let before = layer.selected();
+                    layer.next();
+                    if layer.selected() != before {
+                        self.history.record_command(Command::SelectImage {
+                            index: layer_index,
+                            before,
+                            after: layer.selected(),
+                        });
+                    }
+                    ctx.link().send_message(Msg::Render);
                 }
                 true
             }
@@ -413,8 +2135,19 @@ impl Component for App {
 
                 let dst_layer_index = self.canvas.get_layer_index(dst_layer_name.as_str());
                 match dst_layer_index {
+                    Some(dst_layer_index)
+                        if self.canvas.get_layer(dst_layer_index).unwrap().is_group() =>
+                    {
+                        ctx.link().send_message(Msg::Warn(format!(
+                            "The layer: {} is a group, images can't be moved into it.",
+                            dst_layer_name
+                        )));
+                        return false;
+                    }
+
                     Some(dst_layer_index) => {
                         if src_layer_index != dst_layer_index {
+                            self.history.record_snapshot(&self.canvas);
                             move_image_to_other_layer(
                                 &mut self.canvas,
                                 src_layer_index,
@@ -425,6 +2158,7 @@ impl Component for App {
                     }
 
                     None => {
+                        self.history.record_snapshot(&self.canvas);
                         canvas_insert_new_fit_layer(
                             &mut self.canvas,
                             src_layer_index,
@@ -442,11 +2176,13 @@ impl Component for App {
             }
 
             Msg::DeleteImage(layer_index) => {
+                let snapshot = self.canvas.clone();
                 let layer = self.canvas.get_mut_layer(layer_index);
                 match layer {
                     Some(layer) => {
                         if layer.images().len() > 1 {
                             layer.remove_selected_image().unwrap();
+                            self.history.record(Change::Snapshot(snapshot));
                             ctx.link().send_message(Msg::Render);
                         } else {
                             ctx.link().send_message(Msg::DeleteLayer(layer_index));
@@ -472,6 +2208,8 @@ impl Component for App {
         }
     }
 
+    // `props!` and `html!` expand the props of components into bare statements.
+    #[allow(clippy::unnecessary_operation)]
     fn view(&self, ctx: &Context<Self>) -> Html {
         let topbar_props = props!(TopBar::Properties {
             callback: Some(ctx.link().callback(|_| Msg::ToggleDisplayMenus)),
@@ -480,19 +2218,79 @@ impl Component for App {
         let toolbar_props = props!(ToolBar::Properties {
             width: self.canvas.width(),
             height: self.canvas.height(),
+            history_depth: self.history.depth(),
+            can_undo: self.history.can_undo(),
+            can_redo: self.history.can_redo(),
             callback: Some(ctx.link().callback(|msg| msg)),
         });
 
+        let pointer_down = ctx.link().batch_callback(|event: MouseEvent| {
+            if event.button() != 0 {
+                return None;
+            }
+            Some(Msg::PointerDown(
+                event.offset_x() as f64,
+                event.offset_y() as f64,
+            ))
+        });
+        let pointer_move = ctx.link().batch_callback(|event: MouseEvent| {
+            // only while the main button is down.
+            if event.buttons() & 1 == 0 {
+                return None;
+            }
+            Some(Msg::PointerMove(
+                event.offset_x() as f64,
+                event.offset_y() as f64,
+            ))
+        });
+
         html! {
             <div>
                 <TopBar ..topbar_props/>
                 <div ref={self.toolbar_div_node.clone()}>
                     <ToolBar ..toolbar_props/>
+                    <div class="flex justify-center">
+                        <SceneBar
+                            scenes={self.canvas.get_scenes().iter().map(|scene| scene.name()).collect::<Vec<String>>()}
+                            callback={Some(ctx.link().callback(|msg| msg))}
+                        />
+                        <TimelineBar
+                            playing={self.playback.is_some()}
+                            frame={self.frame}
+                            timeline_len={self.canvas.timeline_len()}
+                            animation={self.canvas.animation()}
+                            callback={Some(ctx.link().callback(|msg| msg))}
+                        />
+                        <SheetBar callback={Some(ctx.link().callback(|msg| msg))}/>
+                    </div>
                 </div>
+                {
+                    match self.crop {
+                        Some(crop) => html! {
+                            <CropBar
+                                rect={crop.rect}
+                                image_width={crop.view.image_width}
+                                image_height={crop.view.image_height}
+                                lock_aspect={crop.aspect.is_some()}
+                                callback={Some(ctx.link().callback(|msg| msg))}
+                            />
+                        },
+                        None => html! {},
+                    }
+                }
                 <div class="flex justify-center items-start">
                     <div class="border-2 m-4 overflow-scroll">
                         <canvas
                             ref={self.canvas_node.clone()}
+                            onmousedown={pointer_down}
+                            onmousemove={pointer_move}
+                            onmouseup={ctx.link().callback(|_| Msg::PointerUp)}
+                            onmouseleave={ctx.link().callback(|_| Msg::PointerUp)}
+                            ondragover={Callback::from(|event: DragEvent| event.prevent_default())}
+                            ondrop={ctx.link().callback(|event: DragEvent| {
+                                event.prevent_default();
+                                Msg::DropFiles(None, dropped_files(&event))
+                            })}
                             width={self.canvas.width().to_string()}
                             height={self.canvas.height().to_string()}
                         />
@@ -502,7 +2300,7 @@ impl Component for App {
                             <ul class="w-128 h-128 p-4">
                                 <div class="fixed overflow-auto w-128 h-128 border-2">
                                 {
-                                    for self.canvas.layers().iter().enumerate().rev().map(|(index, layer)| {
+                                    for canvas_display_layers(&self.canvas).into_iter().map(|(index, depth, layer)| {
                                         let (image_name, image_state) = match layer.get_selected_image() {
                                             Some(image) => {
                                                 match image.data() {
@@ -518,11 +2316,23 @@ impl Component for App {
                                             layer_name: layer.name(),
                                             layer_selected: layer.selected(),
                                             layer_show: layer.show(),
-                                            layer_fit: layer.fit(),
+                                            layer_scale: layer.scale(),
                                             images_len: layer.images().len(),
                                             image_name,
                                             image_state,
                                             layer_opacity: layer.opacity(),
+                                            layer_blend: layer.blend(),
+                                            layer_transform: layer.transform(),
+                                            layer_animated: layer.animated(),
+                                            layer_frame_offset: layer.frame_offset(),
+                                            depth,
+                                            is_group: layer.is_group(),
+                                            text: layer.get_text().cloned(),
+                                            shape: layer.get_shape().cloned(),
+                                            collapsed: layer.collapsed(),
+                                            has_mask: layer.mask().is_some(),
+                                            mask_enabled: layer.mask().map(|mask| mask.enabled()).unwrap_or(false),
+                                            mask_painting: self.mask_brush.map(|brush| brush.layer_index) == Some(index),
                                             callback: Some(ctx.link().callback(|msg| msg))
                                         });
 
@@ -544,5 +2354,3 @@ impl Component for App {
         }
     }
 }
-
-impl App {}
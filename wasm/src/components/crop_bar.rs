@@ -0,0 +1,212 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-27 16:45:19
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-27 19:20:52
+ * @FilePath: /layer-painter/wasm/src/components/crop_bar.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use crate::components::app::Msg as AppMsg;
+use crate::utils::CropRect;
+
+use web_sys::HtmlInputElement;
+use yew::{html, Callback, Component, Context, Html, NodeRef, Properties};
+
+pub enum Msg {
+    SetCropRect,
+    ToggleAspectLock,
+    Reset,
+    Cancel,
+    Apply,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct Props {
+    pub rect: CropRect,
+    pub image_width: f64,
+    pub image_height: f64,
+    pub lock_aspect: bool,
+    pub callback: Option<Callback<AppMsg>>,
+}
+
+/// The numeric entry and the actions of the crop editor.
+pub struct CropBar {
+    x_input_node: NodeRef,
+    y_input_node: NodeRef,
+    w_input_node: NodeRef,
+    h_input_node: NodeRef,
+}
+
+impl Component for CropBar {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            x_input_node: NodeRef::default(),
+            y_input_node: NodeRef::default(),
+            w_input_node: NodeRef::default(),
+            h_input_node: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let app_msg = match msg {
+            Msg::SetCropRect => {
+                let before = ctx.props().rect;
+                let number = |node: &NodeRef, before: f64| {
+                    let value = node.cast::<HtmlInputElement>().unwrap().value_as_number();
+                    if value.is_nan() {
+                        before
+                    } else {
+                        value
+                    }
+                };
+
+                AppMsg::SetCropRect(CropRect {
+                    x: number(&self.x_input_node, before.x),
+                    y: number(&self.y_input_node, before.y),
+                    w: number(&self.w_input_node, before.w),
+                    h: number(&self.h_input_node, before.h),
+                })
+            }
+            Msg::ToggleAspectLock => AppMsg::ToggleCropAspectLock,
+            Msg::Reset => AppMsg::SetCropRect(CropRect {
+                x: 0.0,
+                y: 0.0,
+                w: ctx.props().image_width,
+                h: ctx.props().image_height,
+            }),
+            Msg::Cancel => AppMsg::CancelCrop,
+            Msg::Apply => AppMsg::ApplyCrop,
+        };
+        ctx.props().callback.clone().unwrap().emit(app_msg);
+
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+
+        html! {
+            <nav class="m-2 px-4 flex flex-nowrap shrink-0 justify-between items-center bg-white h-16 border-2">
+                <ul class="shrink-0">
+                    <label class="input-label">
+                        <span>{format!("Crop ({} x {})", props.image_width, props.image_height)}</span>
+                    </label>
+                </ul>
+                <ul class="shrink-0 flex items-center">
+                    <span class="input-label ml-1">{"x: "}</span>
+                    <input
+                        class="input-float"
+                        style="width: 4rem"
+                        ref={self.x_input_node.clone()}
+                        type="number"
+                        min="0"
+                        value={props.rect.x.to_string()}
+                        onchange={ctx.link().callback(|_| Msg::SetCropRect)}
+                    />
+                    <span class="input-label ml-1">{"y: "}</span>
+                    <input
+                        class="input-float"
+                        style="width: 4rem"
+                        ref={self.y_input_node.clone()}
+                        type="number"
+                        min="0"
+                        value={props.rect.y.to_string()}
+                        onchange={ctx.link().callback(|_| Msg::SetCropRect)}
+                    />
+                    <span class="input-label ml-1">{"w: "}</span>
+                    <input
+                        class="input-float"
+                        style="width: 4rem"
+                        ref={self.w_input_node.clone()}
+                        type="number"
+                        min="1"
+                        value={props.rect.w.to_string()}
+                        onchange={ctx.link().callback(|_| Msg::SetCropRect)}
+                    />
+                    <span class="input-label ml-1">{"h: "}</span>
+                    <input
+                        class="input-float"
+                        style="width: 4rem"
+                        ref={self.h_input_node.clone()}
+                        type="number"
+                        min="1"
+                        value={props.rect.h.to_string()}
+                        onchange={ctx.link().callback(|_| Msg::SetCropRect)}
+                    />
+                </ul>
+                <ul class="shrink-0 flex items-center ml-2">
+                    <label class="input-label" for="crop-aspect-lock-checkbox">
+                        <span>{"Lock Ratio"}</span>
+                    </label>
+                    <input
+                        class="ml-1"
+                        id="crop-aspect-lock-checkbox"
+                        type="checkbox"
+                        checked={props.lock_aspect}
+                        onchange={ctx.link().callback(|_| Msg::ToggleAspectLock)}
+                    />
+                </ul>
+                <ul class="shrink-0 flex items-center ml-2">
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Reset)}
+                        title="whole image"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M4 8V4m0 0h4M4 4l5 5m11-1V4m0 0h-4m4 0l-5 5M4 16v4m0 0h4m-4 0l5-5m11 5l-5-5m5 5v-4m0 4h-4"
+                            />
+                        </svg>
+                    </button>
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Cancel)}
+                        title="cancel"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M6 18L18 6M6 6l12 12"
+                            />
+                        </svg>
+                    </button>
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Apply)}
+                        title="apply"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M5 13l4 4L19 7"
+                            />
+                        </svg>
+                    </button>
+                </ul>
+            </nav>
+        }
+    }
+}
@@ -7,15 +7,21 @@
  * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
  */
 
-mod topbar;
-mod toolbar;
 mod app;
-mod pane;
 mod button;
+mod crop_bar;
+mod pane;
+mod scene_bar;
+mod sheet_bar;
+mod timeline_bar;
+mod toolbar;
+mod topbar;
 
-pub use topbar::{TopBar};
-pub use toolbar::{ToolBar, Props};
-pub use app::{App};
-pub use pane::{Pane};
-pub use button::{Button};
-
+pub use app::App;
+pub use crop_bar::CropBar;
+pub use pane::Pane;
+pub use scene_bar::SceneBar;
+pub use sheet_bar::SheetBar;
+pub use timeline_bar::TimelineBar;
+pub use toolbar::ToolBar;
+pub use topbar::TopBar;
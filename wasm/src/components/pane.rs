@@ -7,10 +7,15 @@
  * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
  */
 
-use crate::components::app::Msg as AppMsg;
+use crate::canvas::{BlendMode, MaskMode, ScaleMode, Shape, ShapeKind, Text, TextAlign, Transform};
+use crate::components::app::{MaskBrush, Msg as AppMsg};
 use crate::components::button::Button;
+use crate::utils::{dropped_files, load_mask};
 
-use web_sys::{DragEvent, HtmlDivElement, HtmlInputElement, HtmlLabelElement};
+use web_sys::{
+    DragEvent, HtmlDivElement, HtmlInputElement, HtmlLabelElement, HtmlSelectElement,
+    HtmlTextAreaElement,
+};
 use yew::{html, Callback, Component, Context, Html, NodeRef, Properties};
 
 pub enum Msg {
@@ -27,6 +32,7 @@ pub enum Msg {
     DragOver,
     DragLeave,
     DragDrop(usize),
+    DropFiles(Vec<gloo_file::File>),
 
     // image
     PrevImage,
@@ -39,10 +45,33 @@ pub enum Msg {
     RenameLayer,
     MoveLayerIndex(usize, usize),
     ToggleLayerShow,
-    ToggleLayerFit,
     SetLayerOpacity,
+    SetLayerBlend,
+    SetLayerScale,
+    SetLayerTransform,
+    ToggleLayerAnimated,
+    SetLayerFrameOffset,
     DuplicateLayer,
     DeleteLayer,
+    StartCrop,
+
+    // group
+    ToggleGroupCollapsed,
+    GroupLayer,
+    UngroupLayer,
+
+    // text
+    SetLayerText,
+
+    // shape
+    SetLayerShape,
+
+    // mask
+    FetchMask(Vec<gloo_file::File>),
+    ToggleLayerMask,
+    DeleteMask,
+    ToggleMaskPainting,
+    UpdateMaskBrush,
 }
 
 #[derive(PartialEq, Properties)]
@@ -51,11 +80,23 @@ pub struct Props {
     pub layer_name: String,
     pub layer_selected: usize,
     pub layer_show: bool,
-    pub layer_fit: bool,
+    pub layer_scale: ScaleMode,
     pub images_len: usize,
     pub image_name: String,
     pub image_state: bool,
     pub layer_opacity: f64,
+    pub layer_blend: BlendMode,
+    pub layer_transform: Transform,
+    pub layer_animated: bool,
+    pub layer_frame_offset: usize,
+    pub depth: usize,
+    pub is_group: bool,
+    pub collapsed: bool,
+    pub text: Option<Text>,
+    pub shape: Option<Shape>,
+    pub has_mask: bool,
+    pub mask_enabled: bool,
+    pub mask_painting: bool,
     pub callback: Option<Callback<AppMsg>>,
 }
 
@@ -65,9 +106,43 @@ pub struct Pane {
     layer_name_input_node: NodeRef,
     layer_opacity_label_node: NodeRef,
     layer_opacity_input_node: NodeRef,
+    layer_blend_select_node: NodeRef,
+    layer_scale_select_node: NodeRef,
     dst_layer_name_div_node: NodeRef,
     dst_layer_name_input_node: NodeRef,
     buttons_div_node: NodeRef,
+    mask_mode_select_node: NodeRef,
+    mask_upload_node: NodeRef,
+    brush_size_input_node: NodeRef,
+    brush_value_select_node: NodeRef,
+    dx_input_node: NodeRef,
+    dy_input_node: NodeRef,
+    dw_input_node: NodeRef,
+    dh_input_node: NodeRef,
+    rotation_input_node: NodeRef,
+    anchor_x_input_node: NodeRef,
+    anchor_y_input_node: NodeRef,
+    flip_h_input_node: NodeRef,
+    flip_v_input_node: NodeRef,
+    frame_offset_input_node: NodeRef,
+    text_content_node: NodeRef,
+    font_family_input_node: NodeRef,
+    font_size_input_node: NodeRef,
+    font_weight_select_node: NodeRef,
+    text_color_input_node: NodeRef,
+    text_align_select_node: NodeRef,
+    line_height_input_node: NodeRef,
+    letter_spacing_input_node: NodeRef,
+    shape_kind_select_node: NodeRef,
+    shape_width_input_node: NodeRef,
+    shape_height_input_node: NodeRef,
+    shape_fill_input_node: NodeRef,
+    shape_fill_color_input_node: NodeRef,
+    shape_stroke_input_node: NodeRef,
+    shape_stroke_color_input_node: NodeRef,
+    stroke_width_input_node: NodeRef,
+    corner_radius_input_node: NodeRef,
+    shape_points_input_node: NodeRef,
 }
 
 impl Component for Pane {
@@ -81,9 +156,43 @@ impl Component for Pane {
             layer_name_input_node: NodeRef::default(),
             layer_opacity_label_node: NodeRef::default(),
             layer_opacity_input_node: NodeRef::default(),
+            layer_blend_select_node: NodeRef::default(),
+            layer_scale_select_node: NodeRef::default(),
             dst_layer_name_div_node: NodeRef::default(),
             dst_layer_name_input_node: NodeRef::default(),
             buttons_div_node: NodeRef::default(),
+            mask_mode_select_node: NodeRef::default(),
+            mask_upload_node: NodeRef::default(),
+            brush_size_input_node: NodeRef::default(),
+            brush_value_select_node: NodeRef::default(),
+            dx_input_node: NodeRef::default(),
+            dy_input_node: NodeRef::default(),
+            dw_input_node: NodeRef::default(),
+            dh_input_node: NodeRef::default(),
+            rotation_input_node: NodeRef::default(),
+            anchor_x_input_node: NodeRef::default(),
+            anchor_y_input_node: NodeRef::default(),
+            flip_h_input_node: NodeRef::default(),
+            flip_v_input_node: NodeRef::default(),
+            frame_offset_input_node: NodeRef::default(),
+            text_content_node: NodeRef::default(),
+            font_family_input_node: NodeRef::default(),
+            font_size_input_node: NodeRef::default(),
+            font_weight_select_node: NodeRef::default(),
+            text_color_input_node: NodeRef::default(),
+            text_align_select_node: NodeRef::default(),
+            line_height_input_node: NodeRef::default(),
+            letter_spacing_input_node: NodeRef::default(),
+            shape_kind_select_node: NodeRef::default(),
+            shape_width_input_node: NodeRef::default(),
+            shape_height_input_node: NodeRef::default(),
+            shape_fill_input_node: NodeRef::default(),
+            shape_fill_color_input_node: NodeRef::default(),
+            shape_stroke_input_node: NodeRef::default(),
+            shape_stroke_color_input_node: NodeRef::default(),
+            stroke_width_input_node: NodeRef::default(),
+            corner_radius_input_node: NodeRef::default(),
+            shape_points_input_node: NodeRef::default(),
         }
     }
 
@@ -174,6 +283,10 @@ impl Component for Pane {
                 layer_opacity_input_node.set_class_name("hidden");
                 dst_layer_name_div_node.set_class_name("hidden");
                 dst_layer_name_input_node.set_value("");
+                self.mask_upload_node
+                    .cast::<HtmlInputElement>()
+                    .unwrap()
+                    .set_value("");
             }
 
             Msg::DragStart => {
@@ -193,12 +306,55 @@ impl Component for Pane {
             Msg::DragDrop(layer_index) => {
                 let dst_layer_index = ctx.props().index;
                 if layer_index != dst_layer_index {
-                    ctx.link().send_message(Msg::MoveLayerIndex(layer_index, dst_layer_index));
+                    ctx.link()
+                        .send_message(Msg::MoveLayerIndex(layer_index, dst_layer_index));
                 }
                 let pane_div = self.pane_div_node.cast::<HtmlDivElement>().unwrap();
                 pane_div.set_class_name("mx-1 my-2 py-2 hover:cursor-grab");
             }
 
+            Msg::DropFiles(files) => {
+                let pane_div = self.pane_div_node.cast::<HtmlDivElement>().unwrap();
+                pane_div.set_class_name("mx-1 my-2 py-2 hover:cursor-grab");
+
+                ctx.props()
+                    .callback
+                    .clone()
+                    .unwrap()
+                    .emit(AppMsg::DropFiles(Some(index), files));
+            }
+
+            Msg::FetchMask(files) => {
+                let mode = self
+                    .mask_mode_select_node
+                    .cast::<HtmlSelectElement>()
+                    .unwrap()
+                    .value();
+                let mode = MaskMode::from_name(mode.as_str()).unwrap_or(MaskMode::Luminance);
+
+                let load_callback = ctx.props().callback.clone().unwrap();
+                ctx.link().send_future(async move {
+                    match load_mask(files, mode).await {
+                        Ok(mask) => load_callback.emit(AppMsg::LoadMask(index, mask)),
+                        Err(err) => {
+                            load_callback.emit(AppMsg::Error(format!("Read Mask Failed: {}", err)))
+                        }
+                    }
+                    Msg::Refresh
+                });
+            }
+
+            Msg::UpdateMaskBrush => {
+                if ctx.props().mask_painting {
+                    let brush = self.mask_brush(index);
+                    ctx.props()
+                        .callback
+                        .clone()
+                        .unwrap()
+                        .emit(AppMsg::SetMaskBrush(Some(brush)));
+                }
+            }
+
             others => {
                 let app_msg = match others {
                     // image
@@ -234,8 +390,7 @@ impl Component for Pane {
                     Msg::ToggleLayerShow => {
                         ctx.link().send_message(Msg::CloseLayerMenu);
                         AppMsg::ToggleLayerShow(index)
-                    },
-                    Msg::ToggleLayerFit => AppMsg::ToggleLayerFit(index),
+                    }
                     Msg::SetLayerOpacity => {
                         let layer_opacity_input_node = self
                             .layer_opacity_input_node
@@ -250,8 +405,83 @@ impl Component for Pane {
 
                         AppMsg::SetLayerOpacity(index, opacity)
                     }
+                    Msg::SetLayerBlend => {
+                        let blend = self
+                            .layer_blend_select_node
+                            .cast::<HtmlSelectElement>()
+                            .unwrap()
+                            .value();
+
+                        match BlendMode::from_name(blend.as_str()) {
+                            Some(blend) => AppMsg::SetLayerBlend(index, blend),
+                            None => AppMsg::Warn(format!("Unknown blend mode: {}", blend)),
+                        }
+                    }
+                    Msg::SetLayerScale => {
+                        let scale = self
+                            .layer_scale_select_node
+                            .cast::<HtmlSelectElement>()
+                            .unwrap()
+                            .value();
+
+                        match ScaleMode::from_name(scale.as_str()) {
+                            Some(scale) => AppMsg::SetLayerScale(index, scale),
+                            None => AppMsg::Warn(format!("Unknown scale mode: {}", scale)),
+                        }
+                    }
+                    Msg::SetLayerTransform => {
+                        AppMsg::SetLayerTransform(index, self.layer_transform(ctx))
+                    }
+                    Msg::ToggleLayerAnimated => AppMsg::ToggleLayerAnimated(index),
+                    Msg::SetLayerFrameOffset => {
+                        let input = self
+                            .frame_offset_input_node
+                            .cast::<HtmlInputElement>()
+                            .unwrap();
+                        let offset = input.value_as_number();
+                        if offset.is_nan() || offset < 0.0 {
+                            input.set_value(ctx.props().layer_frame_offset.to_string().as_str());
+                            return false;
+                        }
+
+                        AppMsg::SetLayerFrameOffset(index, offset as usize)
+                    }
                     Msg::DuplicateLayer => AppMsg::DuplicateLayer(index),
                     Msg::DeleteLayer => AppMsg::DeleteLayer(index),
+                    Msg::StartCrop => {
+                        ctx.link().send_message(Msg::CloseLayerMenu);
+                        AppMsg::StartCrop(index)
+                    }
+                    // group
+                    Msg::ToggleGroupCollapsed => AppMsg::ToggleGroupCollapsed(index),
+                    Msg::GroupLayer => {
+                        ctx.link().send_message(Msg::CloseLayerMenu);
+                        AppMsg::GroupLayer(index)
+                    }
+                    Msg::UngroupLayer => {
+                        ctx.link().send_message(Msg::CloseLayerMenu);
+                        AppMsg::UngroupLayer(index)
+                    }
+                    // text
+                    Msg::SetLayerText => match self.layer_text(ctx) {
+                        Some(text) => AppMsg::SetLayerText(index, text),
+                        None => return false,
+                    },
+                    // shape
+                    Msg::SetLayerShape => match self.layer_shape(ctx) {
+                        Some(shape) => AppMsg::SetLayerShape(index, shape),
+                        None => return false,
+                    },
+                    // mask
+                    Msg::ToggleLayerMask => AppMsg::ToggleLayerMask(index),
+                    Msg::DeleteMask => AppMsg::DeleteMask(index),
+                    Msg::ToggleMaskPainting => {
+                        if ctx.props().mask_painting {
+                            AppMsg::SetMaskBrush(None)
+                        } else {
+                            AppMsg::SetMaskBrush(Some(self.mask_brush(index)))
+                        }
+                    }
 
                     _ => AppMsg::Error(String::from("Error")),
                 };
@@ -262,19 +492,29 @@ impl Component for Pane {
         false
     }
 
+    // `html!` expands the props of components into bare statements.
+    #[allow(clippy::unnecessary_operation)]
     fn view(&self, ctx: &Context<Self>) -> Html {
-        let props = ctx.props().clone();
-
-        let image_name_msg = format!(
-            "No.{} / {}: {}{}",
-            props.layer_selected + 1,
-            props.images_len,
-            props.image_name,
-            match props.image_state {
-                true => "",
-                false => " - Not loaded",
-            },
-        );
+        let props = ctx.props();
+
+        let image_name_msg = if props.is_group {
+            String::from("Group")
+        } else if props.text.is_some() {
+            String::from("Text")
+        } else if props.shape.is_some() {
+            String::from("Shape")
+        } else {
+            format!(
+                "No.{} / {}: {}{}",
+                props.layer_selected + 1,
+                props.images_len,
+                props.image_name,
+                match props.image_state {
+                    true => "",
+                    false => " - Not loaded",
+                },
+            )
+        };
 
         let index = ctx.props().index;
 
@@ -292,14 +532,39 @@ impl Component for Pane {
         });
 
         let drag_drop = ctx.link().callback(move |e: DragEvent| {
+            // the files from the system, or another pane.
+            let files = dropped_files(&e);
+            if !files.is_empty() {
+                return Msg::DropFiles(files);
+            }
+
             let data = e.data_transfer().unwrap().get_data("text/plain").unwrap();
-            Msg::DragDrop(data.to_string().parse::<usize>().unwrap())
+            match data.parse::<usize>() {
+                Ok(layer_index) => Msg::DragDrop(layer_index),
+                Err(_) => Msg::DragLeave,
+            }
+        });
+
+        let mask_upload_ref = self.mask_upload_node.clone();
+        let upload_mask = ctx.link().callback(move |_| {
+            let mut result = Vec::new();
+            let input = mask_upload_ref.cast::<HtmlInputElement>().unwrap();
+            if let Some(files) = input.files() {
+                let files = js_sys::try_iter(&files)
+                    .unwrap()
+                    .unwrap()
+                    .map(|v| web_sys::File::from(v.unwrap()))
+                    .map(gloo_file::File::from);
+                result.extend(files);
+            }
+            Msg::FetchMask(result)
         });
 
         html! {
             <div
                 id={props.index.to_string()}
                 class="mx-1 my-2 py-2 hover:cursor-grab"
+                style={format!("padding-left: {}rem", props.depth)}
                 ref={self.pane_div_node.clone()}
                 draggable="true"
                 ondragstart={drag_start}
@@ -308,7 +573,46 @@ impl Component for Pane {
                 ondrop={drag_drop}
             >
                 <div class="flex flex-row justify-between items-center">
-                    <div>
+                    <div class="flex items-center">
+                        {
+                            if props.is_group {
+                                html! {
+                                    <Button
+                                        id={format!("{}-collapse-btn", props.index)}
+                                        class="icon-btn-grey"
+                                        onclick={ctx.link().callback(|_| Msg::ToggleGroupCollapsed)}
+                                        title={
+                                            if props.collapsed {
+                                                "expand"
+                                            } else {
+                                                "collapse"
+                                            }
+                                        }
+                                    >
+                                        <svg
+                                            class="icon-svg"
+                                            xmlns="http://www.w3.org/2000/svg"
+                                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                        >
+                                            <path
+                                                stroke-linecap="round"
+                                                stroke-linejoin="round"
+                                                stroke-width="2"
+                                                d={
+                                                    if props.collapsed {
+                                                        "M9 5l7 7-7 7"
+                                                    } else {
+                                                        "M19 9l-7 7-7-7"
+                                                    }
+                                                }
+                                            />
+                                        </svg>
+                                    </Button>
+                                }
+                            } else {
+                                html! {}
+                            }
+                        }
                         <input
                             class="hidden"
                             ref={self.layer_name_input_node.clone()}
@@ -380,46 +684,56 @@ impl Component for Pane {
                                 }
                             </svg>
                         </Button>
-                        <Button
-                            id={format!("{}-prev-btn", props.index)}
-                            class="btn btn-blue"
-                            onclick={ctx.link().callback(|_| Msg::PrevImage)}
-                            title="prev"
-                            disabled={!props.layer_show}
-                        >
-                            <svg
-                                class="icon-svg"
-                                xmlns="http://www.w3.org/2000/svg"
-                                fill="none" viewBox="0 0 24 24" stroke="currentColor"
-                            >
-                                <path
-                                    stroke-linecap="round"
-                                    stroke-linejoin="round"
-                                    stroke-width="2"
-                                    d="M11 15l-3-3m0 0l3-3m-3 3h8M3 12a9 9 0 1118 0 9 9 0 01-18 0z"
-                                />
-                            </svg>
-                        </Button>
-                        <Button
-                            id={format!("{}-next-btn", props.index)}
-                            class="btn btn-blue"
-                            onclick={ctx.link().callback(|_| Msg::NextImage)}
-                            title="next"
-                            disabled={!props.layer_show}
-                        >
-                            <svg
-                                class="icon-svg"
-                                xmlns="http://www.w3.org/2000/svg"
-                                fill="none" viewBox="0 0 24 24" stroke="currentColor"
-                            >
-                                <path
-                                    stroke-linecap="round"
-                                    stroke-linejoin="round"
-                                    stroke-width="2"
-                                    d="M13 9l3 3m0 0l-3 3m3-3H8m13 0a9 9 0 11-18 0 9 9 0 0118 0z"
-                                />
-                            </svg>
-                        </Button>
+                        {
+                            if props.is_group || props.text.is_some() || props.shape.is_some() {
+                                html! {}
+                            } else {
+                                html! {
+                                    <>
+                                        <Button
+                                            id={format!("{}-prev-btn", props.index)}
+                                            class="btn btn-blue"
+                                            onclick={ctx.link().callback(|_| Msg::PrevImage)}
+                                            title="prev"
+                                            disabled={!props.layer_show}
+                                        >
+                                            <svg
+                                                class="icon-svg"
+                                                xmlns="http://www.w3.org/2000/svg"
+                                                fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                            >
+                                                <path
+                                                    stroke-linecap="round"
+                                                    stroke-linejoin="round"
+                                                    stroke-width="2"
+                                                    d="M11 15l-3-3m0 0l3-3m-3 3h8M3 12a9 9 0 1118 0 9 9 0 01-18 0z"
+                                                />
+                                            </svg>
+                                        </Button>
+                                        <Button
+                                            id={format!("{}-next-btn", props.index)}
+                                            class="btn btn-blue"
+                                            onclick={ctx.link().callback(|_| Msg::NextImage)}
+                                            title="next"
+                                            disabled={!props.layer_show}
+                                        >
+                                            <svg
+                                                class="icon-svg"
+                                                xmlns="http://www.w3.org/2000/svg"
+                                                fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                            >
+                                                <path
+                                                    stroke-linecap="round"
+                                                    stroke-linejoin="round"
+                                                    stroke-width="2"
+                                                    d="M13 9l3 3m0 0l-3 3m3-3H8m13 0a9 9 0 11-18 0 9 9 0 0118 0z"
+                                                />
+                                            </svg>
+                                        </Button>
+                                    </>
+                                }
+                            }
+                        }
                         <Button
                             id={format!("{}-menu-btn", props.index)}
                             class="btn btn-blue"
@@ -472,15 +786,487 @@ impl Component for Pane {
                             />
                         </div>
                     </div>
-                    <div class="flex justify-evenly items-center px-4 mt-3">
-                        <button
+                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                        <span class="input-label">{"blend: "}</span>
+                        <select
+                            class="input-text"
+                            ref={self.layer_blend_select_node.clone()}
+                            onchange={ctx.link().callback(|_| Msg::SetLayerBlend)}
+                        >
+                            {
+                                for BlendMode::ALL.iter().map(|mode| html! {
+                                    <option
+                                        value={mode.name()}
+                                        selected={*mode == props.layer_blend}
+                                    >
+                                        {mode.name()}
+                                    </option>
+                                })
+                            }
+                        </select>
+                    </div>
+                    {
+                        if props.is_group {
+                            html! {}
+                        } else {
+                            html! {
+                                <>
+                                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                                        <span class="input-label">{"scale: "}</span>
+                                        <select
+                                            class="input-text"
+                                            ref={self.layer_scale_select_node.clone()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerScale)}
+                                        >
+                                            {
+                                                for ScaleMode::ALL.iter().map(|mode| html! {
+                                                    <option
+                                                        value={mode.name()}
+                                                        selected={*mode == props.layer_scale}
+                                                    >
+                                                        {mode.name()}
+                                                    </option>
+                                                })
+                                            }
+                                        </select>
+                                    </div>
+                                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                                        <span class="input-label">{"x: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.dx_input_node.clone()}
+                                            type="number"
+                                            value={props.layer_transform.dx.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerTransform)}
+                                        />
+                                        <span class="input-label ml-1">{"y: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.dy_input_node.clone()}
+                                            type="number"
+                                            value={props.layer_transform.dy.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerTransform)}
+                                        />
+                                        <span class="input-label ml-1">{"w: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.dw_input_node.clone()}
+                                            type="number"
+                                            value={props.layer_transform.dw.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerTransform)}
+                                        />
+                                        <span class="input-label ml-1">{"h: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.dh_input_node.clone()}
+                                            type="number"
+                                            value={props.layer_transform.dh.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerTransform)}
+                                        />
+                                    </div>
+                                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                                        <span class="input-label">{"rotate: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.rotation_input_node.clone()}
+                                            type="number"
+                                            value={props.layer_transform.rotation.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerTransform)}
+                                        />
+                                        <span class="input-label ml-1">{"anchor: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.anchor_x_input_node.clone()}
+                                            type="number"
+                                            value={props.layer_transform.anchor_x.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerTransform)}
+                                        />
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.anchor_y_input_node.clone()}
+                                            type="number"
+                                            value={props.layer_transform.anchor_y.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerTransform)}
+                                        />
+                                        <span class="input-label ml-1">{"flip: "}</span>
+                                        <input
+                                            class="ml-1"
+                                            ref={self.flip_h_input_node.clone()}
+                                            type="checkbox"
+                                            title="flip horizontally"
+                                            checked={props.layer_transform.flip_h}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerTransform)}
+                                        />
+                                        <input
+                                            class="ml-1"
+                                            ref={self.flip_v_input_node.clone()}
+                                            type="checkbox"
+                                            title="flip vertically"
+                                            checked={props.layer_transform.flip_v}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerTransform)}
+                                        />
+                                    </div>
+                                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                                        <span class="input-label">{"animate: "}</span>
+                                        <input
+                                            type="checkbox"
+                                            title="play the images as frames"
+                                            checked={props.layer_animated}
+                                            onchange={ctx.link().callback(|_| Msg::ToggleLayerAnimated)}
+                                        />
+                                        <span class="input-label ml-1">{"frame offset: "}</span>
+                                        <input
+                                            class="input-number"
+                                            style="width: 4rem"
+                                            ref={self.frame_offset_input_node.clone()}
+                                            type="number"
+                                            min="0"
+                                            value={props.layer_frame_offset.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerFrameOffset)}
+                                        />
+                                    </div>
+                                </>
+                            }
+                        }
+                    }
+                    {
+                        match props.text.as_ref() {
+                            Some(text) => html! {
+                                <>
+                                    <div class="flex items-start justify-start ml-1 px-4 py-1">
+                                        <span class="input-label">{"text: "}</span>
+                                        <textarea
+                                            class="input-text"
+                                            ref={self.text_content_node.clone()}
+                                            rows="3"
+                                            value={text.content.clone()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerText)}
+                                        />
+                                    </div>
+                                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                                        <span class="input-label">{"font: "}</span>
+                                        <input
+                                            class="input-text"
+                                            ref={self.font_family_input_node.clone()}
+                                            type="text"
+                                            title="css font family"
+                                            value={text.font_family.clone()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerText)}
+                                        />
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.font_size_input_node.clone()}
+                                            type="number"
+                                            min="1"
+                                            title="font size"
+                                            value={text.font_size.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerText)}
+                                        />
+                                        <select
+                                            class="input-text"
+                                            ref={self.font_weight_select_node.clone()}
+                                            title="font weight"
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerText)}
+                                        >
+                                            {
+                                                for Text::WEIGHTS.iter().map(|weight| html! {
+                                                    <option
+                                                        value={weight.to_string()}
+                                                        selected={*weight == text.font_weight}
+                                                    >
+                                                        {weight}
+                                                    </option>
+                                                })
+                                            }
+                                        </select>
+                                        <input
+                                            class="ml-1"
+                                            ref={self.text_color_input_node.clone()}
+                                            type="color"
+                                            title="color"
+                                            value={text.color.clone()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerText)}
+                                        />
+                                    </div>
+                                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                                        <span class="input-label">{"align: "}</span>
+                                        <select
+                                            class="input-text"
+                                            ref={self.text_align_select_node.clone()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerText)}
+                                        >
+                                            {
+                                                for TextAlign::ALL.iter().map(|align| html! {
+                                                    <option
+                                                        value={align.name()}
+                                                        selected={*align == text.align}
+                                                    >
+                                                        {align.name()}
+                                                    </option>
+                                                })
+                                            }
+                                        </select>
+                                        <span class="input-label ml-1">{"line: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.line_height_input_node.clone()}
+                                            type="number"
+                                            min="0"
+                                            step="0.1"
+                                            title="line height, times the font size"
+                                            value={text.line_height.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerText)}
+                                        />
+                                        <span class="input-label ml-1">{"spacing: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.letter_spacing_input_node.clone()}
+                                            type="number"
+                                            title="letter spacing"
+                                            value={text.letter_spacing.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerText)}
+                                        />
+                                    </div>
+                                </>
+                            },
+                            None => html! {},
+                        }
+                    }
+                    {
+                        match props.shape.as_ref() {
+                            Some(shape) => html! {
+                                <>
+                                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                                        <span class="input-label">{"shape: "}</span>
+                                        <select
+                                            class="input-text"
+                                            ref={self.shape_kind_select_node.clone()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                        >
+                                            {
+                                                for ShapeKind::ALL.iter().map(|kind| html! {
+                                                    <option
+                                                        value={kind.name()}
+                                                        selected={*kind == shape.kind}
+                                                    >
+                                                        {kind.name()}
+                                                    </option>
+                                                })
+                                            }
+                                        </select>
+                                        <span class="input-label ml-1">{"w: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.shape_width_input_node.clone()}
+                                            type="number"
+                                            min="0"
+                                            value={shape.width.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                        />
+                                        <span class="input-label ml-1">{"h: "}</span>
+                                        <input
+                                            class="input-float"
+                                            style="width: 4rem"
+                                            ref={self.shape_height_input_node.clone()}
+                                            type="number"
+                                            min="0"
+                                            value={shape.height.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                        />
+                                    </div>
+                                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                                        <span class="input-label">{"fill: "}</span>
+                                        <input
+                                            ref={self.shape_fill_input_node.clone()}
+                                            type="checkbox"
+                                            title="fill the shape"
+                                            checked={shape.fill.is_some()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                        />
+                                        <input
+                                            class="ml-1"
+                                            ref={self.shape_fill_color_input_node.clone()}
+                                            type="color"
+                                            title="fill color"
+                                            value={shape.fill.clone().unwrap_or_else(|| String::from("#3b82f6"))}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                        />
+                                        <span class="input-label ml-1">{"stroke: "}</span>
+                                        <input
+                                            ref={self.shape_stroke_input_node.clone()}
+                                            type="checkbox"
+                                            title="stroke the outline"
+                                            checked={shape.stroke.is_some()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                        />
+                                        <input
+                                            class="ml-1"
+                                            ref={self.shape_stroke_color_input_node.clone()}
+                                            type="color"
+                                            title="stroke color"
+                                            value={shape.stroke.clone().unwrap_or_else(|| String::from("#000000"))}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                        />
+                                        <input
+                                            class="input-float ml-1"
+                                            style="width: 4rem"
+                                            ref={self.stroke_width_input_node.clone()}
+                                            type="number"
+                                            min="0"
+                                            title="stroke width"
+                                            value={shape.stroke_width.to_string()}
+                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                        />
+                                    </div>
+                                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                                        {
+                                            match shape.kind {
+                                                ShapeKind::Rectangle => html! {
+                                                    <>
+                                                        <span class="input-label">{"radius: "}</span>
+                                                        <input
+                                                            class="input-float"
+                                                            style="width: 4rem"
+                                                            ref={self.corner_radius_input_node.clone()}
+                                                            type="number"
+                                                            min="0"
+                                                            title="corner radius"
+                                                            value={shape.corner_radius.to_string()}
+                                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                                        />
+                                                    </>
+                                                },
+                                                ShapeKind::Ellipse => html! {},
+                                                ShapeKind::Line | ShapeKind::Polygon => html! {
+                                                    <>
+                                                        <span class="input-label">{"points: "}</span>
+                                                        <input
+                                                            class="input-text"
+                                                            ref={self.shape_points_input_node.clone()}
+                                                            type="text"
+                                                            title="x,y in the box, from 0 to 1, split by spaces"
+                                                            value={Shape::points_text(&shape.kind_points())}
+                                                            onchange={ctx.link().callback(|_| Msg::SetLayerShape)}
+                                                        />
+                                                    </>
+                                                },
+                                            }
+                                        }
+                                    </div>
+                                </>
+                            },
+                            None => html! {},
+                        }
+                    }
+                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                        <span class="input-label">{"mask: "}</span>
+                        <select class="input-text" ref={self.mask_mode_select_node.clone()}>
+                            {
+                                for MaskMode::ALL.iter().map(|mode| html! {
+                                    <option value={mode.name()}>{mode.name()}</option>
+                                })
+                            }
+                        </select>
+                        <label
                             class="btn btn-blue"
-                            onclick={ctx.link().callback(|_| Msg::ToggleLayerFit)}
+                            for={format!("layer-mask-upload-{}", props.index)}
+                            title="import mask"
+                        >
+                            <svg
+                                class="icon-svg"
+                                xmlns="http://www.w3.org/2000/svg"
+                                fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                            >
+                                <path
+                                    stroke-linecap="round"
+                                    stroke-linejoin="round"
+                                    stroke-width="2"
+                                    d="M4 16v1a3 3 0 003 3h10a3 3 0 003-3v-1m-4-8l-4-4m0 0L8 8m4-4v12"
+                                />
+                            </svg>
+                            <input
+                                class="hidden"
+                                ref={self.mask_upload_node.clone()}
+                                id={format!("layer-mask-upload-{}", props.index)}
+                                type="file"
+                                accept="image/*"
+                                onchange={upload_mask}
+                            />
+                        </label>
+                        {
+                            if props.has_mask {
+                                html! {
+                                    <>
+                                        <input
+                                            class="ml-2"
+                                            type="checkbox"
+                                            title="enable mask"
+                                            checked={props.mask_enabled}
+                                            onchange={ctx.link().callback(|_| Msg::ToggleLayerMask)}
+                                        />
+                                        <button
+                                            class="btn btn-blue"
+                                            onclick={ctx.link().callback(|_| Msg::DeleteMask)}
+                                            title="delete mask"
+                                        >
+                                            <svg
+                                                class="icon-svg"
+                                                xmlns="http://www.w3.org/2000/svg"
+                                                fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                            >
+                                                <path
+                                                    stroke-linecap="round"
+                                                    stroke-linejoin="round"
+                                                    stroke-width="2"
+                                                    d="M6 18L18 6M6 6l12 12"
+                                                />
+                                            </svg>
+                                        </button>
+                                    </>
+                                }
+                            } else {
+                                html! {}
+                            }
+                        }
+                    </div>
+                    <div class="flex items-center justify-start h-10 ml-1 px-4">
+                        <span class="input-label">{"brush: "}</span>
+                        <input
+                            class="input-number"
+                            ref={self.brush_size_input_node.clone()}
+                            type="number"
+                            min="1"
+                            value="20"
+                            onchange={ctx.link().callback(|_| Msg::UpdateMaskBrush)}
+                        />
+                        <select
+                            class="input-text"
+                            ref={self.brush_value_select_node.clone()}
+                            onchange={ctx.link().callback(|_| Msg::UpdateMaskBrush)}
+                        >
+                            <option value="hide">{"hide"}</option>
+                            <option value="reveal">{"reveal"}</option>
+                        </select>
+                        <Button
+                            id={format!("{}-paint-mask-btn", props.index)}
+                            class="btn btn-blue"
+                            onclick={ctx.link().callback(|_| Msg::ToggleMaskPainting)}
                             title={
-                                if props.layer_fit {
-                                    "fit"
+                                if props.mask_painting {
+                                    "stop painting"
                                 } else {
-                                    "unfit"
+                                    "paint mask"
                                 }
                             }
                         >
@@ -489,33 +1275,110 @@ impl Component for Pane {
                                 xmlns="http://www.w3.org/2000/svg"
                                 fill="none" viewBox="0 0 24 24" stroke="currentColor"
                             >
-                                {
-                                    if props.layer_fit {
-                                        html!{
-                                            <path
-                                                stroke-linecap="round"
-                                                stroke-linejoin="round"
-                                                stroke-width="2"
-                                                d="M14 10l-2 1m0 0l-2-1m2 1v2.5M20 7l-2 1m2-1l-2-1m2 1v2.5M14 4l-2-1-2 1M4 7l2-1M4 7l2 1M4 7v2.5M12 21l-2-1m2 1l2-1m-2 1v-2.5M6 18l-2-1v-2.5M18 18l2-1v-2.5"
-                                            />
+                                <path
+                                    stroke-linecap="round"
+                                    stroke-linejoin="round"
+                                    stroke-width="2"
+                                    d={
+                                        if props.mask_painting {
+                                            "M5 13l4 4L19 7"
+                                        } else {
+                                            "M15.232 5.232l3.536 3.536m-2.036-5.036a2.5 2.5 0 113.536 3.536L6.5 21.036H3v-3.572L16.732 3.732z"
                                         }
-                                    } else {
-                                        html!{
+                                    }
+                                />
+                            </svg>
+                        </Button>
+                    </div>
+                    <div class="flex justify-evenly items-center px-4 mt-3">
+                        {
+                            if props.is_group {
+                                html! {
+                                <button
+                                    class="btn btn-blue"
+                                    onclick={ctx.link().callback(|_| Msg::UngroupLayer)}
+                                    title="ungroup"
+                                >
+                                    <svg
+                                        class="icon-svg"
+                                        xmlns="http://www.w3.org/2000/svg"
+                                        fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                    >
+                                        <path
+                                            stroke-linecap="round"
+                                            stroke-linejoin="round"
+                                            stroke-width="2"
+                                            d="M5 19a2 2 0 01-2-2V7a2 2 0 012-2h4l2 2h4a2 2 0 012 2v1M5 19h14a2 2 0 002-2v-5a2 2 0 00-2-2H9a2 2 0 00-2 2v5a2 2 0 01-2 2z"
+                                        />
+                                    </svg>
+                                </button>
+                                }
+                            } else if props.text.is_some() || props.shape.is_some() {
+                                html! {}
+                            } else {
+                                html! {
+                                    <>
+                                        <button
+                                            class="btn btn-blue"
+                                            onclick={ctx.link().callback(|_| Msg::ToggleDstLayerNameInput)}
+                                            tilte="move image"
+                                        >
+                                            <svg
+                                                class="icon-svg"
+                                                xmlns="http://www.w3.org/2000/svg"
+                                                fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                            >
+                                                <path
+                                                    stroke-linecap="round"
+                                                    stroke-linejoin="round"
+                                                    stroke-width="2" d="M14.121 14.121L19 19m-7-7l7-7m-7 7l-2.879 2.879M12 12L9.121 9.121m0 5.758a3 3 0 10-4.243 4.243 3 3 0 004.243-4.243zm0-5.758a3 3 0 10-4.243-4.243 3 3 0 004.243 4.243z"
+                                                />
+                                            </svg>
+                                        </button>
+                                        <button
+                                            class="btn btn-blue"
+                                            onclick={ctx.link().callback(|_| Msg::StartCrop)}
+                                            title="crop image"
+                                        >
+                                            <svg
+                                                class="icon-svg"
+                                                xmlns="http://www.w3.org/2000/svg"
+                                                fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                            >
+                                                <path
+                                                    stroke-linecap="round"
+                                                    stroke-linejoin="round"
+                                                    stroke-width="2"
+                                                    d="M6 2v14a2 2 0 002 2h14M2 6h14a2 2 0 012 2v14"
+                                                />
+                                            </svg>
+                                        </button>
+                                        <button
+                                            class="btn btn-blue"
+                                            onclick={ctx.link().callback(|_| Msg::DeleteImage)}
+                                            title="delete image"
+                                        >
+                                        <svg
+                                            class="icon-svg"
+                                            xmlns="http://www.w3.org/2000/svg"
+                                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                        >
                                             <path
                                                 stroke-linecap="round"
                                                 stroke-linejoin="round"
                                                 stroke-width="2"
-                                                d="M20 7l-8-4-8 4m16 0l-8 4m8-4v10l-8 4m0-10L4 7m8 4v10M4 7v10l8 4"
+                                                d="M19 7l-.867 12.142A2 2 0 0116.138 21H7.862a2 2 0 01-1.995-1.858L5 7m5 4v6m4-6v6m1-10V4a1 1 0 00-1-1h-4a1 1 0 00-1 1v3M4 7h16"
                                             />
-                                        }
-                                    }
+                                        </svg>
+                                        </button>
+                                    </>
                                 }
-                            </svg>
-                        </button>
+                            }
+                        }
                         <button
                             class="btn btn-blue"
-                            onclick={ctx.link().callback(|_| Msg::ToggleDstLayerNameInput)}
-                            tilte="move image"
+                            onclick={ctx.link().callback(|_| Msg::GroupLayer)}
+                            title="group layer"
                         >
                             <svg
                                 class="icon-svg"
@@ -525,28 +1388,11 @@ impl Component for Pane {
                                 <path
                                     stroke-linecap="round"
                                     stroke-linejoin="round"
-                                    stroke-width="2" d="M14.121 14.121L19 19m-7-7l7-7m-7 7l-2.879 2.879M12 12L9.121 9.121m0 5.758a3 3 0 10-4.243 4.243 3 3 0 004.243-4.243zm0-5.758a3 3 0 10-4.243-4.243 3 3 0 004.243 4.243z"
+                                    stroke-width="2"
+                                    d="M9 13h6m-3-3v6M3 17V7a2 2 0 012-2h6l2 2h6a2 2 0 012 2v8a2 2 0 01-2 2H5a2 2 0 01-2-2z"
                                 />
                             </svg>
                         </button>
-                        <button
-                            class="btn btn-blue"
-                            onclick={ctx.link().callback(|_| Msg::DeleteImage)}
-                            title="delete image"
-                        >
-                        <svg
-                            class="icon-svg"
-                            xmlns="http://www.w3.org/2000/svg"
-                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
-                        >
-                            <path
-                                stroke-linecap="round"
-                                stroke-linejoin="round"
-                                stroke-width="2"
-                                d="M19 7l-.867 12.142A2 2 0 0116.138 21H7.862a2 2 0 01-1.995-1.858L5 7m5 4v6m4-6v6m1-10V4a1 1 0 00-1-1h-4a1 1 0 00-1 1v3M4 7h16"
-                            />
-                        </svg>
-                        </button>
                         <button
                             class="btn btn-blue"
                             onclick={ctx.link().callback(|_| Msg::DuplicateLayer)}
@@ -601,3 +1447,141 @@ impl Component for Pane {
         }
     }
 }
+
+impl Pane {
+    fn mask_brush(&self, layer_index: usize) -> MaskBrush {
+        let mut size = self
+            .brush_size_input_node
+            .cast::<HtmlInputElement>()
+            .unwrap()
+            .value_as_number();
+        if size.is_nan() || size < 1.0 {
+            size = 1.0;
+        }
+
+        let value = self
+            .brush_value_select_node
+            .cast::<HtmlSelectElement>()
+            .unwrap()
+            .value();
+
+        MaskBrush {
+            layer_index,
+            radius: size / 2.0,
+            value: if value.as_str() == "reveal" { 255 } else { 0 },
+        }
+    }
+
+    fn layer_text(&self, ctx: &Context<Self>) -> Option<Text> {
+        let before = ctx.props().text.clone()?;
+        let input = |node: &NodeRef| node.cast::<HtmlInputElement>().unwrap();
+        let select = |node: &NodeRef| node.cast::<HtmlSelectElement>().unwrap().value();
+        let number = |node: &NodeRef, before: f64| {
+            let value = input(node).value_as_number();
+            if value.is_nan() {
+                before
+            } else {
+                value
+            }
+        };
+
+        Some(Text {
+            content: self
+                .text_content_node
+                .cast::<HtmlTextAreaElement>()
+                .unwrap()
+                .value(),
+            font_family: match input(&self.font_family_input_node).value() {
+                family if family.trim().is_empty() => before.font_family,
+                family => family,
+            },
+            font_size: number(&self.font_size_input_node, before.font_size).max(1.0),
+            font_weight: select(&self.font_weight_select_node)
+                .parse()
+                .unwrap_or(before.font_weight),
+            color: input(&self.text_color_input_node).value(),
+            align: TextAlign::from_name(select(&self.text_align_select_node).as_str())
+                .unwrap_or(before.align),
+            line_height: number(&self.line_height_input_node, before.line_height).max(0.0),
+            letter_spacing: number(&self.letter_spacing_input_node, before.letter_spacing),
+        })
+    }
+
+    fn layer_shape(&self, ctx: &Context<Self>) -> Option<Shape> {
+        let before = ctx.props().shape.clone()?;
+        let input = |node: &NodeRef| node.cast::<HtmlInputElement>();
+        let number = |node: &NodeRef, before: f64| match input(node) {
+            Some(input) if !input.value_as_number().is_nan() => input.value_as_number(),
+            _ => before,
+        };
+        let color = |checkbox: &NodeRef, color: &NodeRef| match input(checkbox).unwrap().checked() {
+            true => Some(input(color).unwrap().value()),
+            false => None,
+        };
+
+        let kind = self
+            .shape_kind_select_node
+            .cast::<HtmlSelectElement>()
+            .unwrap()
+            .value();
+        let kind = ShapeKind::from_name(kind.as_str()).unwrap_or(before.kind);
+
+        // the points of another kind don't fit.
+        let points = match input(&self.shape_points_input_node) {
+            Some(_) if kind != before.kind => Vec::new(),
+            Some(points) => Shape::parse_points(points.value().as_str()).unwrap_or(before.points),
+            None => before.points,
+        };
+
+        // a line is only stroked.
+        let fill = color(
+            &self.shape_fill_input_node,
+            &self.shape_fill_color_input_node,
+        );
+        let stroke = match color(
+            &self.shape_stroke_input_node,
+            &self.shape_stroke_color_input_node,
+        ) {
+            None if kind == ShapeKind::Line && kind != before.kind => {
+                Some(fill.clone().unwrap_or_else(|| String::from("#000000")))
+            }
+            stroke => stroke,
+        };
+
+        Some(Shape {
+            kind,
+            width: number(&self.shape_width_input_node, before.width).max(0.0),
+            height: number(&self.shape_height_input_node, before.height).max(0.0),
+            fill,
+            stroke,
+            stroke_width: number(&self.stroke_width_input_node, before.stroke_width).max(0.0),
+            corner_radius: number(&self.corner_radius_input_node, before.corner_radius).max(0.0),
+            points,
+        })
+    }
+
+    fn layer_transform(&self, ctx: &Context<Self>) -> Transform {
+        let before = ctx.props().layer_transform;
+        let number = |node: &NodeRef, before: f64| {
+            let value = node.cast::<HtmlInputElement>().unwrap().value_as_number();
+            if value.is_nan() {
+                before
+            } else {
+                value
+            }
+        };
+        let checked = |node: &NodeRef| node.cast::<HtmlInputElement>().unwrap().checked();
+
+        Transform {
+            dx: number(&self.dx_input_node, before.dx),
+            dy: number(&self.dy_input_node, before.dy),
+            dw: number(&self.dw_input_node, before.dw),
+            dh: number(&self.dh_input_node, before.dh),
+            rotation: number(&self.rotation_input_node, before.rotation),
+            flip_h: checked(&self.flip_h_input_node),
+            flip_v: checked(&self.flip_v_input_node),
+            anchor_x: number(&self.anchor_x_input_node, before.anchor_x),
+            anchor_y: number(&self.anchor_y_input_node, before.anchor_y),
+        }
+    }
+}
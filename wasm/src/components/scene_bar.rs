@@ -0,0 +1,188 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-02 21:12:06
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-02 23:01:37
+ * @FilePath: /layer-painter/wasm/src/components/scene_bar.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use crate::components::app::Msg as AppMsg;
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::{html, Callback, Component, Context, Html, NodeRef, Properties};
+
+pub enum Msg {
+    Save,
+    Apply,
+    Update,
+    Delete,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct Props {
+    pub scenes: Vec<String>,
+    pub callback: Option<Callback<AppMsg>>,
+}
+
+/// Creates, applies, updates and deletes the scenes of the canvas.
+pub struct SceneBar {
+    scene_name_node: NodeRef,
+    scene_select_node: NodeRef,
+}
+
+impl SceneBar {
+    fn selected_scene(&self) -> Option<usize> {
+        let value = self
+            .scene_select_node
+            .cast::<HtmlSelectElement>()
+            .unwrap()
+            .value();
+
+        value.parse::<usize>().ok()
+    }
+}
+
+impl Component for SceneBar {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            scene_name_node: NodeRef::default(),
+            scene_select_node: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let app_msg = match msg {
+            Msg::Save => {
+                let input = self.scene_name_node.cast::<HtmlInputElement>().unwrap();
+                let scene_name = input.value();
+                if scene_name.as_str() == "" {
+                    return false;
+                }
+                input.set_value("");
+
+                AppMsg::SaveScene(scene_name)
+            }
+            Msg::Apply => match self.selected_scene() {
+                Some(index) => AppMsg::ApplyScene(index),
+                None => return false,
+            },
+            Msg::Update => match self.selected_scene() {
+                Some(index) => AppMsg::UpdateScene(index),
+                None => return false,
+            },
+            Msg::Delete => match self.selected_scene() {
+                Some(index) => AppMsg::DeleteScene(index),
+                None => return false,
+            },
+        };
+        ctx.props().callback.clone().unwrap().emit(app_msg);
+
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        html! {
+            <nav class="m-2 px-4 flex flex-nowrap shrink-0 justify-between items-center bg-white h-16 border-2">
+                <ul class="shrink-0 flex items-center">
+                    <label class="input-label">
+                        <span>{"Scene"}</span>
+                    </label>
+                    <input
+                        class="input-text"
+                        ref={self.scene_name_node.clone()}
+                        type="text"
+                        placeholder="new scene"
+                    />
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Save)}
+                        title="save scene"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M12 4v16m8-8H4"
+                            />
+                        </svg>
+                    </button>
+                </ul>
+                <ul class="shrink-0 flex items-center ml-2">
+                    <select
+                        class="input-text"
+                        ref={self.scene_select_node.clone()}
+                    >
+                        {
+                            for ctx.props().scenes.iter().enumerate().map(|(index, scene)| html! {
+                                <option value={index.to_string()}>{scene}</option>
+                            })
+                        }
+                    </select>
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Apply)}
+                        title="apply scene"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M5 13l4 4L19 7"
+                            />
+                        </svg>
+                    </button>
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Update)}
+                        title="update scene with the layers"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M4 4v5h.582m15.356 2A8.001 8.001 0 004.582 9m0 0H9m11 11v-5h-.581m0 0a8.003 8.003 0 01-15.357-2m15.357 2H15"
+                            />
+                        </svg>
+                    </button>
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Delete)}
+                        title="delete scene"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M19 7l-.867 12.142A2 2 0 0116.138 21H7.862a2 2 0 01-1.995-1.858L5 7m5 4v6m4-6v6m1-10V4a1 1 0 00-1-1h-4a1 1 0 00-1 1v3M4 7h16"
+                            />
+                        </svg>
+                    </button>
+                </ul>
+            </nav>
+        }
+    }
+}
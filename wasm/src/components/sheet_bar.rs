@@ -0,0 +1,205 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-05 21:37:20
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-05 23:55:02
+ * @FilePath: /layer-painter/wasm/src/components/sheet_bar.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use crate::components::app::Msg as AppMsg;
+use crate::compositor::{AtlasFormat, Packing, SheetOptions, SheetSource};
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::{html, Callback, Component, Context, Html, NodeRef, Properties};
+
+pub enum Msg {
+    Export,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct Props {
+    pub callback: Option<Callback<AppMsg>>,
+}
+
+/// Exports the frames of the timeline packed in a sprite sheet with a json atlas.
+pub struct SheetBar {
+    source_select_node: NodeRef,
+    packing_select_node: NodeRef,
+    padding_input_node: NodeRef,
+    power_of_two_input_node: NodeRef,
+    trim_input_node: NodeRef,
+    format_select_node: NodeRef,
+}
+
+impl SheetBar {
+    fn select_value(node: &NodeRef) -> String {
+        node.cast::<HtmlSelectElement>().unwrap().value()
+    }
+
+    fn checked(node: &NodeRef) -> bool {
+        node.cast::<HtmlInputElement>().unwrap().checked()
+    }
+
+    fn sheet_options(&self) -> SheetOptions {
+        let default = SheetOptions::default();
+
+        let padding = self
+            .padding_input_node
+            .cast::<HtmlInputElement>()
+            .unwrap()
+            .value_as_number();
+        let padding = if padding.is_nan() || padding < 0.0 {
+            default.padding
+        } else {
+            padding as usize
+        };
+
+        SheetOptions {
+            source: SheetSource::from_name(Self::select_value(&self.source_select_node).as_str())
+                .unwrap_or(default.source),
+            packing: Packing::from_name(Self::select_value(&self.packing_select_node).as_str())
+                .unwrap_or(default.packing),
+            padding,
+            power_of_two: Self::checked(&self.power_of_two_input_node),
+            trim: Self::checked(&self.trim_input_node),
+            format: AtlasFormat::from_name(Self::select_value(&self.format_select_node).as_str())
+                .unwrap_or(default.format),
+        }
+    }
+}
+
+impl Component for SheetBar {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            source_select_node: NodeRef::default(),
+            packing_select_node: NodeRef::default(),
+            padding_input_node: NodeRef::default(),
+            power_of_two_input_node: NodeRef::default(),
+            trim_input_node: NodeRef::default(),
+            format_select_node: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let app_msg = match msg {
+            Msg::Export => AppMsg::ExportSheet(self.sheet_options()),
+        };
+        ctx.props().callback.clone().unwrap().emit(app_msg);
+
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let default = SheetOptions::default();
+
+        html! {
+            <nav class="m-2 px-4 flex flex-nowrap shrink-0 justify-between items-center bg-white h-16 border-2">
+                <ul class="shrink-0 flex items-center">
+                    <label class="input-label">
+                        <span>{"Sheet"}</span>
+                    </label>
+                    <select
+                        class="input-text"
+                        ref={self.source_select_node.clone()}
+                        title="the frames of the whole canvas or of every layer"
+                    >
+                        {
+                            for SheetSource::ALL.iter().map(|source| html! {
+                                <option
+                                    value={source.name()}
+                                    selected={*source == default.source}
+                                >
+                                    {source.name()}
+                                </option>
+                            })
+                        }
+                    </select>
+                    <select
+                        class="input-text ml-2"
+                        ref={self.packing_select_node.clone()}
+                    >
+                        {
+                            for Packing::ALL.iter().map(|packing| html! {
+                                <option
+                                    value={packing.name()}
+                                    selected={*packing == default.packing}
+                                >
+                                    {packing.name()}
+                                </option>
+                            })
+                        }
+                    </select>
+                </ul>
+                <ul class="shrink-0 flex items-center ml-2">
+                    <label class="input-label">
+                        <span>{"padding: "}</span>
+                    </label>
+                    <input
+                        class="input-number"
+                        style="width: 4rem"
+                        ref={self.padding_input_node.clone()}
+                        type="number"
+                        min="0"
+                        value={default.padding.to_string()}
+                    />
+                    <label class="input-label ml-2">
+                        <span>{"pot: "}</span>
+                    </label>
+                    <input
+                        ref={self.power_of_two_input_node.clone()}
+                        type="checkbox"
+                        checked={default.power_of_two}
+                        title="round the size up to powers of two"
+                    />
+                    <label class="input-label ml-2">
+                        <span>{"trim: "}</span>
+                    </label>
+                    <input
+                        ref={self.trim_input_node.clone()}
+                        type="checkbox"
+                        checked={default.trim}
+                        title="cut the transparent borders of the frames"
+                    />
+                    <select
+                        class="input-text ml-2"
+                        ref={self.format_select_node.clone()}
+                        title="the layout of the frames in the atlas"
+                    >
+                        {
+                            for AtlasFormat::ALL.iter().map(|format| html! {
+                                <option
+                                    value={format.name()}
+                                    selected={*format == default.format}
+                                >
+                                    {format.name()}
+                                </option>
+                            })
+                        }
+                    </select>
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Export)}
+                        title="export sprite sheet"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M4 16v1a3 3 0 003 3h10a3 3 0 003-3v-1m-4-4l-4 4m0 0l-4-4m4 4V4"
+                            />
+                        </svg>
+                    </button>
+                </ul>
+            </nav>
+        }
+    }
+}
@@ -0,0 +1,331 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-03 21:02:15
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-03 23:41:08
+ * @FilePath: /layer-painter/wasm/src/components/timeline_bar.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use crate::canvas::{Animation, PlaybackMode, FPS_MAX};
+use crate::components::app::Msg as AppMsg;
+use crate::compositor::{AnimatedFormat, AnimatedOptions, Dither};
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::{html, Callback, Component, Context, Html, NodeRef, Properties};
+
+pub enum Msg {
+    TogglePlay,
+    Stop,
+    SetFps,
+    SetMode,
+    Export,
+}
+
+#[derive(PartialEq, Properties)]
+pub struct Props {
+    pub playing: bool,
+    pub frame: usize,
+    pub timeline_len: usize,
+    pub animation: Animation,
+    pub callback: Option<Callback<AppMsg>>,
+}
+
+/// Plays the animated layers, with the fps and the playback mode of the canvas,
+/// and exports the frames as a gif or an apng.
+pub struct TimelineBar {
+    fps_input_node: NodeRef,
+    mode_select_node: NodeRef,
+    format_select_node: NodeRef,
+    delay_input_node: NodeRef,
+    loops_input_node: NodeRef,
+    colors_input_node: NodeRef,
+    dither_select_node: NodeRef,
+    transparent_input_node: NodeRef,
+}
+
+impl TimelineBar {
+    // the empty inputs follow the timeline.
+    fn animated_options(&self, animation: &Animation) -> AnimatedOptions {
+        let format = self
+            .format_select_node
+            .cast::<HtmlSelectElement>()
+            .unwrap()
+            .value();
+        let format = AnimatedFormat::from_extension(format.as_str()).unwrap_or(AnimatedFormat::Gif);
+        let mut options = AnimatedOptions::new(format, animation);
+
+        let number = |node: &NodeRef| {
+            let value = node.cast::<HtmlInputElement>().unwrap().value_as_number();
+            if value.is_nan() || value < 0.0 {
+                None
+            } else {
+                Some(value)
+            }
+        };
+        if let Some(delay) = number(&self.delay_input_node) {
+            options.delay = delay as u32;
+        }
+        if let Some(loops) = number(&self.loops_input_node) {
+            options.loops = loops as u32;
+        }
+        if let Some(colors) = number(&self.colors_input_node) {
+            options.colors = colors as usize;
+        }
+
+        let dither = self
+            .dither_select_node
+            .cast::<HtmlSelectElement>()
+            .unwrap()
+            .value();
+        options.dither = Dither::from_name(dither.as_str()).unwrap_or_default();
+        options.transparent = self
+            .transparent_input_node
+            .cast::<HtmlInputElement>()
+            .unwrap()
+            .checked();
+
+        options
+    }
+}
+
+impl Component for TimelineBar {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            fps_input_node: NodeRef::default(),
+            mode_select_node: NodeRef::default(),
+            format_select_node: NodeRef::default(),
+            delay_input_node: NodeRef::default(),
+            loops_input_node: NodeRef::default(),
+            colors_input_node: NodeRef::default(),
+            dither_select_node: NodeRef::default(),
+            transparent_input_node: NodeRef::default(),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let app_msg = match msg {
+            Msg::TogglePlay => AppMsg::TogglePlayback,
+            Msg::Stop => AppMsg::StopPlayback,
+            Msg::SetFps => {
+                let input = self.fps_input_node.cast::<HtmlInputElement>().unwrap();
+                let fps = input.value_as_number();
+                if fps.is_nan() {
+                    input.set_value(ctx.props().animation.fps().to_string().as_str());
+                    return false;
+                }
+
+                AppMsg::SetAnimationFps(fps)
+            }
+            Msg::SetMode => {
+                let mode = self
+                    .mode_select_node
+                    .cast::<HtmlSelectElement>()
+                    .unwrap()
+                    .value();
+
+                match PlaybackMode::from_name(mode.as_str()) {
+                    Some(mode) => AppMsg::SetPlaybackMode(mode),
+                    None => AppMsg::Warn(format!("Unknown playback mode: {}", mode)),
+                }
+            }
+            Msg::Export => AppMsg::ExportAnimated(self.animated_options(&ctx.props().animation)),
+        };
+        ctx.props().callback.clone().unwrap().emit(app_msg);
+
+        false
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        let fps = props.animation.fps();
+        let mode = props.animation.mode();
+        let (play_title, play_path) = if props.playing {
+            ("pause", "M10 9v6m4-6v6")
+        } else {
+            ("play", "M14.752 11.168l-3.197-2.132A1 1 0 0010 9.87v4.263a1 1 0 001.555.832l3.197-2.132a1 1 0 000-1.664z")
+        };
+
+        html! {
+            <nav class="m-2 px-4 flex flex-nowrap shrink-0 justify-between items-center bg-white h-16 border-2">
+                <ul class="shrink-0 flex items-center">
+                    <label class="input-label">
+                        <span>{"Timeline"}</span>
+                    </label>
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::TogglePlay)}
+                        title={play_title}
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d={play_path}
+                            />
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M21 12a9 9 0 11-18 0 9 9 0 0118 0z"
+                            />
+                        </svg>
+                    </button>
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Stop)}
+                        title="stop and go back to the first frame"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M21 12a9 9 0 11-18 0 9 9 0 0118 0z"
+                            />
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M9 10a1 1 0 011-1h4a1 1 0 011 1v4a1 1 0 01-1 1h-4a1 1 0 01-1-1v-4z"
+                            />
+                        </svg>
+                    </button>
+                    <span class="input-label ml-2">
+                        {format!("frame {} / {}", props.frame % props.timeline_len.max(1) + 1, props.timeline_len.max(1))}
+                    </span>
+                </ul>
+                <ul class="shrink-0 flex items-center ml-2">
+                    <label class="input-label">
+                        <span>{"fps: "}</span>
+                    </label>
+                    <input
+                        class="input-number"
+                        style="width: 4rem"
+                        ref={self.fps_input_node.clone()}
+                        type="number"
+                        min="1"
+                        max={FPS_MAX.to_string()}
+                        value={fps.to_string()}
+                        onchange={ctx.link().callback(|_| Msg::SetFps)}
+                    />
+                    <select
+                        class="input-text ml-2"
+                        ref={self.mode_select_node.clone()}
+                        onchange={ctx.link().callback(|_| Msg::SetMode)}
+                    >
+                        {
+                            for PlaybackMode::ALL.iter().map(|option| html! {
+                                <option
+                                    value={option.name()}
+                                    selected={*option == mode}
+                                >
+                                    {option.name()}
+                                </option>
+                            })
+                        }
+                    </select>
+                </ul>
+                <ul class="shrink-0 flex items-center ml-2">
+                    <select class="input-text" ref={self.format_select_node.clone()}>
+                        {
+                            for AnimatedFormat::ALL.iter().map(|format| html! {
+                                <option value={format.extension()}>{format.extension()}</option>
+                            })
+                        }
+                    </select>
+                    <label class="input-label ml-2">
+                        <span>{"delay: "}</span>
+                    </label>
+                    <input
+                        class="input-number"
+                        style="width: 4rem"
+                        ref={self.delay_input_node.clone()}
+                        type="number"
+                        min="0"
+                        placeholder={((1000.0 / fps).round() as u32).to_string()}
+                        title="the time of every frame in milliseconds"
+                    />
+                    <label class="input-label ml-2">
+                        <span>{"loops: "}</span>
+                    </label>
+                    <input
+                        class="input-number"
+                        style="width: 4rem"
+                        ref={self.loops_input_node.clone()}
+                        type="number"
+                        min="0"
+                        placeholder={if mode == PlaybackMode::Once { "1" } else { "0" }}
+                        title="how many times it plays, 0 plays forever"
+                    />
+                    <label class="input-label ml-2">
+                        <span>{"colors: "}</span>
+                    </label>
+                    <input
+                        class="input-number"
+                        style="width: 4rem"
+                        ref={self.colors_input_node.clone()}
+                        type="number"
+                        min="2"
+                        max="256"
+                        placeholder="256"
+                        title="the colors of a gif"
+                    />
+                    <select
+                        class="input-text ml-2"
+                        ref={self.dither_select_node.clone()}
+                        title="the dithering of a gif"
+                    >
+                        {
+                            for Dither::ALL.iter().map(|dither| html! {
+                                <option
+                                    value={dither.name()}
+                                    selected={*dither == Dither::default()}
+                                >
+                                    {dither.name()}
+                                </option>
+                            })
+                        }
+                    </select>
+                    <input
+                        class="ml-2"
+                        ref={self.transparent_input_node.clone()}
+                        type="checkbox"
+                        checked=true
+                        title="keep the transparent pixels"
+                    />
+                    <button
+                        class="btn btn-blue"
+                        onclick={ctx.link().callback(|_| Msg::Export)}
+                        title="export animation"
+                    >
+                        <svg
+                            class="icon-svg"
+                            xmlns="http://www.w3.org/2000/svg"
+                            fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                        >
+                            <path
+                                stroke-linecap="round"
+                                stroke-linejoin="round"
+                                stroke-width="2"
+                                d="M4 16v1a3 3 0 003 3h10a3 3 0 003-3v-1m-4-4l-4 4m0 0l-4-4m4 4V4"
+                            />
+                        </svg>
+                    </button>
+                </ul>
+            </nav>
+        }
+    }
+}
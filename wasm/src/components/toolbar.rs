@@ -7,19 +7,33 @@
  * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
  */
 
+use crate::canvas::BATCH_TEMPLATE_DEFAULT;
 use crate::components::app::{Msg as AppMsg, State};
+use crate::components::button::Button;
 
 use crate::utils::*;
 use yew::{html, Callback, Component, Context, Html, NodeRef, Properties};
 
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 
 pub enum Msg {
     Reset,
     Resize,
     Save,
+    SaveAs,
+    SaveBundle,
+    Export,
+    ExportBatch,
+    Undo,
+    Redo,
+    SetHistoryDepth,
     FetchImages(String, Vec<gloo_file::File>),
     FetchJson(Vec<gloo_file::File>),
+    // through the dialogs of the app
+    OpenImages(String),
+    OpenProject,
+    AddTextLayer,
+    AddShapeLayer,
     Refresh,
 }
 
@@ -27,6 +41,9 @@ pub enum Msg {
 pub struct Props {
     pub width: usize,
     pub height: usize,
+    pub history_depth: usize,
+    pub can_undo: bool,
+    pub can_redo: bool,
     pub callback: Option<Callback<AppMsg>>,
 }
 
@@ -37,6 +54,50 @@ pub struct ToolBar {
     image_upload_node: NodeRef,
     json_upload_node: NodeRef,
     layer_name_node: NodeRef,
+    export_format_node: NodeRef,
+    export_quality_node: NodeRef,
+    export_transparent_node: NodeRef,
+    batch_template_node: NodeRef,
+    batch_scenes_node: NodeRef,
+    history_depth_node: NodeRef,
+}
+
+impl ToolBar {
+    fn canvas_name(&self) -> String {
+        let canvas_name = self
+            .canvas_name_node
+            .cast::<HtmlInputElement>()
+            .unwrap()
+            .value();
+        if canvas_name.as_str() == "" {
+            String::from("untitled")
+        } else {
+            canvas_name
+        }
+    }
+
+    fn export_options(&self) -> ExportOptions {
+        let format = self
+            .export_format_node
+            .cast::<HtmlSelectElement>()
+            .unwrap()
+            .value();
+        let format = ImageFormat::from_extension(format.as_str()).unwrap_or(ImageFormat::Png);
+
+        let quality = self
+            .export_quality_node
+            .cast::<HtmlInputElement>()
+            .unwrap()
+            .value_as_number();
+
+        let transparent = self
+            .export_transparent_node
+            .cast::<HtmlInputElement>()
+            .unwrap()
+            .checked();
+
+        ExportOptions::new(format, quality, transparent)
+    }
 }
 
 impl Component for ToolBar {
@@ -51,6 +112,12 @@ impl Component for ToolBar {
             image_upload_node: NodeRef::default(),
             json_upload_node: NodeRef::default(),
             layer_name_node: NodeRef::default(),
+            export_format_node: NodeRef::default(),
+            export_quality_node: NodeRef::default(),
+            export_transparent_node: NodeRef::default(),
+            batch_template_node: NodeRef::default(),
+            batch_scenes_node: NodeRef::default(),
+            history_depth_node: NodeRef::default(),
         }
     }
 
@@ -98,31 +165,136 @@ impl Component for ToolBar {
             Msg::FetchJson(files) => {
                 let load_callback = ctx.props().callback.clone().unwrap();
                 ctx.link().send_future(async move {
-                    match load_json(files).await {
-                        Ok(data) => {
-                            load_callback.emit(AppMsg::LoadJson(data));
-                            Msg::Refresh
-                        }
+                    let data = match load_project(files).await {
+                        Ok(data) => data,
                         Err(err) => {
                             load_callback.emit(AppMsg::Error(err));
-                            Msg::Refresh
+                            return Msg::Refresh;
+                        }
+                    };
+
+                    load_callback.emit(AppMsg::LoadProject(None, data));
+                    Msg::Refresh
+                });
+            }
+
+            Msg::OpenImages(layer) => {
+                let load_callback = ctx.props().callback.clone().unwrap();
+                ctx.link().send_future(async move {
+                    let result = match native_open_images().await {
+                        Ok(files) => load_native_images(files).await,
+                        Err(err) => Err(err),
+                    };
+
+                    match result {
+                        Ok(images) if images.is_empty() => {}
+                        Ok(images) => {
+                            load_callback.emit(AppMsg::LoadImages(layer, State::Success(images)))
+                        }
+                        Err(err) => {
+                            load_callback.emit(AppMsg::LoadImages(layer, State::Failed(err)))
+                        }
+                    }
+                    Msg::Refresh
+                });
+            }
+
+            Msg::OpenProject => {
+                let load_callback = ctx.props().callback.clone().unwrap();
+                ctx.link().send_future(async move {
+                    match native_open_project().await {
+                        Ok(Some(file)) => {
+                            load_callback.emit(AppMsg::LoadProject(Some(file.path), file.bytes))
                         }
+                        Ok(None) => {}
+                        Err(err) => load_callback.emit(AppMsg::Error(err)),
                     }
+                    Msg::Refresh
                 });
             }
 
             Msg::Save => {
-                let mut canvas_name = self
-                    .canvas_name_node
+                let save_callback = ctx.props().callback.clone().unwrap();
+                save_callback.emit(AppMsg::Save(self.canvas_name(), false));
+            }
+
+            Msg::SaveAs => {
+                let save_callback = ctx.props().callback.clone().unwrap();
+                save_callback.emit(AppMsg::Save(self.canvas_name(), true));
+            }
+
+            Msg::SaveBundle => {
+                let canvas_name = self.canvas_name();
+
+                let save_callback = ctx.props().callback.clone().unwrap();
+                save_callback.emit(AppMsg::SaveBundle(canvas_name));
+            }
+
+            Msg::Export => {
+                let canvas_name = self.canvas_name();
+
+                let export_callback = ctx.props().callback.clone().unwrap();
+                export_callback.emit(AppMsg::Export(canvas_name, self.export_options()));
+            }
+
+            Msg::ExportBatch => {
+                let canvas_name = self.canvas_name();
+
+                let mut template = self
+                    .batch_template_node
                     .cast::<HtmlInputElement>()
                     .unwrap()
                     .value();
-                if canvas_name.as_str() == "" {
-                    canvas_name = String::from("untitled");
+                if template.as_str() == "" {
+                    template = String::from(BATCH_TEMPLATE_DEFAULT);
                 }
 
-                let save_callback = ctx.props().callback.clone().unwrap();
-                save_callback.emit(AppMsg::Save(canvas_name));
+                let by_scenes = self
+                    .batch_scenes_node
+                    .cast::<HtmlInputElement>()
+                    .unwrap()
+                    .checked();
+
+                let export_callback = ctx.props().callback.clone().unwrap();
+                export_callback.emit(AppMsg::ExportBatch(
+                    canvas_name,
+                    self.export_options(),
+                    template,
+                    by_scenes,
+                ));
+            }
+
+            Msg::AddTextLayer => {
+                let add_callback = ctx.props().callback.clone().unwrap();
+                add_callback.emit(AppMsg::AddTextLayer);
+            }
+
+            Msg::AddShapeLayer => {
+                let add_callback = ctx.props().callback.clone().unwrap();
+                add_callback.emit(AppMsg::AddShapeLayer);
+            }
+
+            Msg::Undo => {
+                let undo_callback = ctx.props().callback.clone().unwrap();
+                undo_callback.emit(AppMsg::Undo);
+            }
+
+            Msg::Redo => {
+                let redo_callback = ctx.props().callback.clone().unwrap();
+                redo_callback.emit(AppMsg::Redo);
+            }
+
+            Msg::SetHistoryDepth => {
+                let depth = self
+                    .history_depth_node
+                    .cast::<HtmlInputElement>()
+                    .unwrap()
+                    .value_as_number();
+
+                if !depth.is_nan() && depth >= 0.0 {
+                    let depth_callback = ctx.props().callback.clone().unwrap();
+                    depth_callback.emit(AppMsg::SetHistoryDepth(depth as usize));
+                }
             }
 
             Msg::Refresh => {
@@ -148,6 +320,8 @@ impl Component for ToolBar {
         false
     }
 
+    // the props of the buttons are bare statements once `html!` expands them.
+    #[allow(clippy::unnecessary_operation)]
     fn view(&self, ctx: &Context<Self>) -> Html {
         let image_upload_ref = self.image_upload_node.clone();
         let layer_name_ref = self.layer_name_node.clone();
@@ -166,6 +340,12 @@ impl Component for ToolBar {
             Msg::FetchImages(layer_name, result)
         });
 
+        let layer_name_ref = self.layer_name_node.clone();
+        let open_images = ctx.link().callback(move |_| {
+            let layer_name = layer_name_ref.cast::<HtmlInputElement>().unwrap().value();
+            Msg::OpenImages(layer_name)
+        });
+
         let json_upload_ref = self.json_upload_node.clone();
         let upload_json = ctx.link().callback(move |_| {
             let mut result = Vec::new();
@@ -186,7 +366,7 @@ impl Component for ToolBar {
                 <div class="flex flex-auto flex-nowrap flex-row items-center">
                     <div>
                         <label class="btn icon-btn-grey" for="reset-btn">
-                            <svg 
+                            <svg
                                 class="icon-svg mr-0"
                                 xmlns="http://www.w3.org/2000/svg"
                                 fill="none" viewBox="0 0 24 24" stroke="currentColor"
@@ -260,6 +440,71 @@ impl Component for ToolBar {
                             </li>
                         </ul>
                     </nav>
+                    <nav class="m-2 px-4 h-16 border-2 bg-white flex flex-nowrap shrink-0 justify-between items-center">
+                        <ul class="shrink-0">
+                            <li>
+                                <Button
+                                    id="undo-btn"
+                                    class="btn icon-btn-grey"
+                                    title="undo (Ctrl+Z)"
+                                    onclick={ctx.link().callback(|_| Msg::Undo)}
+                                    disabled={!ctx.props().can_undo}
+                                >
+                                    <svg
+                                        class="icon-svg mr-0"
+                                        xmlns="http://www.w3.org/2000/svg"
+                                        fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                    >
+                                        <path
+                                            stroke-linecap="round"
+                                            stroke-linejoin="round"
+                                            stroke-width="2"
+                                            d="M3 10h10a8 8 0 018 8v2M3 10l6 6m-6-6l6-6"
+                                        />
+                                    </svg>
+                                </Button>
+                            </li>
+                        </ul>
+                        <ul class="shrink-0">
+                            <li>
+                                <Button
+                                    id="redo-btn"
+                                    class="btn icon-btn-grey"
+                                    title="redo (Ctrl+Shift+Z)"
+                                    onclick={ctx.link().callback(|_| Msg::Redo)}
+                                    disabled={!ctx.props().can_redo}
+                                >
+                                    <svg
+                                        class="icon-svg mr-0"
+                                        xmlns="http://www.w3.org/2000/svg"
+                                        fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                    >
+                                        <path
+                                            stroke-linecap="round"
+                                            stroke-linejoin="round"
+                                            stroke-width="2"
+                                            d="M21 10h-10a8 8 0 00-8 8v2M21 10l-6 6m6-6l-6-6"
+                                        />
+                                    </svg>
+                                </Button>
+                            </li>
+                        </ul>
+                        <ul class="shrink-0">
+                            <li>
+                                <label class="input-label">
+                                    <span>{"History Depth"}</span>
+                                </label>
+                                <input
+                                    class="input-number"
+                                    ref={self.history_depth_node.clone()}
+                                    value={ctx.props().history_depth.to_string()}
+                                    type="number"
+                                    min="0"
+                                    onchange={ctx.link().callback(|_| Msg::SetHistoryDepth)}
+                                />
+                            </li>
+                        </ul>
+                    </nav>
                 </div>
                 <div class="flex flex-auto flex-nowrap shrink-0 flex-row items-center">
                     <nav class="m-2 px-4 flex shrink-0 flex-nowrap justify-between items-center bg-white h-16 border-2">
@@ -291,18 +536,78 @@ impl Component for ToolBar {
                                             d="M4 16l4.586-4.586a2 2 0 012.828 0L16 16m-2-2l1.586-1.586a2 2 0 012.828 0L20 14m-6-6h.01M6 20h12a2 2 0 002-2V6a2 2 0 00-2-2H6a2 2 0 00-2 2v12a2 2 0 002 2z"
                                         />
                                     </svg>
-                                    <input
-                                        ref={self.image_upload_node.clone()}
-                                        id="image-upload-btn"
-                                        style="display: none"
-                                        type="file"
-                                        multiple=true
-                                        accept="image/png"
-                                        onchange={upload_images}
-                                    />
+                                    {
+                                        if is_tauri() {
+                                            html! {
+                                                <input
+                                                    ref={self.image_upload_node.clone()}
+                                                    id="image-upload-btn"
+                                                    style="display: none"
+                                                    type="button"
+                                                    onclick={open_images}
+                                                />
+                                            }
+                                        } else {
+                                            html! {
+                                                <input
+                                                    ref={self.image_upload_node.clone()}
+                                                    id="image-upload-btn"
+                                                    style="display: none"
+                                                    type="file"
+                                                    multiple=true
+                                                    accept="image/*"
+                                                    onchange={upload_images}
+                                                />
+                                            }
+                                        }
+                                    }
                                 </label>
                             </li>
                         </ul>
+                        <ul class="shrink-0">
+                            <li>
+                                <Button
+                                    id="add-text-btn"
+                                    class="btn icon-btn-grey"
+                                    title="add text layer"
+                                    onclick={ctx.link().callback(|_| Msg::AddTextLayer)}
+                                >
+                                    <svg
+                                        class="icon-svg mr-0"
+                                        xmlns="http://www.w3.org/2000/svg"
+                                        fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                    >
+                                        <path
+                                            stroke-linecap="round"
+                                            stroke-linejoin="round"
+                                            stroke-width="2"
+                                            d="M4 6V4h16v2M12 4v16m-3 0h6"
+                                        />
+                                    </svg>
+                                </Button>
+                            </li>
+                            <li>
+                                <Button
+                                    id="add-shape-btn"
+                                    class="btn icon-btn-grey"
+                                    title="add shape layer"
+                                    onclick={ctx.link().callback(|_| Msg::AddShapeLayer)}
+                                >
+                                    <svg
+                                        class="icon-svg mr-0"
+                                        xmlns="http://www.w3.org/2000/svg"
+                                        fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                    >
+                                        <path
+                                            stroke-linecap="round"
+                                            stroke-linejoin="round"
+                                            stroke-width="2"
+                                            d="M4 14h6v6H4zM17 4l4 7h-8zM17 14a3 3 0 110 6 3 3 0 010-6z"
+                                        />
+                                    </svg>
+                                </Button>
+                            </li>
+                        </ul>
                     </nav>
                     <nav class="m-2 px-4 flex flex-nowrap shrink-0 justify-between items-center bg-white h-16 border-2">
                         <ul class="shrink-0">
@@ -332,6 +637,54 @@ impl Component for ToolBar {
                                     />
                                 </label>
                             </li>
+                            {
+                                if is_tauri() {
+                                    html! {
+                                        <li class="shrink-0 mr-2">
+                                            <label class="btn icon-btn-grey" for="save-as-button" title="save as">
+                                                <svg
+                                                    class="icon-svg mr-0"
+                                                    xmlns="http://www.w3.org/2000/svg"
+                                                    fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                                >
+                                                    <path
+                                                        stroke-linecap="round"
+                                                        stroke-linejoin="round"
+                                                        stroke-width="2"
+                                                        d="M8 7H5a2 2 0 00-2 2v9a2 2 0 002 2h14a2 2 0 002-2V9a2 2 0 00-2-2h-3m-1 4l-3 3m0 0l-3-3m3 3V4"
+                                                    />
+                                                </svg>
+                                                <input
+                                                    class="hidden" id="save-as-button" type="button"
+                                                    onclick={ctx.link().callback(|_| Msg::SaveAs)}
+                                                />
+                                            </label>
+                                        </li>
+                                    }
+                                } else {
+                                    html! {}
+                                }
+                            }
+                            <li class="shrink-0 mr-2">
+                                <label class="btn icon-btn-grey" for="save-bundle-button" title="save with images">
+                                    <svg
+                                        class="icon-svg mr-0"
+                                        xmlns="http://www.w3.org/2000/svg"
+                                        fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                    >
+                                        <path
+                                            stroke-linecap="round"
+                                            stroke-linejoin="round"
+                                            stroke-width="2"
+                                            d="M5 8h14M5 8a2 2 0 110-4h14a2 2 0 110 4M5 8v10a2 2 0 002 2h10a2 2 0 002-2V8m-9 4h4"
+                                        />
+                                    </svg>
+                                    <input
+                                        class="hidden" id="save-bundle-button" type="button"
+                                        onclick={ctx.link().callback(|_| {Msg::SaveBundle})}
+                                    />
+                                </label>
+                            </li>
                             <li class="shrink-0">
                                 <label class="btn icon-btn-grey" for="json-upload-btn">
                                     <svg
@@ -346,13 +699,149 @@ impl Component for ToolBar {
                                             d="M4 16v1a3 3 0 003 3h10a3 3 0 003-3v-1m-4-8l-4-4m0 0L8 8m4-4v12"
                                         />
                                     </svg>
+                                    {
+                                        if is_tauri() {
+                                            html! {
+                                                <input
+                                                    class="hidden"
+                                                    ref={self.json_upload_node.clone()}
+                                                    id="json-upload-btn"
+                                                    type="button"
+                                                    onclick={ctx.link().callback(|_| Msg::OpenProject)}
+                                                />
+                                            }
+                                        } else {
+                                            html! {
+                                                <input
+                                                    class="hidden"
+                                                    ref={self.json_upload_node.clone()}
+                                                    id="json-upload-btn"
+                                                    type="file"
+                                                    accept="application/JSON,application/zip,.json,.zip"
+                                                    onchange={upload_json}
+                                                />
+                                            }
+                                        }
+                                    }
+                                </label>
+                            </li>
+                        </ul>
+                    </nav>
+                    <nav class="m-2 px-4 flex flex-nowrap shrink-0 justify-between items-center bg-white h-16 border-2">
+                        <ul class="shrink-0">
+                            <li>
+                                <label class="input-label">
+                                    <span>{"Export"}</span>
+                                </label>
+                                <select
+                                    class="input-number"
+                                    ref={self.export_format_node.clone()}
+                                >
+                                    <option value="png" selected=true>{"PNG"}</option>
+                                    <option value="jpeg">{"JPEG"}</option>
+                                    <option value="webp">{"WebP"}</option>
+                                </select>
+                            </li>
+                        </ul>
+                        <ul class="shrink-0">
+                            <li>
+                                <label class="input-label">
+                                    <span>{"Quality"}</span>
+                                </label>
+                                <input
+                                    class="input-float"
+                                    ref={self.export_quality_node.clone()}
+                                    type="number"
+                                    min="0"
+                                    max="1"
+                                    step="0.01"
+                                    value="0.92"
+                                />
+                            </li>
+                        </ul>
+                        <ul class="shrink-0">
+                            <li>
+                                <label class="input-label" for="export-transparent-checkbox">
+                                    <span>{"Transparent"}</span>
+                                </label>
+                                <input
+                                    ref={self.export_transparent_node.clone()}
+                                    id="export-transparent-checkbox"
+                                    type="checkbox"
+                                    checked=true
+                                />
+                            </li>
+                        </ul>
+                        <ul class="shrink-0 ml-2">
+                            <li>
+                                <label class="btn icon-btn-grey" for="export-button" title="export image">
+                                    <svg
+                                        class="icon-svg mr-0"
+                                        xmlns="http://www.w3.org/2000/svg"
+                                        fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                    >
+                                        <path
+                                            stroke-linecap="round"
+                                            stroke-linejoin="round"
+                                            stroke-width="2"
+                                            d="M3 9a2 2 0 012-2h.93a2 2 0 001.664-.89l.812-1.22A2 2 0 0110.07 4h3.86a2 2 0 011.664.89l.812 1.22A2 2 0 0018.07 7H19a2 2 0 012 2v9a2 2 0 01-2 2H5a2 2 0 01-2-2V9z"
+                                        />
+                                        <path
+                                            stroke-linecap="round"
+                                            stroke-linejoin="round"
+                                            stroke-width="2"
+                                            d="M15 13a3 3 0 11-6 0 3 3 0 016 0z"
+                                        />
+                                    </svg>
                                     <input
-                                        class="hidden"
-                                        ref={self.json_upload_node.clone()}
-                                        id="json-upload-btn"
-                                        type="file"
-                                        accept="application/JSON"
-                                        onchange={upload_json}
+                                        class="hidden" id="export-button" type="button"
+                                        onclick={ctx.link().callback(|_| {Msg::Export})}
+                                    />
+                                </label>
+                            </li>
+                        </ul>
+                        <ul class="shrink-0 ml-2">
+                            <li>
+                                <input
+                                    class="input-text"
+                                    ref={self.batch_template_node.clone()}
+                                    type="text"
+                                    placeholder={BATCH_TEMPLATE_DEFAULT}
+                                    title="the file name of every combination"
+                                />
+                            </li>
+                        </ul>
+                        <ul class="shrink-0 ml-2">
+                            <li>
+                                <label class="input-label" for="export-batch-scenes-checkbox">
+                                    <span>{"Scenes"}</span>
+                                </label>
+                                <input
+                                    ref={self.batch_scenes_node.clone()}
+                                    id="export-batch-scenes-checkbox"
+                                    type="checkbox"
+                                    title="export every scene instead of every combination"
+                                />
+                            </li>
+                        </ul>
+                        <ul class="shrink-0 ml-2">
+                            <li>
+                                <label class="btn icon-btn-grey" for="export-batch-button" title="export every combination">
+                                    <svg
+                                        class="icon-svg mr-0"
+                                        xmlns="http://www.w3.org/2000/svg"
+                                        fill="none" viewBox="0 0 24 24" stroke="currentColor"
+                                    >
+                                        <path
+                                            stroke-linecap="round"
+                                            stroke-linejoin="round"
+                                            stroke-width="2"
+                                            d="M4 16l4.586-4.586a2 2 0 012.828 0L16 16m-2-2l1.586-1.586a2 2 0 012.828 0L20 14m-6-6h.01M6 20h12a2 2 0 002-2V6a2 2 0 00-2-2H6a2 2 0 00-2 2v12a2 2 0 002 2z"
+                                        />
+                                    </svg>
+                                    <input
+                                        class="hidden" id="export-batch-button" type="button"
+                                        onclick={ctx.link().callback(|_| {Msg::ExportBatch})}
                                     />
                                 </label>
                             </li>
@@ -0,0 +1,233 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-04 21:26:10
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-04 23:52:47
+ * @FilePath: /layer-painter/wasm/src/compositor/animated.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use std::collections::HashMap;
+
+use crate::canvas::{Animation, Canvas, PlaybackMode};
+use crate::compositor::{encode_gif, render_onto, Dither, GifOptions, Pixmap};
+
+/// More frames than this are most likely a timeline of layers with unrelated lengths.
+pub const ANIMATED_FRAMES_MAX: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimatedFormat {
+    Gif,
+    Apng,
+}
+
+impl AnimatedFormat {
+    pub const ALL: [AnimatedFormat; 2] = [AnimatedFormat::Gif, AnimatedFormat::Apng];
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "gif" => Some(AnimatedFormat::Gif),
+            "apng" => Some(AnimatedFormat::Apng),
+            _ => None,
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            AnimatedFormat::Gif => "image/gif",
+            AnimatedFormat::Apng => "image/apng",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnimatedFormat::Gif => "gif",
+            AnimatedFormat::Apng => "apng",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimatedOptions {
+    pub format: AnimatedFormat,
+    // the time of every frame in milliseconds.
+    pub delay: u32,
+    // 0 plays forever.
+    pub loops: u32,
+    // gif only.
+    pub colors: usize,
+    pub dither: Dither,
+    pub transparent: bool,
+}
+
+impl AnimatedOptions {
+    /// The delay from the fps of `animation`, playing once for the `once` mode and forever for the others.
+    pub fn new(format: AnimatedFormat, animation: &Animation) -> Self {
+        AnimatedOptions {
+            format,
+            delay: (1000.0 / animation.fps()).round() as u32,
+            loops: match animation.mode() {
+                PlaybackMode::Once => 1,
+                _ => 0,
+            },
+            colors: 256,
+            dither: Dither::default(),
+            transparent: true,
+        }
+    }
+
+    pub fn filename(&self, canvas_name: &str) -> String {
+        format!("{}.{}", canvas_name, self.format.extension())
+    }
+}
+
+/// Composite every frame of the timeline of `canvas`,
+/// returns the frames and the names of the layers which can't be rendered.
+pub fn render_frames(
+    canvas: &Canvas,
+    images: &HashMap<String, Pixmap>,
+    transparent: bool,
+) -> Result<(Vec<Pixmap>, Vec<String>), String> {
+    let timeline_len = canvas.timeline_len();
+    if timeline_len > ANIMATED_FRAMES_MAX {
+        return Err(format!(
+            "the timeline has {} frames, more than {}",
+            timeline_len, ANIMATED_FRAMES_MAX
+        ));
+    }
+
+    let mut canvas = canvas.clone();
+    let mut frames = Vec::with_capacity(timeline_len);
+    let mut failed: Vec<String> = Vec::new();
+    for frame in 0..timeline_len {
+        canvas.set_frame(frame);
+
+        let mut pixmap = if transparent {
+            Pixmap::new(canvas.width(), canvas.height())
+        } else {
+            Pixmap::filled(canvas.width(), canvas.height(), [255, 255, 255, 255])
+        };
+        for layer_name in render_onto(&mut pixmap, &canvas, images) {
+            if !failed.contains(&layer_name) {
+                failed.push(layer_name);
+            }
+        }
+        frames.push(pixmap);
+    }
+
+    Ok((frames, failed))
+}
+
+/// Encode `frames` as `options.format`.
+pub fn encode_animated(frames: &[Pixmap], options: &AnimatedOptions) -> Result<Vec<u8>, String> {
+    match options.format {
+        AnimatedFormat::Gif => encode_gif(
+            frames,
+            &GifOptions {
+                delay: options.delay,
+                loops: options.loops,
+                colors: options.colors,
+                dither: options.dither,
+            },
+        ),
+        AnimatedFormat::Apng => encode_apng(frames, options.delay, options.loops),
+    }
+}
+
+/// Encode `frames` of the same size as a lossless apng with alpha.
+pub fn encode_apng(frames: &[Pixmap], delay: u32, loops: u32) -> Result<Vec<u8>, String> {
+    let first = frames
+        .first()
+        .ok_or_else(|| String::from("there are no frames to encode"))?;
+    let (width, height) = (first.width(), first.height());
+
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(frames.len() as u32, loops)
+            .map_err(|err| format!("{}", err))?;
+        encoder
+            .set_frame_delay(delay.min(u16::MAX as u32) as u16, 1000)
+            .map_err(|err| format!("{}", err))?;
+
+        let mut writer = encoder.write_header().map_err(|err| format!("{}", err))?;
+        for frame in frames.iter() {
+            if frame.width() != width || frame.height() != height {
+                return Err(String::from("the frames have different sizes"));
+            }
+            writer
+                .write_image_data(frame.data())
+                .map_err(|err| format!("{}", err))?;
+        }
+        writer.finish().map_err(|err| format!("{}", err))?;
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{Image, Layer, ScaleMode};
+
+    #[test]
+    fn apng_frames_roundtrip() {
+        let frames = vec![
+            Pixmap::filled(2, 1, [255, 0, 0, 255]),
+            Pixmap::filled(2, 1, [0, 0, 255, 128]),
+        ];
+        let bytes = encode_apng(&frames, 50, 3).unwrap();
+
+        let decoder = png::Decoder::new(bytes.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control().copied().unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (2, 3));
+
+        for frame in frames.iter() {
+            let mut buffer = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut buffer).unwrap();
+            assert_eq!(buffer.as_slice(), frame.data());
+        }
+    }
+
+    #[test]
+    fn frames_of_the_timeline() {
+        let mut layer = Layer::empty("sprite");
+        for name in ["red", "blue"] {
+            layer.append_image(Image::new(name, None, 1.0, 1.0));
+        }
+        layer.set_scale(ScaleMode::Stretch);
+        layer.set_animated(true);
+        let mut canvas = Canvas::new();
+        canvas.set_width(1);
+        canvas.set_height(1);
+        canvas.append_layer(layer);
+
+        let images: HashMap<String, Pixmap> = [
+            (String::from("red"), Pixmap::filled(1, 1, [255, 0, 0, 255])),
+            (String::from("blue"), Pixmap::filled(1, 1, [0, 0, 255, 255])),
+        ]
+        .into_iter()
+        .collect();
+        let (frames, failed) = render_frames(&canvas, &images, true).unwrap();
+        assert!(failed.is_empty());
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(frames[1].pixel(0, 0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn options_from_the_animation() {
+        let options = AnimatedOptions::new(AnimatedFormat::Gif, &Animation::default());
+        assert_eq!(options.delay, 83);
+        assert_eq!(options.loops, 0);
+        assert_eq!(options.filename("sprite"), "sprite.gif");
+        assert_eq!(
+            AnimatedFormat::from_extension("APNG"),
+            Some(AnimatedFormat::Apng)
+        );
+    }
+}
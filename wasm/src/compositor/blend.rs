@@ -0,0 +1,252 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-23 21:10:52
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-23 23:18:30
+ * @FilePath: /layer-painter/wasm/src/compositor/blend.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+//! Blending and source-over compositing of premultiplied colors,
+//! following the W3C Compositing and Blending spec, the same as
+//! `globalCompositeOperation` of a 2d canvas.
+
+use crate::canvas::BlendMode;
+
+/// Composite the premultiplied `src` over `dst` with `mode`.
+pub fn blend_pixel(mode: BlendMode, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+    let sa = src[3];
+    let da = dst[3];
+
+    if sa <= 0.0 {
+        return dst;
+    }
+
+    let out_alpha = sa + da * (1.0 - sa);
+
+    if mode == BlendMode::Normal || da <= 0.0 {
+        return [
+            src[0] + dst[0] * (1.0 - sa),
+            src[1] + dst[1] * (1.0 - sa),
+            src[2] + dst[2] * (1.0 - sa),
+            out_alpha,
+        ];
+    }
+
+    let cs = unpremultiply(src);
+    let cb = unpremultiply(dst);
+    let mixed = blend_color(mode, cb, cs);
+
+    let mut out = [0.0; 4];
+    for c in 0..3 {
+        out[c] = src[c] * (1.0 - da) + dst[c] * (1.0 - sa) + sa * da * mixed[c];
+    }
+    out[3] = out_alpha;
+
+    out
+}
+
+fn unpremultiply(color: [f32; 4]) -> [f32; 3] {
+    if color[3] <= 0.0 {
+        return [0.0; 3];
+    }
+
+    [
+        (color[0] / color[3]).clamp(0.0, 1.0),
+        (color[1] / color[3]).clamp(0.0, 1.0),
+        (color[2] / color[3]).clamp(0.0, 1.0),
+    ]
+}
+
+/// `B(Cb, Cs)` of the spec, with straight colors of the backdrop and the source.
+fn blend_color(mode: BlendMode, cb: [f32; 3], cs: [f32; 3]) -> [f32; 3] {
+    let separable = |f: fn(f32, f32) -> f32| [f(cb[0], cs[0]), f(cb[1], cs[1]), f(cb[2], cs[2])];
+
+    match mode {
+        BlendMode::Normal => cs,
+        BlendMode::Multiply => separable(multiply),
+        BlendMode::Screen => separable(screen),
+        BlendMode::Overlay => separable(|b, s| hard_light(s, b)),
+        BlendMode::Darken => separable(f32::min),
+        BlendMode::Lighten => separable(f32::max),
+        BlendMode::ColorDodge => separable(color_dodge),
+        BlendMode::ColorBurn => separable(color_burn),
+        BlendMode::HardLight => separable(hard_light),
+        BlendMode::SoftLight => separable(soft_light),
+        BlendMode::Difference => separable(|b, s| (b - s).abs()),
+        BlendMode::Exclusion => separable(|b, s| b + s - 2.0 * b * s),
+        BlendMode::Hue => set_lum(set_sat(cs, sat(cb)), lum(cb)),
+        BlendMode::Saturation => set_lum(set_sat(cb, sat(cs)), lum(cb)),
+        BlendMode::Color => set_lum(cs, lum(cb)),
+        BlendMode::Luminosity => set_lum(cb, lum(cs)),
+    }
+}
+
+fn multiply(b: f32, s: f32) -> f32 {
+    b * s
+}
+
+fn screen(b: f32, s: f32) -> f32 {
+    b + s - b * s
+}
+
+fn hard_light(b: f32, s: f32) -> f32 {
+    if s <= 0.5 {
+        multiply(b, 2.0 * s)
+    } else {
+        screen(b, 2.0 * s - 1.0)
+    }
+}
+
+fn color_dodge(b: f32, s: f32) -> f32 {
+    if b <= 0.0 {
+        0.0
+    } else if s >= 1.0 {
+        1.0
+    } else {
+        (b / (1.0 - s)).min(1.0)
+    }
+}
+
+fn color_burn(b: f32, s: f32) -> f32 {
+    if b >= 1.0 {
+        1.0
+    } else if s <= 0.0 {
+        0.0
+    } else {
+        1.0 - ((1.0 - b) / s).min(1.0)
+    }
+}
+
+fn soft_light(b: f32, s: f32) -> f32 {
+    if s <= 0.5 {
+        b - (1.0 - 2.0 * s) * b * (1.0 - b)
+    } else {
+        let d = if b <= 0.25 {
+            ((16.0 * b - 12.0) * b + 4.0) * b
+        } else {
+            b.sqrt()
+        };
+        b + (2.0 * s - 1.0) * (d - b)
+    }
+}
+
+fn lum(c: [f32; 3]) -> f32 {
+    0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+}
+
+fn clip_color(c: [f32; 3]) -> [f32; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+
+    let mut out = c;
+    for v in out.iter_mut() {
+        if n < 0.0 {
+            *v = l + (*v - l) * l / (l - n);
+        }
+        if x > 1.0 {
+            *v = l + (*v - l) * (1.0 - l) / (x - l);
+        }
+    }
+    out
+}
+
+fn set_lum(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(c);
+    clip_color([c[0] + d, c[1] + d, c[2] + d])
+}
+
+fn sat(c: [f32; 3]) -> f32 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+fn set_sat(c: [f32; 3], s: f32) -> [f32; 3] {
+    let max = c[0].max(c[1]).max(c[2]);
+    let min = c[0].min(c[1]).min(c[2]);
+
+    if max <= min {
+        return [0.0; 3];
+    }
+
+    let mut out = [0.0; 3];
+    for (o, v) in out.iter_mut().zip(c.iter()) {
+        *o = if *v >= max {
+            s
+        } else if *v <= min {
+            0.0
+        } else {
+            (*v - min) * s / (max - min)
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(expected.iter()) {
+            assert!((a - e).abs() < 1e-3, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn normal_is_source_over() {
+        let src = [0.5, 0.0, 0.0, 0.5];
+        let dst = [0.0, 0.0, 1.0, 1.0];
+        assert_close(
+            blend_pixel(BlendMode::Normal, src, dst),
+            [0.5, 0.0, 0.5, 1.0],
+        );
+        assert_close(blend_pixel(BlendMode::Normal, [0.0; 4], dst), dst);
+    }
+
+    #[test]
+    fn blend_over_transparent_is_source_over() {
+        let src = [0.3, 0.2, 0.1, 0.5];
+        for mode in BlendMode::ALL {
+            assert_close(blend_pixel(mode, src, [0.0; 4]), src);
+        }
+    }
+
+    #[test]
+    fn blend_formulas_of_opaque_colors() {
+        // the backdrop and the source, straight and opaque.
+        let cb = [0.2, 0.5, 0.8, 1.0];
+        let cs = [0.6, 0.25, 0.9, 1.0];
+        let expected = [
+            (BlendMode::Normal, [0.6, 0.25, 0.9]),
+            (BlendMode::Multiply, [0.12, 0.125, 0.72]),
+            (BlendMode::Screen, [0.68, 0.625, 0.98]),
+            (BlendMode::Overlay, [0.24, 0.25, 0.96]),
+            (BlendMode::Darken, [0.2, 0.25, 0.8]),
+            (BlendMode::Lighten, [0.6, 0.5, 0.9]),
+            (BlendMode::ColorDodge, [0.5, 0.6667, 1.0]),
+            (BlendMode::ColorBurn, [0.0, 0.0, 0.7778]),
+            (BlendMode::HardLight, [0.36, 0.25, 0.96]),
+            (BlendMode::SoftLight, [0.2496, 0.375, 0.8755]),
+            (BlendMode::Difference, [0.4, 0.25, 0.1]),
+            (BlendMode::Exclusion, [0.56, 0.5, 0.26]),
+            (BlendMode::Hue, [0.6032, 0.2801, 0.8801]),
+            (BlendMode::Saturation, [0.1798, 0.5048, 0.8297]),
+            (BlendMode::Color, [0.6165, 0.2665, 0.9165]),
+            (BlendMode::Luminosity, [0.1835, 0.4835, 0.7835]),
+        ];
+        assert_eq!(expected.len(), BlendMode::ALL.len());
+
+        for (mode, [r, g, b]) in expected {
+            assert_close(blend_pixel(mode, cs, cb), [r, g, b, 1.0]);
+        }
+    }
+
+    #[test]
+    fn blend_of_translucent_source() {
+        // Cs (1 - ab) + Cb (1 - as) + as ab B(Cb, Cs), premultiplied.
+        let src = [0.3, 0.125, 0.45, 0.5];
+        let dst = [0.2, 0.5, 0.8, 1.0];
+        let expected = [0.1 + 0.5 * 0.12, 0.25 + 0.5 * 0.125, 0.4 + 0.5 * 0.72, 1.0];
+        assert_close(blend_pixel(BlendMode::Multiply, src, dst), expected);
+    }
+}
@@ -0,0 +1,544 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-04 20:11:42
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-04 23:36:05
+ * @FilePath: /layer-painter/wasm/src/compositor/gif.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+//! A small GIF89a encoder, one palette of every frame by median cut,
+//! with optional Floyd-Steinberg dithering.
+
+use std::collections::HashMap;
+
+use crate::compositor::Pixmap;
+
+use serde::{Deserialize, Serialize};
+
+// the pixels under it are transparent in a gif.
+const ALPHA_THRESHOLD: u8 = 128;
+const LZW_CODES_MAX: u16 = 4096;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dither {
+    None,
+    #[default]
+    FloydSteinberg,
+}
+
+impl Dither {
+    pub const ALL: [Dither; 2] = [Dither::None, Dither::FloydSteinberg];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::FloydSteinberg => "floyd-steinberg",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Dither::ALL
+            .iter()
+            .copied()
+            .find(|dither| dither.name() == name)
+    }
+}
+
+/// The settings of a gif.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GifOptions {
+    // in milliseconds, a gif keeps hundredths of a second.
+    pub delay: u32,
+    // 0 plays forever.
+    pub loops: u32,
+    // 2 ~ 256, with the transparent one.
+    pub colors: usize,
+    pub dither: Dither,
+}
+
+/// Encode `frames` of the same size as an animated gif.
+pub fn encode_gif(frames: &[Pixmap], options: &GifOptions) -> Result<Vec<u8>, String> {
+    let first = frames
+        .first()
+        .ok_or_else(|| String::from("there are no frames to encode"))?;
+    let (width, height) = (first.width(), first.height());
+    if frames
+        .iter()
+        .any(|frame| frame.width() != width || frame.height() != height)
+    {
+        return Err(String::from("the frames have different sizes"));
+    }
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(format!("a gif can't be {} x {}", width, height));
+    }
+
+    let transparent = frames.iter().any(|frame| {
+        frame
+            .data()
+            .chunks_exact(4)
+            .any(|pixel| pixel[3] < ALPHA_THRESHOLD)
+    });
+    let colors = options.colors.clamp(2, 256);
+    let mut palette = median_cut(frames, if transparent { colors - 1 } else { colors });
+    let transparent_index = if transparent {
+        palette.push([0, 0, 0]);
+        Some((palette.len() - 1) as u8)
+    } else {
+        None
+    };
+
+    // the color table has 2 ^ (bits) entries.
+    let mut table_bits = 1;
+    while (1 << table_bits) < palette.len() {
+        table_bits += 1;
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"GIF89a");
+    push_u16(&mut bytes, width as u16);
+    push_u16(&mut bytes, height as u16);
+    bytes.push(0x80 | (7 << 4) | (table_bits - 1));
+    bytes.push(0);
+    bytes.push(0);
+    for index in 0..(1 << table_bits) {
+        bytes.extend_from_slice(palette.get(index).unwrap_or(&[0, 0, 0]));
+    }
+
+    // gif plays once without it, and repeats the count after the first time.
+    if options.loops != 1 {
+        let repeats = options.loops.saturating_sub(1).min(u16::MAX as u32) as u16;
+        bytes.extend_from_slice(&[0x21, 0xff, 0x0b]);
+        bytes.extend_from_slice(b"NETSCAPE2.0");
+        bytes.extend_from_slice(&[0x03, 0x01]);
+        push_u16(&mut bytes, repeats);
+        bytes.push(0);
+    }
+
+    // under 2 hundredths, browsers wait much longer.
+    let delay = ((options.delay + 5) / 10).clamp(2, u16::MAX as u32) as u16;
+    let mut mapper = PaletteMapper::new(&palette[..palette.len() - transparent as usize]);
+    for frame in frames.iter() {
+        let indexes = map_frame(frame, &mut mapper, transparent_index, options.dither);
+
+        // clear the last frame under the transparent pixels.
+        let disposal = if transparent { 2 } else { 1 };
+        bytes.extend_from_slice(&[0x21, 0xf9, 0x04]);
+        bytes.push((disposal << 2) | transparent as u8);
+        push_u16(&mut bytes, delay);
+        bytes.push(transparent_index.unwrap_or(0));
+        bytes.push(0);
+
+        bytes.push(0x2c);
+        push_u16(&mut bytes, 0);
+        push_u16(&mut bytes, 0);
+        push_u16(&mut bytes, width as u16);
+        push_u16(&mut bytes, height as u16);
+        bytes.push(0);
+
+        let min_code_size = table_bits.max(2);
+        bytes.push(min_code_size);
+        for block in lzw_encode(&indexes, min_code_size).chunks(255) {
+            bytes.push(block.len() as u8);
+            bytes.extend_from_slice(block);
+        }
+        bytes.push(0);
+    }
+
+    bytes.push(0x3b);
+    Ok(bytes)
+}
+
+fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Up to `max_colors` colors for the opaque pixels of `frames`,
+/// splitting the box of colors with the widest channel at the median until there are enough.
+fn median_cut(frames: &[Pixmap], max_colors: usize) -> Vec<[u8; 3]> {
+    let mut histogram: HashMap<[u8; 3], u64> = HashMap::new();
+    for frame in frames.iter() {
+        for pixel in frame.data().chunks_exact(4) {
+            if pixel[3] >= ALPHA_THRESHOLD {
+                *histogram.entry([pixel[0], pixel[1], pixel[2]]).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut colors: Vec<([u8; 3], u64)> = histogram.into_iter().collect();
+    if colors.is_empty() {
+        return vec![[0, 0, 0]];
+    }
+    // the same palette for the same frames.
+    colors.sort_unstable();
+
+    // the colors of every box with its widest channel and the range of it.
+    let (channel, range) = widest_channel(&colors);
+    let mut boxes = vec![(colors, channel, range)];
+    while boxes.len() < max_colors.max(1) {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (_, _, range))| *range)
+            .map(|(index, (_, channel, range))| (index, *channel, *range));
+        let (index, channel, range) = match widest {
+            Some(widest) => widest,
+            None => break,
+        };
+        if range == 0 {
+            break;
+        }
+
+        let (mut colors, _, _) = boxes.swap_remove(index);
+        colors.sort_by_key(|(color, _)| color[channel]);
+        let total: u64 = colors.iter().map(|(_, count)| count).sum();
+        let mut count = 0;
+        let mut split = colors.len() - 1;
+        for (index, (_, color_count)) in colors.iter().enumerate() {
+            count += color_count;
+            if count * 2 >= total {
+                split = (index + 1).clamp(1, colors.len() - 1);
+                break;
+            }
+        }
+        let rest = colors.split_off(split);
+        for colors in [colors, rest] {
+            let (channel, range) = widest_channel(&colors);
+            boxes.push((colors, channel, range));
+        }
+    }
+
+    boxes
+        .iter()
+        .map(|(colors, _, _)| {
+            let total: u64 = colors.iter().map(|(_, count)| count).sum();
+            let mut sum = [0u64; 3];
+            for (color, count) in colors.iter() {
+                for channel in 0..3 {
+                    sum[channel] += color[channel] as u64 * count;
+                }
+            }
+            sum.map(|sum| ((sum + total / 2) / total) as u8)
+        })
+        .collect()
+}
+
+// the channel with the largest range of `colors` and the range.
+fn widest_channel(colors: &[([u8; 3], u64)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let min = colors
+                .iter()
+                .map(|(color, _)| color[channel])
+                .min()
+                .unwrap_or(0);
+            let max = colors
+                .iter()
+                .map(|(color, _)| color[channel])
+                .max()
+                .unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .unwrap_or((0, 0))
+}
+
+// finds the nearest color of the palette, remembering the found ones.
+struct PaletteMapper<'a> {
+    palette: &'a [[u8; 3]],
+    found: HashMap<[u8; 3], u8>,
+}
+
+impl<'a> PaletteMapper<'a> {
+    fn new(palette: &'a [[u8; 3]]) -> Self {
+        Self {
+            palette,
+            found: HashMap::new(),
+        }
+    }
+
+    fn index_of(&mut self, color: [u8; 3]) -> u8 {
+        let palette = self.palette;
+        *self.found.entry(color).or_insert_with(|| {
+            palette
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, entry)| {
+                    (0..3)
+                        .map(|channel| {
+                            let diff = entry[channel] as i32 - color[channel] as i32;
+                            diff * diff
+                        })
+                        .sum::<i32>()
+                })
+                .map(|(index, _)| index as u8)
+                .unwrap_or(0)
+        })
+    }
+
+    fn color(&self, index: u8) -> [u8; 3] {
+        self.palette[index as usize]
+    }
+}
+
+// the palette indexes of the pixels of `frame`.
+fn map_frame(
+    frame: &Pixmap,
+    mapper: &mut PaletteMapper,
+    transparent_index: Option<u8>,
+    dither: Dither,
+) -> Vec<u8> {
+    let width = frame.width();
+    let mut indexes = Vec::with_capacity(width * frame.height());
+    // the errors spread to this row and the next one.
+    let mut errors = vec![[0f32; 3]; width + 2];
+    let mut next_errors = vec![[0f32; 3]; width + 2];
+
+    for y in 0..frame.height() {
+        for x in 0..width {
+            let pixel = frame.pixel(x, y);
+            if let (Some(transparent_index), true) = (transparent_index, pixel[3] < ALPHA_THRESHOLD)
+            {
+                indexes.push(transparent_index);
+                continue;
+            }
+
+            if dither == Dither::None {
+                indexes.push(mapper.index_of([pixel[0], pixel[1], pixel[2]]));
+                continue;
+            }
+
+            // the errors of (x, y) are at x + 1.
+            let error = errors[x + 1];
+            let color = [0, 1, 2].map(|channel| {
+                (pixel[channel] as f32 + error[channel])
+                    .round()
+                    .clamp(0.0, 255.0) as u8
+            });
+            let index = mapper.index_of(color);
+            indexes.push(index);
+
+            let found = mapper.color(index);
+            for channel in 0..3 {
+                let error = color[channel] as f32 - found[channel] as f32;
+                errors[x + 2][channel] += error * 7.0 / 16.0;
+                next_errors[x][channel] += error * 3.0 / 16.0;
+                next_errors[x + 1][channel] += error * 5.0 / 16.0;
+                next_errors[x + 2][channel] += error / 16.0;
+            }
+        }
+
+        std::mem::swap(&mut errors, &mut next_errors);
+        next_errors.iter_mut().for_each(|error| *error = [0.0; 3]);
+    }
+
+    indexes
+}
+
+// packs the codes from the lowest bit.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// The variable length lzw codes of gif for `indexes`.
+fn lzw_encode(indexes: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+    let mut writer = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next = end + 1;
+
+    writer.write(clear, code_size);
+    let mut indexes = indexes.iter();
+    let mut prefix = match indexes.next() {
+        Some(index) => *index as u16,
+        None => {
+            writer.write(end, code_size);
+            return writer.finish();
+        }
+    };
+
+    for index in indexes {
+        if let Some(code) = table.get(&(prefix, *index)) {
+            prefix = *code;
+            continue;
+        }
+
+        writer.write(prefix, code_size);
+        if next == LZW_CODES_MAX {
+            writer.write(clear, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next = end + 1;
+        } else {
+            if next >= 1 << code_size {
+                code_size += 1;
+            }
+            table.insert((prefix, *index), next);
+            next += 1;
+        }
+        prefix = *index as u16;
+    }
+
+    writer.write(prefix, code_size);
+    writer.write(end, code_size);
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a plain gif lzw decoder, the codes grow when the table is full for the code size.
+    fn lzw_decode(bytes: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let end = clear + 1;
+        let reset = || {
+            (0..clear + 2)
+                .map(|code| vec![code as u8])
+                .collect::<Vec<_>>()
+        };
+        let mut table = reset();
+        let mut code_size = min_code_size + 1;
+        let mut position = 0;
+        let mut prev: Option<Vec<u8>> = None;
+        let mut result = Vec::new();
+
+        loop {
+            let mut code = 0;
+            for bit in 0..code_size as usize {
+                let byte = bytes[(position + bit) / 8];
+                code |= (((byte >> ((position + bit) % 8)) & 1) as usize) << bit;
+            }
+            position += code_size as usize;
+
+            if code == clear {
+                table = reset();
+                code_size = min_code_size + 1;
+                prev = None;
+                continue;
+            }
+            if code == end {
+                return result;
+            }
+
+            let entry = match (table.get(code), prev.as_ref()) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(prev)) => {
+                    let mut entry = prev.clone();
+                    entry.push(prev[0]);
+                    entry
+                }
+                (None, None) => panic!("the code {} isn't in the table", code),
+            };
+            result.extend_from_slice(&entry);
+
+            if let Some(mut prev) = prev.take() {
+                if table.len() < LZW_CODES_MAX as usize {
+                    prev.push(entry[0]);
+                    table.push(prev);
+                }
+            }
+            if table.len() == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+            prev = Some(entry);
+        }
+    }
+
+    // deterministic noise of `colors` indexes.
+    fn noise(len: usize, colors: u32) -> Vec<u8> {
+        let mut seed = 12345u32;
+        (0..len)
+            .map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                ((seed >> 16) % colors) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lzw_roundtrip() {
+        for min_code_size in 2..=8u8 {
+            let colors = 1u32 << min_code_size;
+            // long enough to fill the table and clear it.
+            for indexes in [
+                noise(40000, colors),
+                vec![0; 10000],
+                noise(7, colors),
+                vec![],
+            ] {
+                let bytes = lzw_encode(&indexes, min_code_size);
+                assert_eq!(
+                    lzw_decode(&bytes, min_code_size),
+                    indexes,
+                    "{}",
+                    min_code_size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn median_cut_keeps_few_colors() {
+        let mut frame = Pixmap::filled(2, 2, [255, 0, 0, 255]);
+        frame.set_pixel(1, 1, [0, 0, 255, 255]);
+        let mut palette = median_cut(&[frame.clone()], 16);
+        palette.sort_unstable();
+        assert_eq!(palette, vec![[0, 0, 255], [255, 0, 0]]);
+        assert_eq!(median_cut(&[frame], 1).len(), 1);
+    }
+
+    #[test]
+    fn gif_frames() {
+        let mut frame = Pixmap::filled(3, 2, [255, 0, 0, 255]);
+        frame.set_pixel(0, 0, [0, 0, 0, 0]);
+        let options = GifOptions {
+            delay: 100,
+            loops: 0,
+            colors: 256,
+            dither: Dither::None,
+        };
+        let bytes = encode_gif(&[frame.clone(), frame], &options).unwrap();
+
+        assert_eq!(&bytes[..6], b"GIF89a");
+        assert_eq!(&bytes[6..10], &[3, 0, 2, 0]);
+        assert_eq!(*bytes.last().unwrap(), 0x3b);
+        // the loop extension and two frames.
+        let find = |pattern: &[u8]| {
+            bytes
+                .windows(pattern.len())
+                .filter(|w| *w == pattern)
+                .count()
+        };
+        assert_eq!(find(b"NETSCAPE2.0"), 1);
+        assert_eq!(find(&[0x21, 0xf9, 0x04, (2 << 2) | 1, 10, 0]), 2);
+
+        assert!(encode_gif(&[], &options).is_err());
+        assert!(encode_gif(&[Pixmap::new(1, 1), Pixmap::new(2, 1)], &options).is_err());
+    }
+}
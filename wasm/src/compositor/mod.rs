@@ -0,0 +1,26 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-18 20:09:26
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-18 23:41:18
+ * @FilePath: /layer-painter/wasm/src/compositor/mod.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+mod animated;
+mod blend;
+mod gif;
+mod pixmap;
+mod render;
+mod shape;
+mod sheet;
+mod text;
+
+pub use animated::*;
+pub use blend::blend_pixel;
+pub use gif::{encode_gif, Dither, GifOptions};
+pub use pixmap::{Pixmap, PIXMAP_SIZE_MAX};
+pub use render::*;
+pub use shape::*;
+pub use sheet::*;
+pub use text::*;
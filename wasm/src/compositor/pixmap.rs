@@ -0,0 +1,291 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-18 20:11:37
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-18 23:40:12
+ * @FilePath: /layer-painter/wasm/src/compositor/pixmap.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+/// The largest side of the pixmaps drawn from text and shape layers,
+/// larger ones are most likely typos and would take up the whole memory.
+pub const PIXMAP_SIZE_MAX: usize = 16384;
+
+/// A straight (not premultiplied) RGBA8 pixel buffer, row by row from the top left,
+/// the same layout as `ImageData` in the browser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pixmap {
+    width: usize,
+    height: usize,
+    data: Vec<u8>,
+}
+
+impl Pixmap {
+    pub fn new(width: usize, height: usize) -> Self {
+        Pixmap {
+            width,
+            height,
+            data: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn filled(width: usize, height: usize, rgba: [u8; 4]) -> Self {
+        let mut data = Vec::with_capacity(width * height * 4);
+        for _ in 0..width * height {
+            data.extend_from_slice(&rgba);
+        }
+
+        Pixmap {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn from_rgba(width: usize, height: usize, data: Vec<u8>) -> Result<Self, String> {
+        if data.len() != width * height * 4 {
+            return Err(format!(
+                "rgba data len (is {}) should be width * height * 4 (is {})",
+                data.len(),
+                width * height * 4
+            ));
+        }
+
+        Ok(Pixmap {
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = (y * self.width + x) * 4;
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, rgba: [u8; 4]) {
+        let i = (y * self.width + x) * 4;
+        self.data[i..i + 4].copy_from_slice(&rgba);
+    }
+
+    /// The pixels of the rect, which must be inside.
+    pub fn crop(&self, x: usize, y: usize, width: usize, height: usize) -> Pixmap {
+        let mut data = Vec::with_capacity(width * height * 4);
+        for row in y..y + height {
+            let start = (row * self.width + x) * 4;
+            data.extend_from_slice(&self.data[start..start + width * 4]);
+        }
+
+        Pixmap {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Copy the pixels of `source` with its top left at `(x, y)`, without blending.
+    pub fn copy_from(&mut self, source: &Pixmap, x: usize, y: usize) {
+        let width = source.width.min(self.width.saturating_sub(x));
+        for row in 0..source.height.min(self.height.saturating_sub(y)) {
+            let from = row * source.width * 4;
+            let to = ((y + row) * self.width + x) * 4;
+            self.data[to..to + width * 4].copy_from_slice(&source.data[from..from + width * 4]);
+        }
+    }
+
+    /// Premultiplied color of the pixel in 0.0 ~ 1.0.
+    pub fn premultiplied(&self, x: usize, y: usize) -> [f32; 4] {
+        let [r, g, b, a] = self.pixel(x, y);
+        let a = a as f32 / 255.0;
+        [
+            r as f32 / 255.0 * a,
+            g as f32 / 255.0 * a,
+            b as f32 / 255.0 * a,
+            a,
+        ]
+    }
+
+    pub fn set_premultiplied(&mut self, x: usize, y: usize, color: [f32; 4]) {
+        let a = color[3].clamp(0.0, 1.0);
+        let unpremultiply = |c: f32| {
+            if a <= 0.0 {
+                0
+            } else {
+                ((c / a).clamp(0.0, 1.0) * 255.0).round() as u8
+            }
+        };
+
+        self.set_pixel(
+            x,
+            y,
+            [
+                unpremultiply(color[0]),
+                unpremultiply(color[1]),
+                unpremultiply(color[2]),
+                (a * 255.0).round() as u8,
+            ],
+        );
+    }
+
+    /// Bilinear sample (premultiplied) at `(x, y)` in pixel space,
+    /// where pixel centers are at `.5`. Samples are clamped into the rect
+    /// `(left, top, right, bottom)`, like `drawImage` does with its source rect.
+    pub fn sample(&self, x: f64, y: f64, bounds: (f64, f64, f64, f64)) -> [f32; 4] {
+        let (left, top, right, bottom) = bounds;
+        if self.width == 0 || self.height == 0 || right <= left || bottom <= top {
+            return [0.0; 4];
+        }
+
+        let clamp_index = |v: f64, min: f64, max: f64, len: usize| -> usize {
+            let v = v.clamp(min, max - 1.0).clamp(0.0, (len - 1) as f64);
+            v as usize
+        };
+
+        let fx = x - 0.5;
+        let fy = y - 0.5;
+        let x0 = fx.floor();
+        let y0 = fy.floor();
+        let tx = (fx - x0) as f32;
+        let ty = (fy - y0) as f32;
+
+        let left = left.floor();
+        let top = top.floor();
+        let right = right.ceil();
+        let bottom = bottom.ceil();
+
+        let xa = clamp_index(x0, left, right, self.width);
+        let xb = clamp_index(x0 + 1.0, left, right, self.width);
+        let ya = clamp_index(y0, top, bottom, self.height);
+        let yb = clamp_index(y0 + 1.0, top, bottom, self.height);
+
+        let p00 = self.premultiplied(xa, ya);
+        let p10 = self.premultiplied(xb, ya);
+        let p01 = self.premultiplied(xa, yb);
+        let p11 = self.premultiplied(xb, yb);
+
+        let mut result = [0.0; 4];
+        for c in 0..4 {
+            let top = p00[c] * (1.0 - tx) + p10[c] * tx;
+            let bottom = p01[c] * (1.0 - tx) + p11[c] * tx;
+            result[c] = top * (1.0 - ty) + bottom * ty;
+        }
+
+        result
+    }
+
+    /// Nearest sample (premultiplied) at `(x, y)`, clamped the same as `sample`.
+    pub fn sample_nearest(&self, x: f64, y: f64, bounds: (f64, f64, f64, f64)) -> [f32; 4] {
+        let (left, top, right, bottom) = bounds;
+        if self.width == 0 || self.height == 0 || right <= left || bottom <= top {
+            return [0.0; 4];
+        }
+
+        let x = x
+            .floor()
+            .clamp(left.floor(), right.ceil() - 1.0)
+            .clamp(0.0, (self.width - 1) as f64);
+        let y = y
+            .floor()
+            .clamp(top.floor(), bottom.ceil() - 1.0)
+            .clamp(0.0, (self.height - 1) as f64);
+
+        self.premultiplied(x as usize, y as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // black | white
+    fn two_pixels() -> Pixmap {
+        Pixmap::from_rgba(2, 1, vec![0, 0, 0, 255, 255, 255, 255, 255]).unwrap()
+    }
+
+    #[test]
+    fn from_rgba_checks_len() {
+        assert!(Pixmap::from_rgba(2, 2, vec![0; 15]).is_err());
+        assert_eq!(
+            Pixmap::from_rgba(1, 1, vec![1, 2, 3, 4])
+                .unwrap()
+                .pixel(0, 0),
+            [1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn premultiplied_roundtrip() {
+        let mut pixmap = Pixmap::new(1, 1);
+        pixmap.set_premultiplied(0, 0, [0.5, 0.25, 0.0, 0.5]);
+        assert_eq!(pixmap.pixel(0, 0), [255, 128, 0, 128]);
+
+        pixmap.set_premultiplied(0, 0, [0.5, 0.5, 0.5, 0.0]);
+        assert_eq!(pixmap.pixel(0, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bilinear_sample_between_centers() {
+        let pixmap = two_pixels();
+        let bounds = (0.0, 0.0, 2.0, 1.0);
+        assert_eq!(pixmap.sample(0.5, 0.5, bounds), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(pixmap.sample(1.0, 0.5, bounds), [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(pixmap.sample(1.25, 0.5, bounds), [0.75, 0.75, 0.75, 1.0]);
+        // clamped at the edges.
+        assert_eq!(pixmap.sample(2.0, 0.5, bounds), [1.0, 1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn nearest_sample_picks_pixel() {
+        let pixmap = two_pixels();
+        let bounds = (0.0, 0.0, 2.0, 1.0);
+        assert_eq!(
+            pixmap.sample_nearest(0.9, 0.5, bounds),
+            [0.0, 0.0, 0.0, 1.0]
+        );
+        assert_eq!(
+            pixmap.sample_nearest(1.1, 0.5, bounds),
+            [1.0, 1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn sample_is_clamped_into_bounds() {
+        let pixmap = two_pixels();
+        // only the white pixel is in the source rect.
+        let bounds = (1.0, 0.0, 2.0, 1.0);
+        assert_eq!(pixmap.sample(1.0, 0.5, bounds), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(
+            pixmap.sample_nearest(0.5, 0.5, bounds),
+            [1.0, 1.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn crop_and_copy_from() {
+        let pixmap = two_pixels();
+        let white = pixmap.crop(1, 0, 1, 1);
+        assert_eq!(white.pixel(0, 0), [255, 255, 255, 255]);
+
+        let mut target = Pixmap::new(2, 2);
+        target.copy_from(&white, 1, 1);
+        assert_eq!(target.pixel(1, 1), [255, 255, 255, 255]);
+        assert_eq!(target.pixel(0, 0), [0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,902 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-18 20:25:03
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-18 23:52:45
+ * @FilePath: /layer-painter/wasm/src/compositor/render.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use std::collections::HashMap;
+
+use crate::canvas::{BlendMode, Canvas, Image, Layer, Mask, ScaleMode};
+use crate::compositor::{
+    blend_pixel, draw_shape, shape_image, shape_sample, text_size, text_to_pixmap, Pixmap,
+};
+
+/// Where the source rect of an image is drawn on the canvas,
+/// the same arguments as `drawImage(image, sx, sy, sw, sh, dx, dy, dw, dh)`,
+/// then flipped and rotated (clockwise, in degrees) around the anchor.
+/// A tiled source rect is repeated in the size of `tile` over the destination rect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub sx: f64,
+    pub sy: f64,
+    pub sw: f64,
+    pub sh: f64,
+    pub dx: f64,
+    pub dy: f64,
+    pub dw: f64,
+    pub dh: f64,
+    pub rotation: f64,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    pub anchor_x: f64,
+    pub anchor_y: f64,
+    pub tile: Option<(f64, f64)>,
+    // bilinear or nearest sampling.
+    pub smooth: bool,
+}
+
+impl Placement {
+    fn is_transformed(&self) -> bool {
+        self.rotation % 360.0 != 0.0 || self.flip_h || self.flip_v
+    }
+
+    fn pivot(&self) -> (f64, f64) {
+        (
+            self.dx + self.anchor_x * self.dw,
+            self.dy + self.anchor_y * self.dh,
+        )
+    }
+
+    /// Map a point of the destination rect to the canvas.
+    pub fn to_canvas(&self, x: f64, y: f64) -> (f64, f64) {
+        if !self.is_transformed() {
+            return (x, y);
+        }
+
+        let (px, py) = self.pivot();
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let mut x = x - px;
+        let mut y = y - py;
+        if self.flip_h {
+            x = -x;
+        }
+        if self.flip_v {
+            y = -y;
+        }
+
+        (px + x * cos - y * sin, py + x * sin + y * cos)
+    }
+
+    /// Map a point of the canvas back to the destination rect before the transform.
+    pub fn to_local(&self, x: f64, y: f64) -> (f64, f64) {
+        if !self.is_transformed() {
+            return (x, y);
+        }
+
+        let (px, py) = self.pivot();
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let (x, y) = (x - px, y - py);
+        let mut lx = x * cos + y * sin;
+        let mut ly = -x * sin + y * cos;
+        if self.flip_h {
+            lx = -lx;
+        }
+        if self.flip_v {
+            ly = -ly;
+        }
+
+        (px + lx, py + ly)
+    }
+
+    /// Map a point of the destination rect before the transform to the source image.
+    pub fn to_source(&self, x: f64, y: f64) -> (f64, f64) {
+        let (width, height) = self.tile.unwrap_or((self.dw, self.dh));
+        let x = (x - self.dx).rem_euclid(width);
+        let y = (y - self.dy).rem_euclid(height);
+
+        (
+            self.sx + x * self.sw / width,
+            self.sy + y * self.sh / height,
+        )
+    }
+
+    /// The size of a pixel of the destination rect in the source rect.
+    pub fn source_pixel(&self) -> f64 {
+        let (width, height) = self.tile.unwrap_or((self.dw, self.dh));
+        if width <= 0.0 || height <= 0.0 {
+            return 1.0;
+        }
+        (self.sw / width * self.sh / height).sqrt()
+    }
+
+    fn sample(&self, source: &Pixmap, u: f64, v: f64) -> [f32; 4] {
+        let bounds = (self.sx, self.sy, self.sx + self.sw, self.sy + self.sh);
+        if self.smooth {
+            source.sample(u, v, bounds)
+        } else {
+            source.sample_nearest(u, v, bounds)
+        }
+    }
+
+    /// The corners of the transformed destination rect on the canvas, clockwise
+    /// from the top left one before the transform.
+    pub fn corners(&self) -> [(f64, f64); 4] {
+        [
+            self.to_canvas(self.dx, self.dy),
+            self.to_canvas(self.dx + self.dw, self.dy),
+            self.to_canvas(self.dx + self.dw, self.dy + self.dh),
+            self.to_canvas(self.dx, self.dy + self.dh),
+        ]
+    }
+
+    /// Whether the canvas point is inside the transformed destination rect.
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        let (x, y) = self.to_local(x, y);
+        x >= self.dx && x < self.dx + self.dw && y >= self.dy && y < self.dy + self.dh
+    }
+
+    /// The bounding box of the transformed destination rect on the canvas,
+    /// as (left, top, right, bottom).
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        self.corners().iter().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(l, t, r, b), (x, y)| (l.min(*x), t.min(*y), r.max(*x), b.max(*y)),
+        )
+    }
+}
+
+/// The box a layer is scaled into, see `ScaleMode`.
+pub fn layer_box(canvas: &Canvas, layer: &Layer) -> (f64, f64) {
+    let auto = |size: f64, canvas_size: usize| {
+        if size > 0.0 {
+            size
+        } else {
+            canvas_size as f64
+        }
+    };
+
+    (
+        auto(layer.dw(), canvas.width()),
+        auto(layer.dh(), canvas.height()),
+    )
+}
+
+pub fn layer_placement(canvas: &Canvas, layer: &Layer, image: &Image) -> Placement {
+    let transform = layer.transform();
+    let (box_width, box_height) = layer_box(canvas, layer);
+    let (mut sx, mut sy, mut sw, mut sh) = (image.sx(), image.sy(), image.sw(), image.sh());
+    let (mut dx, mut dy) = (transform.dx, transform.dy);
+    let mut tile = None;
+    let mut smooth = true;
+
+    let (dw, dh) = match layer.scale() {
+        ScaleMode::None => (sw, sh),
+        ScaleMode::Contain => {
+            let scale = f64::min(box_width / sw, box_height / sh);
+            (sw * scale, sh * scale)
+        }
+        ScaleMode::Cover => {
+            // cut the source rect to the aspect ratio of the box.
+            let scale = f64::max(box_width / sw, box_height / sh);
+            let (cut_width, cut_height) = (box_width / scale, box_height / scale);
+            sx += (sw - cut_width) / 2.0;
+            sy += (sh - cut_height) / 2.0;
+            sw = cut_width;
+            sh = cut_height;
+            (box_width, box_height)
+        }
+        ScaleMode::Stretch => (box_width, box_height),
+        ScaleMode::Center => {
+            let (width, height) = (sw.min(box_width), sh.min(box_height));
+            sx += (sw - width) / 2.0;
+            sy += (sh - height) / 2.0;
+            dx += (box_width - width) / 2.0;
+            dy += (box_height - height) / 2.0;
+            sw = width;
+            sh = height;
+            (width, height)
+        }
+        ScaleMode::Tile => {
+            tile = Some((sw, sh));
+            (box_width, box_height)
+        }
+        ScaleMode::IntegerScale => {
+            let scale = f64::min(box_width / sw, box_height / sh).floor().max(1.0);
+            smooth = false;
+            (sw * scale, sh * scale)
+        }
+    };
+
+    Placement {
+        sx,
+        sy,
+        sw,
+        sh,
+        dx,
+        dy,
+        dw,
+        dh,
+        rotation: transform.rotation,
+        flip_h: transform.flip_h,
+        flip_v: transform.flip_v,
+        anchor_x: transform.anchor_x,
+        anchor_y: transform.anchor_y,
+        tile,
+        smooth,
+    }
+}
+
+/// The image drawn for `layer`, the selected one, the box of its shape,
+/// or its rasterized text found by `Text::key` in `images`, in the size of the pixmap.
+/// A text missing from `images` has the size of it in the bundled font.
+pub fn layer_image(layer: &Layer, images: &HashMap<String, Pixmap>) -> Option<Image> {
+    if let Some(shape) = layer.get_shape() {
+        return Some(shape_image(shape));
+    }
+
+    match layer.get_text() {
+        Some(text) => {
+            let key = text.key();
+            let (width, height) = match images.get(key.as_str()) {
+                Some(pixmap) => (pixmap.width(), pixmap.height()),
+                None => text_size(text).ok()?,
+            };
+            Some(Image::new(key.as_str(), None, width as f64, height as f64))
+        }
+        None => layer.get_selected_image().cloned(),
+    }
+}
+
+/// Map the canvas point (`x`, `y`) to the normalized space of the mask of `layer`.
+pub fn layer_mask_point(
+    canvas: &Canvas,
+    layer: &Layer,
+    images: &HashMap<String, Pixmap>,
+    x: f64,
+    y: f64,
+) -> Option<(f64, f64)> {
+    if layer.is_group() {
+        return Some((x / canvas.width() as f64, y / canvas.height() as f64));
+    }
+
+    let placement = layer_placement(canvas, layer, &layer_image(layer, images)?);
+    if placement.dw <= 0.0 || placement.dh <= 0.0 {
+        return None;
+    }
+
+    let (x, y) = placement.to_local(x, y);
+    Some((
+        (x - placement.dx) / placement.dw,
+        (y - placement.dy) / placement.dh,
+    ))
+}
+
+/// The size of a new mask of `layer`, the size of its source rect or the canvas.
+pub fn layer_mask_size(canvas: &Canvas, layer: &Layer) -> (usize, usize) {
+    match layer.get_selected_image() {
+        Some(image) if !layer.is_group() => {
+            (image.sw().round() as usize, image.sh().round() as usize)
+        }
+        _ => (canvas.width(), canvas.height()),
+    }
+}
+
+/// The index of the topmost visible layer which has a visible pixel at the canvas point,
+/// a layer without decoded pixels is hit inside its rect. Groups are never picked.
+pub fn layer_at(
+    canvas: &Canvas,
+    images: &HashMap<String, Pixmap>,
+    x: f64,
+    y: f64,
+) -> Option<usize> {
+    // the depth of the hidden group being skipped.
+    let mut hidden: Option<usize> = None;
+    let mut visible = Vec::new();
+
+    for (index, (depth, layer)) in canvas.walk_layers().into_iter().enumerate() {
+        match hidden {
+            Some(hidden_depth) if depth > hidden_depth => continue,
+            _ => hidden = None,
+        }
+
+        if !layer.show() {
+            hidden = Some(depth);
+            continue;
+        }
+
+        if !layer.is_group() {
+            visible.push((index, layer));
+        }
+    }
+
+    // a later layer in the walk is drawn over the earlier ones.
+    visible.into_iter().rev().find_map(|(index, layer)| {
+        let image = layer_image(layer, images)?;
+        let placement = layer_placement(canvas, layer, &image);
+        if placement.dw <= 0.0 || placement.dh <= 0.0 || !placement.contains(x, y) {
+            return None;
+        }
+
+        let (local_x, local_y) = placement.to_local(x, y);
+        let (u, v) = placement.to_source(local_x, local_y);
+
+        let mut alpha = match (layer.get_shape(), images.get(image.name().as_str())) {
+            (Some(shape), _) => shape_sample(shape, u, v, placement.source_pixel())[3],
+            (None, Some(source)) => placement.sample(source, u, v)[3],
+            (None, None) => return Some(index),
+        };
+        if let Some(mask) = layer.mask().filter(|mask| mask.enabled()) {
+            alpha *= mask.coverage(
+                (local_x - placement.dx) / placement.dw,
+                (local_y - placement.dy) / placement.dh,
+            );
+        }
+
+        if alpha > 0.0 {
+            Some(index)
+        } else {
+            None
+        }
+    })
+}
+
+/// Composite the visible layers of `canvas` on a transparent pixmap of the canvas size.
+/// `images` are the decoded pixels of every `Image` by its name,
+/// returns the pixmap and the names of the layers which can't be rendered.
+pub fn render(canvas: &Canvas, images: &HashMap<String, Pixmap>) -> (Pixmap, Vec<String>) {
+    let mut target = Pixmap::new(canvas.width(), canvas.height());
+    let failed = render_onto(&mut target, canvas, images);
+    (target, failed)
+}
+
+/// Same as `render`, but composites over the existing pixels of `target`.
+pub fn render_onto(
+    target: &mut Pixmap,
+    canvas: &Canvas,
+    images: &HashMap<String, Pixmap>,
+) -> Vec<String> {
+    let mut failed = Vec::new();
+    render_layers(target, canvas, canvas.get_layers(), images, &mut failed);
+    failed
+}
+
+fn render_layers(
+    target: &mut Pixmap,
+    canvas: &Canvas,
+    layers: &[Layer],
+    images: &HashMap<String, Pixmap>,
+    failed: &mut Vec<String>,
+) {
+    for layer in layers.iter() {
+        if !layer.show() {
+            continue;
+        }
+
+        let mask = layer.mask().filter(|mask| mask.enabled());
+
+        // a group is rendered alone first, so the blend modes of its layers
+        // only apply inside the group.
+        if layer.is_group() {
+            let mut group = Pixmap::new(target.width(), target.height());
+            render_layers(&mut group, canvas, layer.get_layers(), images, failed);
+            composite_pixmap(target, &group, layer.opacity(), layer.blend(), mask);
+            continue;
+        }
+
+        if let Some(shape) = layer.get_shape() {
+            let placement = layer_placement(canvas, layer, &shape_image(shape));
+            draw_shape(
+                target,
+                shape,
+                &placement,
+                layer.opacity(),
+                layer.blend(),
+                mask,
+            );
+            continue;
+        }
+
+        let image = match layer_image(layer, images) {
+            Some(image) => image,
+            None => {
+                failed.push(layer.name());
+                continue;
+            }
+        };
+
+        // the text missing from `images` is drawn in the bundled font.
+        let rasterized = match layer.get_text() {
+            Some(text) if !images.contains_key(image.name().as_str()) => text_to_pixmap(text).ok(),
+            _ => None,
+        };
+
+        match rasterized
+            .as_ref()
+            .or_else(|| images.get(image.name().as_str()))
+        {
+            Some(source) => {
+                let placement = layer_placement(canvas, layer, &image);
+                draw_pixmap(
+                    target,
+                    source,
+                    &placement,
+                    layer.opacity(),
+                    layer.blend(),
+                    mask,
+                );
+            }
+            None => failed.push(layer.name()),
+        }
+    }
+}
+
+/// Composite `source` of the same size over `target`, pixel by pixel.
+/// `mask` is stretched over the whole pixmap.
+pub fn composite_pixmap(
+    target: &mut Pixmap,
+    source: &Pixmap,
+    opacity: f64,
+    blend: BlendMode,
+    mask: Option<&Mask>,
+) {
+    if opacity <= 0.0 {
+        return;
+    }
+
+    let opacity = opacity.min(1.0) as f32;
+    let width = target.width().min(source.width());
+    let height = target.height().min(source.height());
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut src = source.premultiplied(x, y);
+            if src[3] <= 0.0 {
+                continue;
+            }
+
+            let mut alpha = opacity;
+            if let Some(mask) = mask {
+                alpha *= mask.coverage(
+                    (x as f64 + 0.5) / width as f64,
+                    (y as f64 + 0.5) / height as f64,
+                );
+            }
+            for c in src.iter_mut() {
+                *c *= alpha;
+            }
+
+            let dst = target.premultiplied(x, y);
+            target.set_premultiplied(x, y, blend_pixel(blend, src, dst));
+        }
+    }
+}
+
+/// Draw the source rect of `source` into the destination rect of `target`
+/// with bilinear or nearest sampling, blended by `blend` and composited by source-over.
+/// `mask` is stretched over the destination rect.
+pub fn draw_pixmap(
+    target: &mut Pixmap,
+    source: &Pixmap,
+    placement: &Placement,
+    opacity: f64,
+    blend: BlendMode,
+    mask: Option<&Mask>,
+) {
+    draw_samples(target, placement, opacity, blend, mask, |u, v| {
+        placement.sample(source, u, v)
+    });
+}
+
+/// Draw the premultiplied colors of `sample` at the points of the source rect
+/// into the destination rect of `target`, see `draw_pixmap`.
+pub(crate) fn draw_samples<F>(
+    target: &mut Pixmap,
+    placement: &Placement,
+    opacity: f64,
+    blend: BlendMode,
+    mask: Option<&Mask>,
+    sample: F,
+) where
+    F: Fn(f64, f64) -> [f32; 4],
+{
+    let Placement {
+        sw,
+        sh,
+        dx,
+        dy,
+        dw,
+        dh,
+        ..
+    } = *placement;
+
+    if sw <= 0.0 || sh <= 0.0 || dw <= 0.0 || dh <= 0.0 || opacity <= 0.0 {
+        return;
+    }
+
+    let opacity = opacity.min(1.0) as f32;
+
+    // pixels whose centers may be inside the transformed destination rect.
+    let (left, top, right, bottom) = placement.bounds();
+    let x_start = (left - 0.5).ceil().max(0.0) as usize;
+    let y_start = (top - 0.5).ceil().max(0.0) as usize;
+    let x_end = ((right - 0.5).ceil().max(0.0) as usize).min(target.width());
+    let y_end = ((bottom - 0.5).ceil().max(0.0) as usize).min(target.height());
+
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            let (local_x, local_y) = placement.to_local(x as f64 + 0.5, y as f64 + 0.5);
+            if local_x < dx || local_x >= dx + dw || local_y < dy || local_y >= dy + dh {
+                continue;
+            }
+
+            let (u, v) = placement.to_source(local_x, local_y);
+
+            let mut src = sample(u, v);
+            if src[3] <= 0.0 {
+                continue;
+            }
+
+            let mut alpha = opacity;
+            if let Some(mask) = mask {
+                alpha *= mask.coverage((local_x - dx) / dw, (local_y - dy) / dh);
+            }
+            for c in src.iter_mut() {
+                *c *= alpha;
+            }
+
+            let dst = target.premultiplied(x, y);
+            target.set_premultiplied(x, y, blend_pixel(blend, src, dst));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::{MaskMode, Text, Transform};
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    fn canvas(width: usize, height: usize, layers: Vec<Layer>) -> Canvas {
+        let mut canvas = Canvas::new();
+        canvas.set_width(width);
+        canvas.set_height(height);
+        canvas.set_layers(layers);
+        canvas
+    }
+
+    fn image_layer(image: &str, width: f64, height: f64, scale: ScaleMode) -> Layer {
+        let mut layer = Layer::empty(image);
+        layer.append_image(Image::new(image, None, width, height));
+        layer.set_scale(scale);
+        layer
+    }
+
+    fn images(pixmaps: &[(&str, Pixmap)]) -> HashMap<String, Pixmap> {
+        pixmaps
+            .iter()
+            .map(|(name, pixmap)| (String::from(*name), pixmap.clone()))
+            .collect()
+    }
+
+    // a 2x2 pixmap, red, blue / black, white.
+    fn quad() -> Pixmap {
+        let mut pixmap = Pixmap::new(2, 2);
+        pixmap.set_pixel(0, 0, RED);
+        pixmap.set_pixel(1, 0, BLUE);
+        pixmap.set_pixel(0, 1, [0, 0, 0, 255]);
+        pixmap.set_pixel(1, 1, [255, 255, 255, 255]);
+        pixmap
+    }
+
+    fn placement(scale: ScaleMode) -> Placement {
+        let layer = image_layer("a", 2.0, 2.0, scale);
+        let canvas = canvas(8, 4, vec![layer.clone()]);
+        layer_placement(&canvas, &layer, layer.get_selected_image().unwrap())
+    }
+
+    fn rects(placement: &Placement) -> [f64; 8] {
+        let Placement {
+            sx,
+            sy,
+            sw,
+            sh,
+            dx,
+            dy,
+            dw,
+            dh,
+            ..
+        } = *placement;
+        [sx, sy, sw, sh, dx, dy, dw, dh]
+    }
+
+    #[test]
+    fn placement_of_every_scale_mode() {
+        let expected = [
+            (ScaleMode::None, [0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 2.0, 2.0]),
+            (ScaleMode::Contain, [0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 4.0, 4.0]),
+            (ScaleMode::Cover, [0.0, 0.5, 2.0, 1.0, 0.0, 0.0, 8.0, 4.0]),
+            (ScaleMode::Stretch, [0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 8.0, 4.0]),
+            (ScaleMode::Center, [0.0, 0.0, 2.0, 2.0, 3.0, 1.0, 2.0, 2.0]),
+            (ScaleMode::Tile, [0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 8.0, 4.0]),
+            (
+                ScaleMode::IntegerScale,
+                [0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 4.0, 4.0],
+            ),
+        ];
+        assert_eq!(expected.len(), ScaleMode::ALL.len());
+
+        for (scale, rect) in expected {
+            let placement = placement(scale);
+            assert_eq!(rects(&placement), rect, "{}", scale.name());
+            assert_eq!(placement.tile.is_some(), scale == ScaleMode::Tile);
+            assert_eq!(placement.smooth, scale != ScaleMode::IntegerScale);
+        }
+    }
+
+    #[test]
+    fn source_rect_crops_the_image() {
+        let mut layer = image_layer("a", 2.0, 2.0, ScaleMode::None);
+        let image = &mut layer.get_mut_images()[0];
+        image.set_sx(1.0);
+        image.set_sy(1.0);
+        image.set_sw(1.0);
+        image.set_sh(1.0);
+
+        let canvas = canvas(2, 2, vec![layer]);
+        let (pixmap, failed) = render(&canvas, &images(&[("a", quad())]));
+        assert!(failed.is_empty());
+        assert_eq!(pixmap.pixel(0, 0), [255, 255, 255, 255]);
+        assert_eq!(pixmap.pixel(1, 0), CLEAR);
+        assert_eq!(pixmap.pixel(0, 1), CLEAR);
+    }
+
+    #[test]
+    fn offsets_move_the_image() {
+        let mut layer = image_layer("a", 2.0, 2.0, ScaleMode::None);
+        layer.set_dx(1.0);
+        layer.set_dy(2.0);
+
+        let canvas = canvas(4, 4, vec![layer]);
+        let (pixmap, _) = render(&canvas, &images(&[("a", quad())]));
+        assert_eq!(pixmap.pixel(0, 0), CLEAR);
+        assert_eq!(pixmap.pixel(1, 2), RED);
+        assert_eq!(pixmap.pixel(2, 2), BLUE);
+        assert_eq!(pixmap.pixel(2, 3), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn integer_scale_keeps_pixels_sharp() {
+        let canvas = canvas(
+            4,
+            4,
+            vec![image_layer("a", 2.0, 2.0, ScaleMode::IntegerScale)],
+        );
+        let (pixmap, _) = render(&canvas, &images(&[("a", quad())]));
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            assert_eq!(pixmap.pixel(x, y), RED);
+        }
+        assert_eq!(pixmap.pixel(2, 0), BLUE);
+    }
+
+    #[test]
+    fn opacity_scales_alpha() {
+        let mut layer = image_layer("a", 1.0, 1.0, ScaleMode::Stretch);
+        layer.set_opacity(0.5);
+
+        let canvas = canvas(1, 1, vec![layer]);
+        let (pixmap, _) = render(&canvas, &images(&[("a", Pixmap::filled(1, 1, RED))]));
+        assert_eq!(pixmap.pixel(0, 0), [255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn hidden_and_missing_layers() {
+        let mut hidden = image_layer("a", 1.0, 1.0, ScaleMode::Stretch);
+        hidden.set_show(false);
+        let missing = image_layer("b", 1.0, 1.0, ScaleMode::Stretch);
+
+        let canvas = canvas(1, 1, vec![hidden, missing]);
+        let (pixmap, failed) = render(&canvas, &images(&[("a", Pixmap::filled(1, 1, RED))]));
+        assert_eq!(pixmap.pixel(0, 0), CLEAR);
+        assert_eq!(failed, vec![String::from("b")]);
+    }
+
+    #[test]
+    fn group_is_rendered_alone() {
+        let mut multiply = image_layer("red", 1.0, 1.0, ScaleMode::Stretch);
+        multiply.set_blend(BlendMode::Multiply);
+        let layers = vec![
+            image_layer("blue", 1.0, 1.0, ScaleMode::Stretch),
+            Layer::group("group", vec![multiply.clone()]),
+        ];
+        let pixmaps = images(&[
+            ("red", Pixmap::filled(1, 1, RED)),
+            ("blue", Pixmap::filled(1, 1, BLUE)),
+        ]);
+
+        // the multiply only blends inside the group, over nothing.
+        let (pixmap, _) = render(&canvas(1, 1, layers), &pixmaps);
+        assert_eq!(pixmap.pixel(0, 0), RED);
+
+        let layers = vec![image_layer("blue", 1.0, 1.0, ScaleMode::Stretch), multiply];
+        let (pixmap, _) = render(&canvas(1, 1, layers), &pixmaps);
+        assert_eq!(pixmap.pixel(0, 0), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn group_opacity_applies_once() {
+        let mut group = Layer::group(
+            "group",
+            vec![
+                image_layer("red", 1.0, 1.0, ScaleMode::Stretch),
+                image_layer("red", 1.0, 1.0, ScaleMode::Stretch),
+            ],
+        );
+        group.set_opacity(0.5);
+        let pixmaps = images(&[("red", Pixmap::filled(1, 1, RED))]);
+
+        let (pixmap, _) = render(&canvas(1, 1, vec![group.clone()]), &pixmaps);
+        assert_eq!(pixmap.pixel(0, 0), [255, 0, 0, 128]);
+
+        group.set_show(false);
+        let (pixmap, _) = render(&canvas(1, 1, vec![group]), &pixmaps);
+        assert_eq!(pixmap.pixel(0, 0), CLEAR);
+    }
+
+    #[test]
+    fn mask_hides_the_layer() {
+        // hides the left half.
+        let rgba = [0, 0, 0, 255, 255, 255, 255, 255];
+        let mask = Mask::from_rgba(2, 1, &rgba, MaskMode::Luminance).unwrap();
+        let mut layer = image_layer("a", 1.0, 1.0, ScaleMode::Stretch);
+        layer.set_mask(Some(mask.clone()));
+
+        let pixmaps = images(&[("a", Pixmap::filled(1, 1, RED))]);
+        let (pixmap, _) = render(&canvas(4, 1, vec![layer.clone()]), &pixmaps);
+        assert_eq!(pixmap.pixel(0, 0), CLEAR);
+        assert_eq!(pixmap.pixel(3, 0), RED);
+        assert_eq!(
+            layer_at(&canvas(4, 1, vec![layer.clone()]), &pixmaps, 0.5, 0.5),
+            None
+        );
+        assert_eq!(
+            layer_at(&canvas(4, 1, vec![layer.clone()]), &pixmaps, 3.5, 0.5),
+            Some(0)
+        );
+
+        // a disabled mask shows everything.
+        layer.get_mut_mask().unwrap().set_enabled(false);
+        let (pixmap, _) = render(&canvas(4, 1, vec![layer]), &pixmaps);
+        assert_eq!(pixmap.pixel(0, 0), RED);
+
+        // the mask of a group covers the canvas.
+        let mut group = Layer::group(
+            "group",
+            vec![image_layer("a", 1.0, 1.0, ScaleMode::Stretch)],
+        );
+        group.set_mask(Some(mask));
+        let (pixmap, _) = render(&canvas(4, 1, vec![group]), &pixmaps);
+        assert_eq!(pixmap.pixel(0, 0), CLEAR);
+        assert_eq!(pixmap.pixel(3, 0), RED);
+    }
+
+    fn transformed(transform: Transform) -> Layer {
+        let mut layer = image_layer("a", 2.0, 2.0, ScaleMode::None);
+        layer.set_transform(transform);
+        layer
+    }
+
+    #[test]
+    fn rotation_around_the_anchor() {
+        let layer = transformed(Transform {
+            rotation: 90.0,
+            ..Transform::default()
+        });
+        let canvas = canvas(2, 2, vec![layer]);
+        let pixmaps = images(&[("a", quad())]);
+
+        // clockwise, red is at the top right.
+        let (pixmap, _) = render(&canvas, &pixmaps);
+        assert_eq!(pixmap.pixel(1, 0), RED);
+        assert_eq!(pixmap.pixel(1, 1), BLUE);
+        assert_eq!(pixmap.pixel(0, 0), [0, 0, 0, 255]);
+        assert_eq!(pixmap.pixel(0, 1), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn flips_mirror_the_image() {
+        let pixmaps = images(&[("a", quad())]);
+
+        let layer = transformed(Transform {
+            flip_h: true,
+            ..Transform::default()
+        });
+        let (pixmap, _) = render(&canvas(2, 2, vec![layer]), &pixmaps);
+        assert_eq!(pixmap.pixel(0, 0), BLUE);
+        assert_eq!(pixmap.pixel(1, 0), RED);
+
+        let layer = transformed(Transform {
+            flip_v: true,
+            ..Transform::default()
+        });
+        let (pixmap, _) = render(&canvas(2, 2, vec![layer]), &pixmaps);
+        assert_eq!(pixmap.pixel(0, 0), [0, 0, 0, 255]);
+        assert_eq!(pixmap.pixel(0, 1), RED);
+    }
+
+    #[test]
+    fn transformed_bounds_and_hits() {
+        let layer = transformed(Transform {
+            dx: 2.0,
+            rotation: 45.0,
+            ..Transform::default()
+        });
+        let canvas = canvas(8, 8, vec![layer.clone()]);
+        let placement = layer_placement(&canvas, &layer, layer.get_selected_image().unwrap());
+
+        let (left, top, right, bottom) = placement.bounds();
+        let diagonal = 2.0 * std::f64::consts::SQRT_2;
+        assert!((right - left - diagonal).abs() < 1e-9);
+        assert!((bottom - top - diagonal).abs() < 1e-9);
+
+        // the corners of the unrotated rect are outside.
+        assert!(placement.contains(3.0, 1.0));
+        assert!(!placement.contains(2.1, 0.1));
+
+        let pixmaps = images(&[("a", quad())]);
+        assert_eq!(layer_at(&canvas, &pixmaps, 3.0, 1.0), Some(0));
+        assert_eq!(layer_at(&canvas, &pixmaps, 2.1, 0.1), None);
+    }
+
+    #[test]
+    fn size_of_the_layer_box() {
+        let layer = transformed(Transform {
+            dw: 4.0,
+            dh: 1.0,
+            ..Transform::default()
+        });
+        let mut stretched = layer.clone();
+        stretched.set_scale(ScaleMode::Stretch);
+        let canvas = canvas(8, 8, vec![]);
+
+        assert_eq!(layer_box(&canvas, &layer), (4.0, 1.0));
+        assert_eq!(layer_box(&canvas, &Layer::empty("auto")), (8.0, 8.0));
+        let placement =
+            layer_placement(&canvas, &stretched, stretched.get_selected_image().unwrap());
+        assert_eq!((placement.dw, placement.dh), (4.0, 1.0));
+    }
+
+    #[test]
+    fn tile_repeats_the_image() {
+        let canvas = canvas(4, 2, vec![image_layer("a", 2.0, 2.0, ScaleMode::Tile)]);
+        let (pixmap, _) = render(&canvas, &images(&[("a", quad())]));
+        assert_eq!(pixmap.pixel(2, 0), pixmap.pixel(0, 0));
+        assert_eq!(pixmap.pixel(3, 1), pixmap.pixel(1, 1));
+    }
+
+    #[test]
+    fn text_is_drawn_in_the_bundled_font() {
+        let text = Text {
+            content: String::from("Hi"),
+            ..Text::default()
+        };
+        let mut other = text.clone();
+        other.font_family = String::from("Inter");
+
+        let bundled = canvas(64, 64, vec![Layer::text("bundled", text)]);
+        let (pixmap, failed) = render(&bundled, &HashMap::new());
+        assert!(failed.is_empty());
+        assert!((0..64).any(|y| (0..64).any(|x| pixmap.pixel(x, y)[3] > 0)));
+
+        let other = canvas(64, 64, vec![Layer::text("other", other)]);
+        assert_eq!(
+            render(&other, &HashMap::new()).1,
+            vec![String::from("other")]
+        );
+    }
+}
@@ -0,0 +1,303 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-10 21:14:05
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-10 23:31:44
+ * @FilePath: /layer-painter/wasm/src/compositor/shape.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+//! The shape layers are drawn from the signed distances to their outlines,
+//! sampled for every pixel of the target, so they are sharp in any size.
+
+use crate::canvas::{parse_hex_color, BlendMode, Image, Mask, Shape, ShapeKind};
+use crate::compositor::render::draw_samples;
+use crate::compositor::{Pixmap, Placement, PIXMAP_SIZE_MAX};
+
+fn premultiply(color: [u8; 4]) -> [f32; 4] {
+    let a = color[3] as f32 / 255.0;
+    [
+        color[0] as f32 / 255.0 * a,
+        color[1] as f32 / 255.0 * a,
+        color[2] as f32 / 255.0 * a,
+        a,
+    ]
+}
+
+// the box of the outline, inside the box of the shape by half of the stroke.
+fn outline_box(shape: &Shape) -> (f64, f64, f64, f64) {
+    let inset = match shape.stroke {
+        Some(_) => (shape.stroke_width / 2.0).max(0.0),
+        None => 0.0,
+    };
+    let width = (shape.width - inset * 2.0).max(0.0);
+    let height = (shape.height - inset * 2.0).max(0.0);
+    (inset, inset, width, height)
+}
+
+fn segment_distance(x: f64, y: f64, from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (ex, ey) = (to.0 - from.0, to.1 - from.1);
+    let (wx, wy) = (x - from.0, y - from.1);
+    let length = ex * ex + ey * ey;
+    let t = if length > 0.0 {
+        ((wx * ex + wy * ey) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (wx - ex * t).hypot(wy - ey * t)
+}
+
+/// The signed distance from (`x`, `y`) in the box of `shape` to its outline,
+/// negative inside. A line has no inside.
+pub fn shape_distance(shape: &Shape, x: f64, y: f64) -> f64 {
+    let (left, top, width, height) = outline_box(shape);
+    let (half_width, half_height) = (width / 2.0, height / 2.0);
+    let (px, py) = (x - left - half_width, y - top - half_height);
+    let point = |(nx, ny): (f64, f64)| (left + nx * width, top + ny * height);
+
+    match shape.kind {
+        ShapeKind::Rectangle => {
+            let radius = shape
+                .corner_radius
+                .min(half_width)
+                .min(half_height)
+                .max(0.0);
+            let qx = px.abs() - half_width + radius;
+            let qy = py.abs() - half_height + radius;
+            qx.max(0.0).hypot(qy.max(0.0)) + qx.max(qy).min(0.0) - radius
+        }
+
+        ShapeKind::Ellipse => {
+            if half_width <= 0.0 || half_height <= 0.0 {
+                return px.abs().max(py.abs());
+            }
+
+            // an approximation, exact on the outline.
+            let k0 = (px / half_width).hypot(py / half_height);
+            let k1 = (px / (half_width * half_width)).hypot(py / (half_height * half_height));
+            if k1 > 0.0 {
+                k0 * (k0 - 1.0) / k1
+            } else {
+                -half_width.min(half_height)
+            }
+        }
+
+        ShapeKind::Line => {
+            let points = shape.kind_points();
+            segment_distance(x, y, point(points[0]), point(points[1]))
+        }
+
+        ShapeKind::Polygon => {
+            let points: Vec<(f64, f64)> = shape.kind_points().into_iter().map(point).collect();
+            let mut distance = f64::MAX;
+            let mut inside = false;
+            for (index, to) in points.iter().enumerate() {
+                let from = points[(index + points.len() - 1) % points.len()];
+                distance = distance.min(segment_distance(x, y, from, *to));
+
+                // the even-odd rule.
+                if (to.1 > y) != (from.1 > y)
+                    && x < (from.0 - to.0) * (y - to.1) / (from.1 - to.1) + to.0
+                {
+                    inside = !inside;
+                }
+            }
+
+            if inside {
+                -distance
+            } else {
+                distance
+            }
+        }
+    }
+}
+
+/// The premultiplied color of `shape` at (`x`, `y`) in its box,
+/// antialiased over a pixel of the size `pixel` in the box.
+pub fn shape_sample(shape: &Shape, x: f64, y: f64, pixel: f64) -> [f32; 4] {
+    let distance = shape_distance(shape, x, y);
+    let pixel = pixel.max(f64::EPSILON);
+    let coverage = |distance: f64| (0.5 - distance / pixel).clamp(0.0, 1.0) as f32;
+
+    let mut color = [0.0; 4];
+    if shape.kind != ShapeKind::Line {
+        if let Some(fill) = shape.fill.as_deref().and_then(parse_hex_color) {
+            let alpha = coverage(distance);
+            color = premultiply(fill).map(|c| c * alpha);
+        }
+    }
+
+    if let Some(stroke) = shape.stroke.as_deref().and_then(parse_hex_color) {
+        if shape.stroke_width > 0.0 {
+            let alpha = coverage(distance.abs() - shape.stroke_width / 2.0);
+            let stroke = premultiply(stroke).map(|c| c * alpha);
+            for (c, s) in color.iter_mut().zip(stroke) {
+                *c = s + *c * (1.0 - stroke[3]);
+            }
+        }
+    }
+
+    color
+}
+
+/// An image of the size of the box of `shape`, to place it like the images.
+pub fn shape_image(shape: &Shape) -> Image {
+    Image::new("", None, shape.width.max(0.0), shape.height.max(0.0))
+}
+
+/// Draw `shape` in its own size, to copy it as an image.
+pub fn shape_to_pixmap(shape: &Shape) -> Result<Pixmap, String> {
+    let width = shape.width.max(0.0).ceil() as usize;
+    let height = shape.height.max(0.0).ceil() as usize;
+    if width > PIXMAP_SIZE_MAX || height > PIXMAP_SIZE_MAX {
+        return Err(format!(
+            "the shape is {}x{}, larger than {}x{}",
+            width, height, PIXMAP_SIZE_MAX, PIXMAP_SIZE_MAX
+        ));
+    }
+
+    let mut pixmap = Pixmap::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            let color = shape_sample(shape, x as f64 + 0.5, y as f64 + 0.5, 1.0);
+            pixmap.set_premultiplied(x, y, color);
+        }
+    }
+    Ok(pixmap)
+}
+
+/// Draw `shape` into the destination rect of `target`, like `draw_pixmap`.
+pub fn draw_shape(
+    target: &mut Pixmap,
+    shape: &Shape,
+    placement: &Placement,
+    opacity: f64,
+    blend: BlendMode,
+    mask: Option<&Mask>,
+) {
+    let pixel = placement.source_pixel();
+    draw_samples(target, placement, opacity, blend, mask, |x, y| {
+        shape_sample(shape, x, y, pixel)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shape(kind: ShapeKind) -> Shape {
+        let mut shape = Shape::new(kind);
+        shape.width = 10.0;
+        shape.height = 10.0;
+        shape.fill = Some(String::from("#ff0000"));
+        shape.stroke = None;
+        shape
+    }
+
+    fn alpha(shape: &Shape, x: f64, y: f64) -> f32 {
+        shape_sample(shape, x, y, 1.0)[3]
+    }
+
+    #[test]
+    fn rectangle_coverage() {
+        let rect = shape(ShapeKind::Rectangle);
+        assert_eq!(shape_distance(&rect, 5.0, 5.0), -5.0);
+        assert_eq!(alpha(&rect, 5.0, 5.0), 1.0);
+        assert_eq!(alpha(&rect, 0.5, 5.0), 1.0);
+        // half covered on the edge.
+        assert_eq!(alpha(&rect, 0.0, 5.0), 0.5);
+        assert_eq!(alpha(&rect, -1.0, 5.0), 0.0);
+
+        let mut rounded = rect;
+        rounded.corner_radius = 4.0;
+        assert_eq!(alpha(&rounded, 0.5, 0.5), 0.0);
+        assert_eq!(alpha(&rounded, 5.0, 0.5), 1.0);
+    }
+
+    #[test]
+    fn ellipse_coverage() {
+        let mut ellipse = shape(ShapeKind::Ellipse);
+        ellipse.width = 20.0;
+        assert!((shape_distance(&ellipse, 20.0, 5.0)).abs() < 1e-9);
+        assert!((shape_distance(&ellipse, 10.0, 0.0)).abs() < 1e-9);
+        assert_eq!(alpha(&ellipse, 10.0, 5.0), 1.0);
+        assert_eq!(alpha(&ellipse, 1.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn line_is_only_stroked() {
+        let mut line = shape(ShapeKind::Line);
+        assert_eq!(alpha(&line, 5.0, 5.0), 0.0);
+
+        line.stroke = Some(String::from("#00ff00"));
+        line.stroke_width = 2.0;
+        assert_eq!(shape_sample(&line, 5.0, 5.0, 1.0), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(alpha(&line, 5.0, 7.0), 0.0);
+    }
+
+    #[test]
+    fn polygon_coverage() {
+        // the default triangle, pointing up.
+        let triangle = shape(ShapeKind::Polygon);
+        assert_eq!(alpha(&triangle, 5.0, 8.0), 1.0);
+        assert_eq!(alpha(&triangle, 1.0, 1.0), 0.0);
+        assert_eq!(alpha(&triangle, 9.0, 1.0), 0.0);
+        assert!(shape_distance(&triangle, 5.0, 8.0) < 0.0);
+        assert!(shape_distance(&triangle, 5.0, 11.0) > 0.0);
+    }
+
+    #[test]
+    fn stroke_over_fill() {
+        let mut rect = shape(ShapeKind::Rectangle);
+        rect.stroke = Some(String::from("#0000ff80"));
+        rect.stroke_width = 2.0;
+
+        // the stroke is centered on the outline, inside the box.
+        let edge = shape_sample(&rect, 1.5, 5.0, 1.0);
+        let blue = 128.0 / 255.0;
+        let expected = [1.0 - blue, 0.0, blue, 1.0];
+        for (c, e) in edge.iter().zip(expected.iter()) {
+            assert!((c - e).abs() < 1e-6, "{:?}", edge);
+        }
+        assert_eq!(shape_sample(&rect, 5.0, 5.0, 1.0), [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn coverage_is_sharp_at_any_scale() {
+        let rect = shape(ShapeKind::Rectangle);
+        let mut canvas = Pixmap::new(100, 100);
+        let placement = Placement {
+            sx: 0.0,
+            sy: 0.0,
+            sw: 10.0,
+            sh: 10.0,
+            dx: 0.0,
+            dy: 0.0,
+            dw: 100.0,
+            dh: 100.0,
+            rotation: 0.0,
+            flip_h: false,
+            flip_v: false,
+            anchor_x: 0.5,
+            anchor_y: 0.5,
+            tile: None,
+            smooth: true,
+        };
+        assert_eq!(placement.source_pixel(), 0.1);
+        draw_shape(&mut canvas, &rect, &placement, 1.0, BlendMode::Normal, None);
+        assert_eq!(canvas.pixel(0, 50), [255, 0, 0, 255]);
+        assert_eq!(canvas.pixel(99, 99), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn pixmap_of_the_box() {
+        let pixmap = shape_to_pixmap(&shape(ShapeKind::Ellipse)).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (10, 10));
+        assert_eq!(pixmap.pixel(5, 5), [255, 0, 0, 255]);
+        assert_eq!(pixmap.pixel(0, 0)[3], 0);
+
+        let mut huge = shape(ShapeKind::Rectangle);
+        huge.width = 1e9;
+        assert!(shape_to_pixmap(&huge).is_err());
+    }
+}
@@ -0,0 +1,854 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-05 20:24:51
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-05 23:48:16
+ * @FilePath: /layer-painter/wasm/src/compositor/sheet.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+//! Packing the frames of the timeline into one sprite sheet,
+//! with a TexturePacker json atlas of the frame rects.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::canvas::Canvas;
+use crate::compositor::{render_frames, render_onto, Pixmap, ANIMATED_FRAMES_MAX};
+
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+
+/// Larger sheets can't be loaded by most of the gpus.
+pub const SHEET_SIZE_MAX: usize = 16384;
+
+/// What the frames of the sheet are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SheetSource {
+    /// Every frame of the whole canvas.
+    #[default]
+    Composition,
+    /// The frames of every visible layer alone, at its place on the canvas.
+    Layers,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Packing {
+    /// Cells of the largest frame, row by row.
+    Grid,
+    /// The free rects of the sheet, placing every frame in the best fitting one.
+    #[default]
+    MaxRects,
+}
+
+/// The layout of the frames in the atlas json.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AtlasFormat {
+    /// `frames` is an object by the frame names.
+    #[default]
+    Hash,
+    /// `frames` is an array with a `filename` in every frame.
+    Array,
+}
+
+impl SheetSource {
+    pub const ALL: [SheetSource; 2] = [SheetSource::Composition, SheetSource::Layers];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SheetSource::Composition => "composition",
+            SheetSource::Layers => "layers",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        SheetSource::ALL
+            .iter()
+            .copied()
+            .find(|source| source.name() == name)
+    }
+}
+
+impl Packing {
+    pub const ALL: [Packing; 2] = [Packing::Grid, Packing::MaxRects];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Packing::Grid => "grid",
+            Packing::MaxRects => "max-rects",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Packing::ALL
+            .iter()
+            .copied()
+            .find(|packing| packing.name() == name)
+    }
+}
+
+impl AtlasFormat {
+    pub const ALL: [AtlasFormat; 2] = [AtlasFormat::Hash, AtlasFormat::Array];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AtlasFormat::Hash => "hash",
+            AtlasFormat::Array => "array",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        AtlasFormat::ALL
+            .iter()
+            .copied()
+            .find(|format| format.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SheetOptions {
+    pub source: SheetSource,
+    pub packing: Packing,
+    // between the frames and around them.
+    pub padding: usize,
+    pub power_of_two: bool,
+    // cut the transparent borders of the frames.
+    pub trim: bool,
+    pub format: AtlasFormat,
+}
+
+impl Default for SheetOptions {
+    fn default() -> Self {
+        SheetOptions {
+            source: SheetSource::Composition,
+            packing: Packing::MaxRects,
+            padding: 2,
+            power_of_two: false,
+            trim: true,
+            format: AtlasFormat::Hash,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AtlasRect {
+    pub x: usize,
+    pub y: usize,
+    pub w: usize,
+    pub h: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AtlasSize {
+    pub w: usize,
+    pub h: usize,
+}
+
+/// A frame in the atlas, the same fields as TexturePacker.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AtlasFrame {
+    #[serde(skip)]
+    pub filename: String,
+    /// The rect on the sheet.
+    pub frame: AtlasRect,
+    pub rotated: bool,
+    pub trimmed: bool,
+    /// The rect of the trimmed pixels in the untrimmed frame.
+    pub sprite_source_size: AtlasRect,
+    pub source_size: AtlasSize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AtlasMeta {
+    pub app: String,
+    pub version: String,
+    pub image: String,
+    pub format: String,
+    pub size: AtlasSize,
+    pub scale: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Atlas {
+    pub frames: Vec<AtlasFrame>,
+    pub meta: AtlasMeta,
+}
+
+// the frames as an object in their order.
+struct FramesByName<'a>(&'a [AtlasFrame]);
+
+impl Serialize for FramesByName<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for frame in self.0.iter() {
+            map.serialize_entry(&frame.filename, frame)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Serialize)]
+struct NamedFrame<'a> {
+    filename: &'a str,
+    #[serde(flatten)]
+    frame: &'a AtlasFrame,
+}
+
+#[derive(Serialize)]
+struct AtlasJson<'a, F: Serialize> {
+    frames: F,
+    meta: &'a AtlasMeta,
+}
+
+impl Atlas {
+    pub fn to_json(&self, format: AtlasFormat) -> Result<String, String> {
+        match format {
+            AtlasFormat::Hash => serde_json::to_string_pretty(&AtlasJson {
+                frames: FramesByName(&self.frames),
+                meta: &self.meta,
+            }),
+            AtlasFormat::Array => serde_json::to_string_pretty(&AtlasJson {
+                frames: self
+                    .frames
+                    .iter()
+                    .map(|frame| NamedFrame {
+                        filename: frame.filename.as_str(),
+                        frame,
+                    })
+                    .collect::<Vec<NamedFrame>>(),
+                meta: &self.meta,
+            }),
+        }
+        .map_err(|err| format!("{}", err))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteSheet {
+    pub pixmap: Pixmap,
+    pub atlas: Atlas,
+}
+
+// a rendered frame before packing.
+struct SheetFrame {
+    filename: String,
+    pixmap: Pixmap,
+    // the rect of `pixmap` in the untrimmed frame.
+    source: AtlasRect,
+    source_size: AtlasSize,
+}
+
+/// Render the frames of `options.source` and pack them in one sheet,
+/// `image` is the file name of the sheet in the atlas.
+/// Returns the sheet and the names of the layers which can't be rendered.
+pub fn render_sprite_sheet(
+    canvas: &Canvas,
+    images: &HashMap<String, Pixmap>,
+    options: &SheetOptions,
+    image: &str,
+) -> Result<(SpriteSheet, Vec<String>), String> {
+    let (frames, failed) = match options.source {
+        SheetSource::Composition => {
+            let (frames, failed) = render_frames(canvas, images, true)?;
+            let frames = frames
+                .into_iter()
+                .enumerate()
+                .map(|(index, pixmap)| (frame_name(canvas.name().as_str(), index), pixmap))
+                .collect();
+            (frames, failed)
+        }
+        SheetSource::Layers => render_layer_frames(canvas, images)?,
+    };
+    if frames.is_empty() {
+        return Err(String::from("there are no frames to pack"));
+    }
+
+    let frames: Vec<SheetFrame> = frames
+        .into_iter()
+        .map(|(filename, pixmap)| trim_frame(filename, pixmap, options.trim))
+        .collect();
+    let sizes: Vec<(usize, usize)> = frames
+        .iter()
+        .map(|frame| (frame.pixmap.width(), frame.pixmap.height()))
+        .collect();
+    let (size, positions) = pack(&sizes, options)?;
+
+    let mut pixmap = Pixmap::new(size.w, size.h);
+    let mut atlas_frames = Vec::with_capacity(frames.len());
+    for (frame, (x, y)) in frames.into_iter().zip(positions) {
+        pixmap.copy_from(&frame.pixmap, x, y);
+        atlas_frames.push(AtlasFrame {
+            filename: frame.filename,
+            frame: AtlasRect {
+                x,
+                y,
+                w: frame.pixmap.width(),
+                h: frame.pixmap.height(),
+            },
+            rotated: false,
+            trimmed: frame.source.w != frame.source_size.w || frame.source.h != frame.source_size.h,
+            sprite_source_size: frame.source,
+            source_size: frame.source_size,
+        });
+    }
+
+    let atlas = Atlas {
+        frames: atlas_frames,
+        meta: AtlasMeta {
+            app: String::from("layer-painter"),
+            version: String::from("1.0"),
+            image: String::from(image),
+            format: String::from("RGBA8888"),
+            size,
+            scale: String::from("1"),
+        },
+    };
+
+    Ok((SpriteSheet { pixmap, atlas }, failed))
+}
+
+fn frame_name(name: &str, index: usize) -> String {
+    let name = if name.is_empty() { "frame" } else { name };
+    format!("{}_{:04}.png", name, index)
+}
+
+// the name and the pixels of a frame.
+type NamedPixmap = (String, Pixmap);
+
+// the frames of every visible layer with the others hidden,
+// an animated layer has the frames of its own cycle.
+fn render_layer_frames(
+    canvas: &Canvas,
+    images: &HashMap<String, Pixmap>,
+) -> Result<(Vec<NamedPixmap>, Vec<String>), String> {
+    let mut leaves = Vec::new();
+    // the depth of the hidden group being skipped.
+    let mut hidden: Option<usize> = None;
+    for (index, (depth, layer)) in canvas.walk_layers().into_iter().enumerate() {
+        match hidden {
+            Some(hidden_depth) if depth > hidden_depth => continue,
+            _ => hidden = None,
+        }
+
+        if !layer.show() {
+            hidden = Some(depth);
+        } else if !layer.is_group() {
+            leaves.push(index);
+        }
+    }
+
+    let mode = canvas.animation().mode();
+    let mut frames = Vec::new();
+    let mut failed: Vec<String> = Vec::new();
+    let mut used = HashSet::new();
+    for index in leaves.iter() {
+        let layer = canvas.get_layer(*index).unwrap();
+        let frames_len = if layer.animated() {
+            mode.cycle_len(layer.frame_offset(), layer.get_images().len())
+        } else {
+            1
+        };
+        if frames.len() + frames_len > ANIMATED_FRAMES_MAX {
+            return Err(format!(
+                "the sheet has more than {} frames",
+                ANIMATED_FRAMES_MAX
+            ));
+        }
+
+        // the same names for the layers with the same name.
+        let mut name = layer.name();
+        if !used.insert(name.clone()) {
+            name = format!("{}-{}", name, index);
+            used.insert(name.clone());
+        }
+
+        let mut alone = canvas.clone();
+        for other in leaves.iter().filter(|other| *other != index) {
+            if let Some(layer) = alone.get_mut_layer(*other) {
+                layer.set_show(false);
+            }
+        }
+
+        for frame in 0..frames_len {
+            alone.set_frame(frame);
+            let mut pixmap = Pixmap::new(canvas.width(), canvas.height());
+            for layer_name in render_onto(&mut pixmap, &alone, images) {
+                if !failed.contains(&layer_name) {
+                    failed.push(layer_name);
+                }
+            }
+            frames.push((frame_name(name.as_str(), frame), pixmap));
+        }
+    }
+
+    Ok((frames, failed))
+}
+
+// cut the transparent borders, keeping 1 x 1 of an empty frame.
+fn trim_frame(filename: String, pixmap: Pixmap, trim: bool) -> SheetFrame {
+    let source_size = AtlasSize {
+        w: pixmap.width(),
+        h: pixmap.height(),
+    };
+    let full = AtlasRect {
+        x: 0,
+        y: 0,
+        w: pixmap.width(),
+        h: pixmap.height(),
+    };
+    if !trim {
+        return SheetFrame {
+            filename,
+            pixmap,
+            source: full,
+            source_size,
+        };
+    }
+
+    let mut bounds: Option<(usize, usize, usize, usize)> = None;
+    for y in 0..pixmap.height() {
+        for x in 0..pixmap.width() {
+            if pixmap.pixel(x, y)[3] == 0 {
+                continue;
+            }
+            bounds = Some(match bounds {
+                Some((left, top, right, bottom)) => {
+                    (left.min(x), top.min(y), right.max(x + 1), bottom.max(y + 1))
+                }
+                None => (x, y, x + 1, y + 1),
+            });
+        }
+    }
+
+    let source = match bounds {
+        Some((left, top, right, bottom)) => AtlasRect {
+            x: left,
+            y: top,
+            w: right - left,
+            h: bottom - top,
+        },
+        None => AtlasRect {
+            x: 0,
+            y: 0,
+            w: 1.min(full.w),
+            h: 1.min(full.h),
+        },
+    };
+    if source == full {
+        return SheetFrame {
+            filename,
+            pixmap,
+            source,
+            source_size,
+        };
+    }
+
+    SheetFrame {
+        filename,
+        pixmap: pixmap.crop(source.x, source.y, source.w, source.h),
+        source,
+        source_size,
+    }
+}
+
+/// The size of the sheet and the top left of every one of `sizes` in it.
+pub fn pack(
+    sizes: &[(usize, usize)],
+    options: &SheetOptions,
+) -> Result<(AtlasSize, Vec<(usize, usize)>), String> {
+    let padding = options.padding;
+    let round = |size: usize| {
+        if options.power_of_two {
+            size.max(1).next_power_of_two()
+        } else {
+            size.max(1)
+        }
+    };
+    let too_large = |width: usize, height: usize| {
+        Err(format!(
+            "the sheet would be {} x {}, larger than {}",
+            width, height, SHEET_SIZE_MAX
+        ))
+    };
+
+    // every frame takes its size with the padding on the right and the bottom,
+    // in the sheet without the padding on the left and the top.
+    let padded: Vec<(usize, usize)> = sizes
+        .iter()
+        .map(|(width, height)| (width + padding, height + padding))
+        .collect();
+    let max_width = padded.iter().map(|(width, _)| *width).max().unwrap_or(0);
+    let max_height = padded.iter().map(|(_, height)| *height).max().unwrap_or(0);
+
+    match options.packing {
+        Packing::Grid => {
+            let count = padded.len().max(1);
+            // about as wide as high.
+            let columns = ((count as f64 * max_height as f64 / max_width.max(1) as f64)
+                .sqrt()
+                .ceil() as usize)
+                .clamp(1, count);
+            let rows = count.div_ceil(columns);
+            let width = round(columns * max_width + padding);
+            let height = round(rows * max_height + padding);
+            if width > SHEET_SIZE_MAX || height > SHEET_SIZE_MAX {
+                return too_large(width, height);
+            }
+
+            let positions = (0..padded.len())
+                .map(|index| {
+                    (
+                        padding + index % columns * max_width,
+                        padding + index / columns * max_height,
+                    )
+                })
+                .collect();
+            Ok((
+                AtlasSize {
+                    w: width,
+                    h: height,
+                },
+                positions,
+            ))
+        }
+
+        Packing::MaxRects => {
+            let area: usize = padded.iter().map(|(width, height)| width * height).sum();
+            let mut width = round(max_width.max((area as f64).sqrt().ceil() as usize) + padding);
+            let mut height = round(max_height.max(area / width.max(1)) + padding);
+
+            loop {
+                if width > SHEET_SIZE_MAX || height > SHEET_SIZE_MAX {
+                    return too_large(width, height);
+                }
+                if let Some(positions) = max_rects(&padded, width - padding, height - padding) {
+                    let positions = positions
+                        .into_iter()
+                        .map(|(x, y)| (x + padding, y + padding))
+                        .collect();
+                    return Ok((
+                        AtlasSize {
+                            w: width,
+                            h: height,
+                        },
+                        positions,
+                    ));
+                }
+
+                // grow the shorter side.
+                if height <= width {
+                    height = round(height + (height / 8).max(1));
+                } else {
+                    width = round(width + (width / 8).max(1));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rect {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+impl Rect {
+    fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.w <= self.x + self.w
+            && other.y + other.h <= self.y + self.h
+    }
+
+    fn intersects(&self, other: &Rect) -> bool {
+        other.x < self.x + self.w
+            && self.x < other.x + other.w
+            && other.y < self.y + self.h
+            && self.y < other.y + other.h
+    }
+}
+
+// places the larger ones first, at the free rect which leaves the shortest side.
+fn max_rects(sizes: &[(usize, usize)], width: usize, height: usize) -> Option<Vec<(usize, usize)>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|index| {
+        let (width, height) = sizes[*index];
+        (
+            std::cmp::Reverse(width.max(height)),
+            std::cmp::Reverse(width.min(height)),
+        )
+    });
+
+    let mut free = vec![Rect {
+        x: 0,
+        y: 0,
+        w: width,
+        h: height,
+    }];
+    let mut positions = vec![(0, 0); sizes.len()];
+
+    for index in order {
+        let (width, height) = sizes[index];
+        let placed = free
+            .iter()
+            .filter(|rect| rect.w >= width && rect.h >= height)
+            .min_by_key(|rect| {
+                let (short, long) = (rect.w - width, rect.h - height);
+                (short.min(long), short.max(long), rect.y, rect.x)
+            })
+            .map(|rect| Rect {
+                x: rect.x,
+                y: rect.y,
+                w: width,
+                h: height,
+            })?;
+        positions[index] = (placed.x, placed.y);
+
+        let mut next = Vec::with_capacity(free.len() + 4);
+        for rect in free.iter() {
+            if !rect.intersects(&placed) {
+                next.push(*rect);
+                continue;
+            }
+
+            // the parts of the free rect around the placed one.
+            if placed.x > rect.x {
+                next.push(Rect {
+                    w: placed.x - rect.x,
+                    ..*rect
+                });
+            }
+            if placed.x + placed.w < rect.x + rect.w {
+                next.push(Rect {
+                    x: placed.x + placed.w,
+                    w: rect.x + rect.w - placed.x - placed.w,
+                    ..*rect
+                });
+            }
+            if placed.y > rect.y {
+                next.push(Rect {
+                    h: placed.y - rect.y,
+                    ..*rect
+                });
+            }
+            if placed.y + placed.h < rect.y + rect.h {
+                next.push(Rect {
+                    y: placed.y + placed.h,
+                    h: rect.y + rect.h - placed.y - placed.h,
+                    ..*rect
+                });
+            }
+        }
+
+        // the rects inside another one are not needed.
+        free = next
+            .iter()
+            .enumerate()
+            .filter(|(index, rect)| {
+                !next.iter().enumerate().any(|(other_index, other)| {
+                    other_index != *index
+                        && other.contains(rect)
+                        && (other != *rect || other_index < *index)
+                })
+            })
+            .map(|(_, rect)| *rect)
+            .collect();
+    }
+
+    Some(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deterministic sizes of 1 ~ `max`.
+    fn sizes(count: usize, max: usize) -> Vec<(usize, usize)> {
+        let mut seed = 7u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as usize % max + 1
+        };
+        (0..count).map(|_| (next(), next())).collect()
+    }
+
+    fn assert_packed(sizes: &[(usize, usize)], options: &SheetOptions) -> AtlasSize {
+        let (size, positions) = pack(sizes, options).unwrap();
+        let padding = options.padding;
+        let rects: Vec<Rect> = sizes
+            .iter()
+            .zip(positions.iter())
+            .map(|((w, h), (x, y))| Rect {
+                x: *x,
+                y: *y,
+                w: *w,
+                h: *h,
+            })
+            .collect();
+
+        for (index, rect) in rects.iter().enumerate() {
+            assert!(rect.x >= padding && rect.y >= padding, "{:?}", rect);
+            assert!(
+                rect.x + rect.w + padding <= size.w,
+                "{:?} in {:?}",
+                rect,
+                size
+            );
+            assert!(
+                rect.y + rect.h + padding <= size.h,
+                "{:?} in {:?}",
+                rect,
+                size
+            );
+
+            // with the padding between them.
+            let padded = Rect {
+                w: rect.w + padding,
+                h: rect.h + padding,
+                ..*rect
+            };
+            for other in rects[index + 1..].iter() {
+                let other = Rect {
+                    w: other.w + padding,
+                    h: other.h + padding,
+                    ..*other
+                };
+                assert!(
+                    !padded.intersects(&other),
+                    "{:?} overlaps {:?}",
+                    padded,
+                    other
+                );
+            }
+        }
+
+        size
+    }
+
+    #[test]
+    fn max_rects_never_overlap() {
+        for (count, max, padding) in [(1, 10, 0), (30, 40, 0), (100, 64, 2), (200, 17, 1)] {
+            let options = SheetOptions {
+                padding,
+                ..SheetOptions::default()
+            };
+            assert_packed(&sizes(count, max), &options);
+        }
+        assert_packed(&[(10, 10); 16], &SheetOptions::default());
+    }
+
+    #[test]
+    fn grid_cells_of_the_largest_frame() {
+        let options = SheetOptions {
+            packing: Packing::Grid,
+            padding: 1,
+            ..SheetOptions::default()
+        };
+        let sizes = sizes(10, 20);
+        assert_packed(&sizes, &options);
+
+        let (size, positions) = pack(&[(4, 4); 4], &options).unwrap();
+        assert_eq!(positions, vec![(1, 1), (6, 1), (1, 6), (6, 6)]);
+        assert_eq!((size.w, size.h), (11, 11));
+    }
+
+    #[test]
+    fn power_of_two_sheets() {
+        let options = SheetOptions {
+            power_of_two: true,
+            ..SheetOptions::default()
+        };
+        let size = assert_packed(&sizes(50, 30), &options);
+        assert!(size.w.is_power_of_two() && size.h.is_power_of_two());
+    }
+
+    #[test]
+    fn too_large_sheets_fail() {
+        assert!(pack(&[(SHEET_SIZE_MAX + 1, 1)], &SheetOptions::default()).is_err());
+    }
+
+    #[test]
+    fn trim_transparent_borders() {
+        let mut pixmap = Pixmap::new(4, 3);
+        pixmap.set_pixel(1, 1, [255, 0, 0, 255]);
+        pixmap.set_pixel(2, 1, [255, 0, 0, 255]);
+
+        let frame = trim_frame(String::from("a"), pixmap.clone(), true);
+        assert_eq!(
+            frame.source,
+            AtlasRect {
+                x: 1,
+                y: 1,
+                w: 2,
+                h: 1
+            }
+        );
+        assert_eq!((frame.pixmap.width(), frame.pixmap.height()), (2, 1));
+        assert_eq!(frame.source_size, AtlasSize { w: 4, h: 3 });
+
+        let frame = trim_frame(String::from("a"), pixmap, false);
+        assert_eq!(
+            frame.source,
+            AtlasRect {
+                x: 0,
+                y: 0,
+                w: 4,
+                h: 3
+            }
+        );
+
+        let frame = trim_frame(String::from("empty"), Pixmap::new(4, 3), true);
+        assert_eq!((frame.pixmap.width(), frame.pixmap.height()), (1, 1));
+    }
+
+    #[test]
+    fn atlas_json_layouts() {
+        let frame = AtlasFrame {
+            filename: String::from("a-0"),
+            frame: AtlasRect {
+                x: 1,
+                y: 2,
+                w: 3,
+                h: 4,
+            },
+            rotated: false,
+            trimmed: true,
+            sprite_source_size: AtlasRect {
+                x: 0,
+                y: 0,
+                w: 3,
+                h: 4,
+            },
+            source_size: AtlasSize { w: 5, h: 6 },
+        };
+        let atlas = Atlas {
+            frames: vec![frame],
+            meta: AtlasMeta {
+                app: String::from("layer-painter"),
+                version: String::from("1.0"),
+                image: String::from("a.png"),
+                format: String::from("RGBA8888"),
+                size: AtlasSize { w: 8, h: 8 },
+                scale: String::from("1"),
+            },
+        };
+
+        let json = |format| -> serde_json::Value {
+            serde_json::from_str(&atlas.to_json(format).unwrap()).unwrap()
+        };
+
+        let hash = json(AtlasFormat::Hash);
+        assert_eq!(hash["frames"]["a-0"]["frame"]["w"], 3);
+        assert_eq!(hash["frames"]["a-0"]["spriteSourceSize"]["h"], 4);
+        assert_eq!(hash["meta"]["size"]["w"], 8);
+
+        let array = json(AtlasFormat::Array);
+        assert_eq!(array["frames"][0]["filename"], "a-0");
+        assert_eq!(array["frames"][0]["sourceSize"]["h"], 6);
+    }
+}
@@ -0,0 +1,277 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-12 20:41:17
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-12 23:05:38
+ * @FilePath: /layer-painter/wasm/src/compositor/text.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+//! The text layers in the bundled font are rasterized here,
+//! so they are drawn the same in the app and by the command-line renderer.
+//! The other fonts can only be drawn by the browser.
+
+use ab_glyph::{point, Font, FontRef, GlyphId, PxScale, ScaleFont};
+
+use crate::canvas::{parse_hex_color, Text, TextAlign};
+use crate::compositor::{Pixmap, PIXMAP_SIZE_MAX};
+
+const SANS: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+const SANS_BOLD: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf");
+
+/// The css families drawn with the bundled DejaVu Sans, in lowercase.
+pub const BUNDLED_FAMILIES: [&str; 2] = ["sans-serif", "dejavu sans"];
+
+/// Whether the first family of the css family list of `text` is the bundled font.
+pub fn has_bundled_font(text: &Text) -> bool {
+    let family = text
+        .font_family
+        .split(',')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_lowercase();
+    BUNDLED_FAMILIES.contains(&family.as_str())
+}
+
+fn font_of(text: &Text) -> Result<FontRef<'static>, String> {
+    if !has_bundled_font(text) {
+        return Err(format!(
+            "the font `{}` isn't bundled, only sans-serif can be drawn",
+            text.font_family
+        ));
+    }
+
+    // the bold face, like the browsers pick it.
+    let data = if text.font_weight >= 600 {
+        SANS_BOLD
+    } else {
+        SANS
+    };
+    FontRef::try_from_slice(data).map_err(|err| format!("{}", err))
+}
+
+// the css font size is the size of the em square.
+fn scale_of<F: Font>(text: &Text, font: &F) -> PxScale {
+    let units_per_em = font.units_per_em().unwrap_or(1000.0);
+    PxScale::from(text.font_size as f32 * font.height_unscaled() / units_per_em)
+}
+
+// the glyphs of every line and their x, with the width of the line.
+struct Layout {
+    lines: Vec<(Vec<(GlyphId, f32)>, f32)>,
+    box_width: f32,
+    width: usize,
+    height: usize,
+}
+
+fn layout<F: Font>(text: &Text, font: &F) -> Result<Layout, String> {
+    let font = font.as_scaled(scale_of(text, font));
+
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut glyphs = Vec::new();
+        let mut x = 0.0;
+        let mut previous: Option<GlyphId> = None;
+        for character in line.chars() {
+            let id = font.glyph_id(character);
+            if let Some(previous) = previous {
+                // the letters are spaced without kerning, like every one is drawn alone.
+                x += if text.letter_spacing == 0.0 {
+                    font.kern(previous, id)
+                } else {
+                    text.letter_spacing as f32
+                };
+            }
+            glyphs.push((id, x));
+            x += font.h_advance(id);
+            previous = Some(id);
+        }
+        lines.push((glyphs, x.max(0.0)));
+    }
+
+    let box_width = lines
+        .iter()
+        .fold(0.0_f32, |width, (_, line_width)| width.max(*line_width));
+    let width = (box_width.ceil() as usize).max(1);
+    let height = ((lines.len() as f64 * text.line_pixels()).ceil() as usize).max(1);
+    if width > PIXMAP_SIZE_MAX || height > PIXMAP_SIZE_MAX {
+        return Err(format!(
+            "the text is {}x{}, larger than {}x{}",
+            width, height, PIXMAP_SIZE_MAX, PIXMAP_SIZE_MAX
+        ));
+    }
+
+    Ok(Layout {
+        lines,
+        box_width,
+        width,
+        height,
+    })
+}
+
+/// The size of the box of the lines of `text` in the bundled font.
+pub fn text_size(text: &Text) -> Result<(usize, usize), String> {
+    let layout = layout(text, &font_of(text)?)?;
+    Ok((layout.width, layout.height))
+}
+
+/// Draw the lines of `text` in the bundled font in the box of the longest line,
+/// every line is vertically centered in its line height.
+pub fn text_to_pixmap(text: &Text) -> Result<Pixmap, String> {
+    let font = font_of(text)?;
+    let color = parse_hex_color(text.color.as_str())
+        .ok_or_else(|| format!("the color `{}` isn't a hex color", text.color))?;
+    let layout = layout(text, &font)?;
+
+    let scale = scale_of(text, &font);
+    let scaled = font.as_scaled(scale);
+    let line_pixels = text.line_pixels() as f32;
+
+    // the coverage of every pixel, the glyphs may overlap.
+    let mut coverage = vec![0.0_f32; layout.width * layout.height];
+    for (index, (glyphs, line_width)) in layout.lines.iter().enumerate() {
+        let left = match text.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => (layout.box_width - line_width) / 2.0,
+            TextAlign::Right => layout.box_width - line_width,
+        };
+        let baseline =
+            (index as f32 + 0.5) * line_pixels + (scaled.ascent() + scaled.descent()) / 2.0;
+
+        for (id, x) in glyphs.iter() {
+            let glyph = id.with_scale_and_position(scale, point(left + x, baseline));
+            let outlined = match font.outline_glyph(glyph) {
+                Some(outlined) => outlined,
+                None => continue,
+            };
+
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, c| {
+                let x = bounds.min.x as i64 + gx as i64;
+                let y = bounds.min.y as i64 + gy as i64;
+                if x < 0 || y < 0 || x >= layout.width as i64 || y >= layout.height as i64 {
+                    return;
+                }
+                let covered = &mut coverage[y as usize * layout.width + x as usize];
+                *covered = (*covered + c).min(1.0);
+            });
+        }
+    }
+
+    let mut pixmap = Pixmap::new(layout.width, layout.height);
+    let alpha = color[3] as f32 / 255.0;
+    for y in 0..layout.height {
+        for x in 0..layout.width {
+            let c = coverage[y * layout.width + x];
+            if c > 0.0 {
+                let a = (alpha * c * 255.0).round() as u8;
+                pixmap.set_pixel(x, y, [color[0], color[1], color[2], a]);
+            }
+        }
+    }
+
+    Ok(pixmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(content: &str) -> Text {
+        Text {
+            content: String::from(content),
+            ..Text::default()
+        }
+    }
+
+    #[test]
+    fn bundled_families() {
+        let mut text = text("a");
+        assert!(has_bundled_font(&text));
+        text.font_family = String::from("\"DejaVu Sans\", serif");
+        assert!(has_bundled_font(&text));
+        text.font_family = String::from("Inter, sans-serif");
+        assert!(!has_bundled_font(&text));
+        assert!(text_to_pixmap(&text).is_err());
+    }
+
+    #[test]
+    fn box_of_the_lines() {
+        let one = text("Hello");
+        let (width, height) = text_size(&one).unwrap();
+        // 1.2 x 32px.
+        assert_eq!(height, 39);
+        assert!(width > 60 && width < 100, "{}", width);
+
+        let two = text("Hello\nHello");
+        assert_eq!(text_size(&two).unwrap(), (width, 77));
+
+        let mut spaced = text("Hello");
+        spaced.letter_spacing = 10.0;
+        assert!(text_size(&spaced).unwrap().0 >= width + 40);
+
+        assert_eq!(text_size(&text("")).unwrap(), (1, 1));
+    }
+
+    #[test]
+    fn glyphs_are_drawn_in_the_color() {
+        let mut text = text("I");
+        text.color = String::from("#ff0000");
+        let pixmap = text_to_pixmap(&text).unwrap();
+
+        let pixels: Vec<[u8; 4]> = (0..pixmap.height())
+            .flat_map(|y| (0..pixmap.width()).map(move |x| (x, y)))
+            .map(|(x, y)| pixmap.pixel(x, y))
+            .collect();
+        assert!(pixels.contains(&[255, 0, 0, 255]));
+        assert!(pixels
+            .iter()
+            .all(|pixel| pixel[3] == 0 || pixel[..3] == [255, 0, 0]));
+        // the line is centered, nothing on the first and the last rows.
+        assert!((0..pixmap.width()).all(|x| pixmap.pixel(x, 0)[3] == 0));
+        assert!((0..pixmap.width()).all(|x| pixmap.pixel(x, pixmap.height() - 1)[3] == 0));
+    }
+
+    #[test]
+    fn alignment_of_shorter_lines() {
+        let column = |align: TextAlign| {
+            let mut text = text("IIIIIIII\nI");
+            text.align = align;
+            let pixmap = text_to_pixmap(&text).unwrap();
+            // the columns covered by the second line.
+            let y = pixmap.height() * 3 / 4;
+            let xs: Vec<usize> = (0..pixmap.width())
+                .filter(|x| pixmap.pixel(*x, y)[3] > 128)
+                .collect();
+            (xs[0], pixmap.width())
+        };
+
+        let (left, _) = column(TextAlign::Left);
+        let (center, width) = column(TextAlign::Center);
+        let (right, _) = column(TextAlign::Right);
+        assert!(left < width / 4);
+        assert!(center > width / 3 && center < width * 2 / 3);
+        assert!(right > width * 3 / 4);
+    }
+
+    #[test]
+    fn bold_and_invalid_colors() {
+        let regular = text("Hello");
+        let mut bold = text("Hello");
+        bold.font_weight = 700;
+        assert!(text_size(&bold).unwrap().0 > text_size(&regular).unwrap().0);
+
+        let mut named = text("Hello");
+        named.color = String::from("red");
+        assert!(text_to_pixmap(&named).is_err());
+    }
+
+    #[test]
+    fn huge_texts_fail() {
+        let mut huge = text("Hello");
+        huge.font_size = 100000.0;
+        assert!(text_size(&huge).is_err());
+    }
+}
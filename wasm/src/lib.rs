@@ -0,0 +1,13 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-19 15:20:44
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-19 15:31:02
+ * @FilePath: /layer-painter/wasm/src/lib.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+// The document model and the compositor don't need a browser,
+// they are shared with the command-line renderer in `cli`.
+pub mod canvas;
+pub mod compositor;
@@ -8,10 +8,10 @@
  */
 
 mod components;
-mod canvas;
 mod utils;
 
 use components::App;
+use layer_painter_wasm::{canvas, compositor};
 
 fn main() {
     wasm_logger::init(wasm_logger::Config::default());
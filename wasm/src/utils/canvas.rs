@@ -9,26 +9,53 @@
 
 use crate::canvas::*;
 
+// the size of a new layer is auto, so it fits the canvas after resizing.
+
 pub fn canvas_append_new_fit_layer(canvas: &mut Canvas, name: &str) {
-    let mut layer = Layer::empty(name);
-    layer.set_dw(canvas.width() as f64);
-    layer.set_dh(canvas.height() as f64);
+    let layer = Layer::empty(name);
     canvas.append_layer(layer);
 }
 
 pub fn canvas_insert_new_fit_layer(canvas: &mut Canvas, index: usize, name: &str) {
-    let mut layer = Layer::empty(name);
-    layer.set_dw(canvas.width() as f64);
-    layer.set_dh(canvas.height() as f64);
+    let layer = Layer::empty(name);
     canvas.insert_layer(index, layer);
 }
 
 pub fn canvas_duplicate_layer(canvas: &mut Canvas, layer_index: usize) {
-    let layers = canvas.get_mut_layers();
-    let mut layer = layers.get(layer_index).unwrap().clone();
+    let mut layer = canvas.get_layer(layer_index).unwrap().clone();
     layer.set_name(format!("{}-{}", layer.name(), "duplicate").as_str());
 
-    layers.insert(layer_index, layer);
+    canvas.insert_layer(layer_index, layer);
+}
+
+/// The layers to list in the layer menu, from the top to the bottom,
+/// with their index and depth in the layer tree.
+/// The layers of a collapsed group are left out.
+pub fn canvas_display_layers(canvas: &Canvas) -> Vec<(usize, usize, &Layer)> {
+    fn list<'a>(
+        layers: &'a [Layer],
+        first_index: usize,
+        depth: usize,
+        result: &mut Vec<(usize, usize, &'a Layer)>,
+    ) {
+        let mut indexes = Vec::new();
+        let mut index = first_index;
+        for layer in layers.iter() {
+            indexes.push(index);
+            index += layer.get_tree_len();
+        }
+
+        for (layer, index) in layers.iter().zip(indexes).rev() {
+            result.push((index, depth, layer));
+            if layer.is_group() && !layer.collapsed() {
+                list(layer.get_layers(), index + 1, depth + 1, result);
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    list(canvas.get_layers(), 0, 0, &mut result);
+    result
 }
 
 pub fn move_image_to_other_layer(
@@ -37,39 +64,67 @@ pub fn move_image_to_other_layer(
     dst_layer_index: usize,
 ) {
     let src_layer = canvas.get_mut_layer(src_layer_index).unwrap();
-    let dst_layer_index = dst_layer_index;
 
     let image = src_layer.remove_selected_image();
 
-    match image {
-        Ok(image) => {
-            if src_layer.images().len() == 0 {
-                canvas.delete_layer(src_layer_index);
+    if let Ok(image) = image {
+        let mut dst_layer_index = dst_layer_index;
+        if src_layer.images().is_empty() {
+            canvas.delete_layer(src_layer_index);
+            if dst_layer_index > src_layer_index {
+                dst_layer_index -= 1;
             }
-
-            let dst_layer = canvas.get_mut_layer(dst_layer_index).unwrap();
-            dst_layer.append_image(image);
-            dst_layer.set_selected(dst_layer.images().len() - 1);
         }
 
-        Err(_) => {}
+        let dst_layer = canvas.get_mut_layer(dst_layer_index).unwrap();
+        dst_layer.append_image(image);
+        dst_layer.set_selected(dst_layer.images().len() - 1);
     }
 }
 
 pub fn copy_image_data_from_canvas(canvas: &mut Canvas, other_canvas: Canvas) {
-    for layer in canvas.get_mut_layers().iter_mut() {
-        for other_layer in other_canvas.layers().iter() {
+    let other_layers: Vec<&Layer> = other_canvas
+        .walk_layers()
+        .into_iter()
+        .map(|(_, layer)| layer)
+        .collect();
+
+    canvas.for_each_layer_mut(|layer| {
+        for other_layer in other_layers.iter() {
             for other_image in other_layer.images().iter() {
                 for image in layer.get_mut_images().iter_mut() {
                     if image.name().as_str() == other_image.name().as_str() {
-                        let data = &other_image.data();
-                        match data {
-                            Some(data) => image.set_data(data),
-                            None => {}
+                        if let Some(data) = &other_image.data() {
+                            image.set_data(data);
+                        }
+                        if let Some(bytes) = other_image.bytes() {
+                            image.set_bytes(bytes);
                         }
                     }
                 }
             }
         }
-    }
+    });
+}
+
+/// `count` image names `<stem>-<n>.png` which are not used in `canvas`,
+/// the pixmaps are found by the image names.
+pub fn canvas_new_image_names(canvas: &Canvas, stem: &str, count: usize) -> Vec<String> {
+    let used: Vec<String> = canvas
+        .walk_layers()
+        .into_iter()
+        .flat_map(|(_, layer)| {
+            layer
+                .images()
+                .iter()
+                .map(|image| image.name())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    (1..)
+        .map(|n| format!("{}-{}.png", stem, n))
+        .filter(|name| !used.contains(name))
+        .take(count)
+        .collect()
 }
@@ -0,0 +1,74 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-08 20:42:17
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-08 22:15:03
+ * @FilePath: /layer-painter/wasm/src/utils/clipboard.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use crate::utils::bytes_blob;
+
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::ClipboardEvent;
+
+/// The images in the clipboard of a paste event.
+pub fn pasted_images(event: &ClipboardEvent) -> Vec<gloo_file::File> {
+    let files = match event.clipboard_data().and_then(|data| data.files()) {
+        Some(files) => files,
+        None => return Vec::new(),
+    };
+
+    (0..files.length())
+        .filter_map(|index| files.get(index))
+        .map(gloo_file::File::from)
+        // screenshots are pasted as `image.png`.
+        .filter(|file| file.raw_mime_type() == "image/png")
+        .collect()
+}
+
+/// Whether some text of the page is selected, which is copied instead of the images.
+pub fn has_text_selection() -> bool {
+    match gloo_utils::window().get_selection() {
+        Ok(Some(selection)) => !selection.is_collapsed(),
+        _ => false,
+    }
+}
+
+fn js_error(err: JsValue) -> String {
+    err.as_string().unwrap_or_else(|| format!("{:#?}", err))
+}
+
+/// Write a png to the clipboard.
+// `ClipboardItem` is behind the unstable apis of web-sys, so it goes through js.
+pub async fn write_clipboard_png(bytes: &[u8]) -> Result<(), String> {
+    let window: JsValue = gloo_utils::window().into();
+    let navigator =
+        js_sys::Reflect::get(&window, &JsValue::from_str("navigator")).map_err(js_error)?;
+    let clipboard =
+        js_sys::Reflect::get(&navigator, &JsValue::from_str("clipboard")).map_err(js_error)?;
+    let item_class = js_sys::Reflect::get(&window, &JsValue::from_str("ClipboardItem"))
+        .map_err(js_error)?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| String::from("copying images isn't supported here"))?;
+    let write = js_sys::Reflect::get(&clipboard, &JsValue::from_str("write"))
+        .map_err(js_error)?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| String::from("copying images isn't supported here"))?;
+
+    let blob = bytes_blob(bytes, "image/png")?;
+    let data = js_sys::Object::new();
+    js_sys::Reflect::set(&data, &JsValue::from_str("image/png"), &blob).map_err(js_error)?;
+    let item =
+        js_sys::Reflect::construct(&item_class, &js_sys::Array::of1(&data)).map_err(js_error)?;
+
+    let promise = write
+        .call1(&clipboard, &js_sys::Array::of1(&item))
+        .map_err(js_error)?
+        .dyn_into::<js_sys::Promise>()
+        .map_err(|_| String::from("writing the clipboard doesn't return a promise"))?;
+    JsFuture::from(promise).await.map_err(js_error)?;
+
+    Ok(())
+}
@@ -0,0 +1,245 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-27 14:02:36
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-27 19:31:08
+ * @FilePath: /layer-painter/wasm/src/utils/crop.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use crate::canvas::Image;
+
+// half of the size of a handle on the canvas.
+const HANDLE_RADIUS: f64 = 6.0;
+
+/// A source rect in the pixels of the whole image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+impl CropRect {
+    pub fn of_image(image: &Image) -> Self {
+        Self {
+            x: image.sx(),
+            y: image.sy(),
+            w: image.sw(),
+            h: image.sh(),
+        }
+    }
+
+    pub fn apply_to(&self, image: &mut Image) {
+        image.set_sx(self.x);
+        image.set_sy(self.y);
+        image.set_sw(self.w);
+        image.set_sh(self.h);
+    }
+
+    /// Round to whole pixels and keep it inside the image, at least 1px.
+    pub fn clamp(&self, image_width: f64, image_height: f64) -> Self {
+        let w = self.w.round().clamp(1.0, image_width.max(1.0));
+        let h = self.h.round().clamp(1.0, image_height.max(1.0));
+
+        Self {
+            x: self.x.round().clamp(0.0, (image_width - w).max(0.0)),
+            y: self.y.round().clamp(0.0, (image_height - h).max(0.0)),
+            w,
+            h,
+        }
+    }
+}
+
+/// What a drag in the crop editor changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CropHandle {
+    Move,
+    N,
+    S,
+    E,
+    W,
+    NE,
+    NW,
+    SE,
+    SW,
+}
+
+impl CropHandle {
+    // which edges the handle moves, as (left, top, right, bottom).
+    fn edges(&self) -> (bool, bool, bool, bool) {
+        match self {
+            CropHandle::Move => (true, true, true, true),
+            CropHandle::N => (false, true, false, false),
+            CropHandle::S => (false, false, false, true),
+            CropHandle::E => (false, false, true, false),
+            CropHandle::W => (true, false, false, false),
+            CropHandle::NE => (false, true, true, false),
+            CropHandle::NW => (true, true, false, false),
+            CropHandle::SE => (false, false, true, true),
+            CropHandle::SW => (true, false, false, true),
+        }
+    }
+}
+
+/// How the whole image is shown on the canvas while cropping,
+/// contained and centered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropView {
+    pub image_width: f64,
+    pub image_height: f64,
+    pub scale: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+}
+
+impl CropView {
+    pub fn new(image_width: f64, image_height: f64, canvas_width: f64, canvas_height: f64) -> Self {
+        let scale = f64::min(canvas_width / image_width, canvas_height / image_height);
+
+        Self {
+            image_width,
+            image_height,
+            scale,
+            offset_x: (canvas_width - image_width * scale) / 2.0,
+            offset_y: (canvas_height - image_height * scale) / 2.0,
+        }
+    }
+
+    pub fn image_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            (x - self.offset_x) / self.scale,
+            (y - self.offset_y) / self.scale,
+        )
+    }
+
+    pub fn canvas_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            x * self.scale + self.offset_x,
+            y * self.scale + self.offset_y,
+        )
+    }
+
+    /// The positions of the handles of `rect` on the canvas.
+    pub fn handles(&self, rect: &CropRect) -> Vec<(CropHandle, f64, f64)> {
+        let (left, top) = self.canvas_point(rect.x, rect.y);
+        let (right, bottom) = self.canvas_point(rect.x + rect.w, rect.y + rect.h);
+        let center_x = (left + right) / 2.0;
+        let center_y = (top + bottom) / 2.0;
+
+        vec![
+            (CropHandle::NW, left, top),
+            (CropHandle::N, center_x, top),
+            (CropHandle::NE, right, top),
+            (CropHandle::E, right, center_y),
+            (CropHandle::SE, right, bottom),
+            (CropHandle::S, center_x, bottom),
+            (CropHandle::SW, left, bottom),
+            (CropHandle::W, left, center_y),
+        ]
+    }
+
+    pub fn handle_radius(&self) -> f64 {
+        HANDLE_RADIUS
+    }
+
+    /// The handle under the canvas point, or `Move` inside the rect.
+    pub fn handle_at(&self, rect: &CropRect, x: f64, y: f64) -> Option<CropHandle> {
+        let handle = self
+            .handles(rect)
+            .into_iter()
+            .find(|(_, hx, hy)| (x - hx).abs() <= HANDLE_RADIUS && (y - hy).abs() <= HANDLE_RADIUS);
+        if let Some((handle, _, _)) = handle {
+            return Some(handle);
+        }
+
+        let (ix, iy) = self.image_point(x, y);
+        if ix >= rect.x && ix <= rect.x + rect.w && iy >= rect.y && iy <= rect.y + rect.h {
+            Some(CropHandle::Move)
+        } else {
+            None
+        }
+    }
+}
+
+/// Drag `handle` of `before` by (`dx`, `dy`) image pixels,
+/// `aspect` is the width / height ratio to keep.
+pub fn drag_crop(
+    before: &CropRect,
+    handle: CropHandle,
+    dx: f64,
+    dy: f64,
+    aspect: Option<f64>,
+    view: &CropView,
+) -> CropRect {
+    let (image_width, image_height) = (view.image_width, view.image_height);
+
+    if handle == CropHandle::Move {
+        let rect = CropRect {
+            x: before.x + dx,
+            y: before.y + dy,
+            ..*before
+        };
+        return rect.clamp(image_width, image_height);
+    }
+
+    let (move_left, move_top, move_right, move_bottom) = handle.edges();
+    let mut left = before.x;
+    let mut top = before.y;
+    let mut right = before.x + before.w;
+    let mut bottom = before.y + before.h;
+
+    if move_left {
+        left = (left + dx).clamp(0.0, right - 1.0);
+    }
+    if move_right {
+        right = (right + dx).clamp(left + 1.0, image_width);
+    }
+    if move_top {
+        top = (top + dy).clamp(0.0, bottom - 1.0);
+    }
+    if move_bottom {
+        bottom = (bottom + dy).clamp(top + 1.0, image_height);
+    }
+
+    if let Some(aspect) = aspect.filter(|aspect| *aspect > 0.0) {
+        let mut w = right - left;
+        let mut h = bottom - top;
+
+        // the dragged edges decide, a corner follows the larger change.
+        let horizontal = move_left || move_right;
+        let vertical = move_top || move_bottom;
+        if horizontal && (!vertical || w / aspect >= h) {
+            h = w / aspect;
+        } else {
+            w = h * aspect;
+        }
+
+        // keep the opposite edges and fit in the image.
+        let max_w = if move_left { right } else { image_width - left };
+        let max_h = if move_top { bottom } else { image_height - top };
+        let scale = f64::min(1.0, f64::min(max_w / w, max_h / h));
+        w *= scale;
+        h *= scale;
+
+        if move_left {
+            left = right - w;
+        } else {
+            right = left + w;
+        }
+        if move_top {
+            top = bottom - h;
+        } else {
+            bottom = top + h;
+        }
+    }
+
+    CropRect {
+        x: left,
+        y: top,
+        w: right - left,
+        h: bottom - top,
+    }
+    .clamp(image_width, image_height)
+}
@@ -0,0 +1,248 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-17 21:08:14
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-17 22:31:40
+ * @FilePath: /layer-painter/wasm/src/utils/export.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+
+use crate::canvas::{
+    apply_batch_output, batch_outputs, combinations, variant_layers, BatchManifest, Canvas,
+};
+use crate::compositor::{render_onto, render_sprite_sheet, Pixmap, SheetOptions};
+use crate::utils::{create_canvas_element, put_pixmap};
+
+use wasm_bindgen::JsValue;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+            "webp" => Some(ImageFormat::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    // jpeg has no alpha channel, the browser would fill it with black.
+    pub fn has_alpha(&self) -> bool {
+        !matches!(self, ImageFormat::Jpeg)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportOptions {
+    pub format: ImageFormat,
+    // 0.0 ~ 1.0, ignored by png.
+    pub quality: f64,
+    pub transparent: bool,
+}
+
+impl ExportOptions {
+    pub fn new(format: ImageFormat, quality: f64, transparent: bool) -> Self {
+        let quality = if quality.is_nan() {
+            0.92
+        } else {
+            quality.clamp(0.0, 1.0)
+        };
+
+        ExportOptions {
+            format,
+            quality,
+            transparent,
+        }
+    }
+
+    pub fn filename(&self, canvas_name: &str) -> String {
+        format!("{}.{}", canvas_name, self.format.extension())
+    }
+}
+
+/// Composite the visible layers with the size of `canvas` on a detached canvas element,
+/// then encode it as a data url.
+pub fn export_canvas_data_url(
+    canvas: &Canvas,
+    pixmaps: &HashMap<String, Pixmap>,
+    options: &ExportOptions,
+) -> Result<String, String> {
+    let mut pixmap = if !options.transparent || !options.format.has_alpha() {
+        Pixmap::filled(canvas.width(), canvas.height(), [255, 255, 255, 255])
+    } else {
+        Pixmap::new(canvas.width(), canvas.height())
+    };
+
+    for layer_name in render_onto(&mut pixmap, canvas, pixmaps) {
+        log::warn!("The layer: {} can't be exported.", layer_name);
+    }
+
+    let (element, cctx) = create_canvas_element(canvas.width() as u32, canvas.height() as u32)?;
+    put_pixmap(&cctx, &pixmap)?;
+
+    let data_url = element
+        .to_data_url_with_type_and_encoder_options(
+            options.format.mime(),
+            &JsValue::from_f64(options.quality),
+        )
+        .map_err(|err| format!("{:#?}", err))?;
+
+    // the browsers encode the types they don't support as png, like webp in WebKit.
+    if !data_url.starts_with(format!("data:{}", options.format.mime()).as_str()) {
+        return Err(format!(
+            "the browser can't encode {} images",
+            options.format.extension()
+        ));
+    }
+    Ok(data_url)
+}
+
+/// Decode the base64 data of `data_url`.
+pub fn data_url_bytes(data_url: &str) -> Result<Vec<u8>, String> {
+    let data = data_url
+        .split_once(',')
+        .map(|(_, data)| data)
+        .ok_or_else(|| String::from("the exported data url is broken"))?;
+    base64::decode(data).map_err(|err| format!("{}", err))
+}
+
+/// Render every combination of the images of the visible layers which have more than one,
+/// or every scene, and pack the files named by `template` in a zip with a `manifest.json`.
+pub fn export_batch(
+    canvas: &Canvas,
+    pixmaps: &HashMap<String, Pixmap>,
+    options: &ExportOptions,
+    template: &str,
+    by_scenes: bool,
+) -> Result<Vec<u8>, String> {
+    let (scenes, combinations) = if by_scenes {
+        let scenes: Vec<String> = canvas
+            .get_scenes()
+            .iter()
+            .map(|scene| scene.name())
+            .collect();
+        if scenes.is_empty() {
+            return Err(String::from("the canvas has no scenes"));
+        }
+        (scenes, vec![Vec::new()])
+    } else {
+        (Vec::new(), combinations(canvas, &variant_layers(canvas))?)
+    };
+    let outputs = batch_outputs(
+        template,
+        canvas,
+        &scenes,
+        &combinations,
+        options.format.extension(),
+    );
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    // the images are compressed already.
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    for output in outputs.iter() {
+        let mut canvas = canvas.clone();
+        apply_batch_output(&mut canvas, output);
+
+        let bytes = data_url_bytes(&export_canvas_data_url(&canvas, pixmaps, options)?)?;
+
+        zip.start_file(output.file.as_str(), stored)
+            .map_err(|err| format!("{}", err))?;
+        zip.write_all(&bytes).map_err(|err| format!("{}", err))?;
+    }
+
+    let manifest = BatchManifest {
+        canvas: canvas.name(),
+        width: canvas.width(),
+        height: canvas.height(),
+        outputs,
+    };
+    zip.start_file(
+        "manifest.json",
+        FileOptions::default().compression_method(CompressionMethod::Deflated),
+    )
+    .map_err(|err| format!("{}", err))?;
+    zip.write_all(manifest.to_json()?.as_bytes())
+        .map_err(|err| format!("{}", err))?;
+
+    let cursor = zip.finish().map_err(|err| format!("{}", err))?;
+    Ok(cursor.into_inner())
+}
+
+/// Encode the pixels of `pixmap` as a png.
+pub fn encode_png(pixmap: &Pixmap) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    {
+        let mut encoder =
+            png::Encoder::new(&mut bytes, pixmap.width() as u32, pixmap.height() as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|err| format!("{}", err))?;
+        writer
+            .write_image_data(pixmap.data())
+            .map_err(|err| format!("{}", err))?;
+    }
+    Ok(bytes)
+}
+
+/// Pack the frames in a sprite sheet, and zip it as `<canvas_name>.png`
+/// with its atlas as `<canvas_name>.json`.
+pub fn export_sprite_sheet(
+    canvas: &Canvas,
+    pixmaps: &HashMap<String, Pixmap>,
+    options: &SheetOptions,
+    canvas_name: &str,
+) -> Result<Vec<u8>, String> {
+    let image = format!("{}.png", canvas_name);
+    let (sheet, failed) = render_sprite_sheet(canvas, pixmaps, options, image.as_str())?;
+    for layer_name in failed {
+        log::warn!("The layer: {} can't be exported.", layer_name);
+    }
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file(
+        image.as_str(),
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )
+    .map_err(|err| format!("{}", err))?;
+    zip.write_all(&encode_png(&sheet.pixmap)?)
+        .map_err(|err| format!("{}", err))?;
+
+    zip.start_file(
+        format!("{}.json", canvas_name),
+        FileOptions::default().compression_method(CompressionMethod::Deflated),
+    )
+    .map_err(|err| format!("{}", err))?;
+    zip.write_all(sheet.atlas.to_json(options.format)?.as_bytes())
+        .map_err(|err| format!("{}", err))?;
+
+    let cursor = zip.finish().map_err(|err| format!("{}", err))?;
+    Ok(cursor.into_inner())
+}
@@ -0,0 +1,454 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-22 20:17:45
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-22 23:26:09
+ * @FilePath: /layer-painter/wasm/src/utils/history.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use std::collections::VecDeque;
+
+use crate::canvas::{Animation, BlendMode, Canvas, ScaleMode, Shape, Text, Transform};
+
+use super::CropRect;
+
+pub const HISTORY_DEPTH_DEFAULT: usize = 50;
+
+/// A change which knows how to apply and revert itself,
+/// so it doesn't need a snapshot of the whole canvas.
+#[derive(Debug, Clone)]
+pub enum Command {
+    Resize {
+        before: (usize, usize),
+        after: (usize, usize),
+    },
+    RenameLayer {
+        index: usize,
+        before: String,
+        after: String,
+    },
+    SwapLayers(usize, usize),
+    ToggleLayerShow(usize),
+    ToggleLayerMask(usize),
+    SetLayerOpacity {
+        index: usize,
+        before: f64,
+        after: f64,
+    },
+    SetLayerBlend {
+        index: usize,
+        before: BlendMode,
+        after: BlendMode,
+    },
+    SetLayerScale {
+        index: usize,
+        before: ScaleMode,
+        after: ScaleMode,
+    },
+    SelectImage {
+        index: usize,
+        before: usize,
+        after: usize,
+    },
+    SetLayerTransform {
+        index: usize,
+        before: Transform,
+        after: Transform,
+    },
+    SetImageCrop {
+        index: usize,
+        image: usize,
+        before: CropRect,
+        after: CropRect,
+    },
+    ToggleLayerAnimated(usize),
+    SetLayerFrameOffset {
+        index: usize,
+        before: usize,
+        after: usize,
+    },
+    SetAnimation {
+        before: Animation,
+        after: Animation,
+    },
+    SetLayerText {
+        index: usize,
+        before: Text,
+        after: Text,
+    },
+    SetLayerShape {
+        index: usize,
+        before: Shape,
+        after: Shape,
+    },
+}
+
+impl Command {
+    fn apply(&self, canvas: &mut Canvas) {
+        self.set(canvas, false);
+    }
+
+    fn revert(&self, canvas: &mut Canvas) {
+        self.set(canvas, true);
+    }
+
+    fn set(&self, canvas: &mut Canvas, revert: bool) {
+        fn pick<T>(revert: bool, before: T, after: T) -> T {
+            if revert {
+                before
+            } else {
+                after
+            }
+        }
+
+        match self {
+            Command::Resize { before, after } => {
+                let (width, height) = pick(revert, *before, *after);
+                canvas.set_width(width);
+                canvas.set_height(height);
+            }
+
+            Command::RenameLayer {
+                index,
+                before,
+                after,
+            } => {
+                canvas.rename_layer(*index, pick(revert, before, after));
+            }
+
+            Command::SwapLayers(src, dst) => {
+                canvas.swap_layer(*src, *dst);
+            }
+
+            Command::ToggleLayerShow(index) => {
+                if let Some(layer) = canvas.get_mut_layer(*index) {
+                    layer.set_show(!layer.show());
+                }
+            }
+
+            Command::ToggleLayerMask(index) => {
+                if let Some(mask) = canvas
+                    .get_mut_layer(*index)
+                    .and_then(|layer| layer.get_mut_mask())
+                {
+                    mask.set_enabled(!mask.enabled());
+                }
+            }
+
+            Command::SetLayerOpacity {
+                index,
+                before,
+                after,
+            } => {
+                if let Some(layer) = canvas.get_mut_layer(*index) {
+                    layer.set_opacity(pick(revert, *before, *after));
+                }
+            }
+
+            Command::SetLayerBlend {
+                index,
+                before,
+                after,
+            } => {
+                if let Some(layer) = canvas.get_mut_layer(*index) {
+                    layer.set_blend(pick(revert, *before, *after));
+                }
+            }
+
+            Command::SetLayerScale {
+                index,
+                before,
+                after,
+            } => {
+                if let Some(layer) = canvas.get_mut_layer(*index) {
+                    layer.set_scale(pick(revert, *before, *after));
+                }
+            }
+
+            Command::SelectImage {
+                index,
+                before,
+                after,
+            } => {
+                if let Some(layer) = canvas.get_mut_layer(*index) {
+                    let selected = pick(revert, *before, *after);
+                    if selected < layer.images().len() {
+                        layer.set_selected(selected);
+                    }
+                }
+            }
+
+            Command::SetLayerTransform {
+                index,
+                before,
+                after,
+            } => {
+                if let Some(layer) = canvas.get_mut_layer(*index) {
+                    layer.set_transform(pick(revert, *before, *after));
+                }
+            }
+
+            Command::SetImageCrop {
+                index,
+                image,
+                before,
+                after,
+            } => {
+                if let Some(image) = canvas
+                    .get_mut_layer(*index)
+                    .and_then(|layer| layer.get_mut_images().get_mut(*image))
+                {
+                    pick(revert, before, after).apply_to(image);
+                }
+            }
+
+            Command::ToggleLayerAnimated(index) => {
+                if let Some(layer) = canvas.get_mut_layer(*index) {
+                    layer.set_animated(!layer.animated());
+                }
+            }
+
+            Command::SetLayerFrameOffset {
+                index,
+                before,
+                after,
+            } => {
+                if let Some(layer) = canvas.get_mut_layer(*index) {
+                    layer.set_frame_offset(pick(revert, *before, *after));
+                }
+            }
+
+            Command::SetAnimation { before, after } => {
+                *canvas.get_mut_animation() = pick(revert, *before, *after);
+            }
+
+            Command::SetLayerText {
+                index,
+                before,
+                after,
+            } => {
+                if let Some(text) = canvas
+                    .get_mut_layer(*index)
+                    .and_then(|layer| layer.get_mut_text())
+                {
+                    *text = pick(revert, before, after).clone();
+                }
+            }
+
+            Command::SetLayerShape {
+                index,
+                before,
+                after,
+            } => {
+                if let Some(shape) = canvas
+                    .get_mut_layer(*index)
+                    .and_then(|layer| layer.get_mut_shape())
+                {
+                    *shape = pick(revert, before, after).clone();
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Change {
+    Command(Command),
+    // the whole canvas before the change.
+    Snapshot(Canvas),
+}
+
+/// Undo / redo stacks of the canvas changes,
+/// keeps at most `depth` changes to undo.
+pub struct History {
+    undo: VecDeque<Change>,
+    redo: Vec<Change>,
+    depth: usize,
+}
+
+impl History {
+    pub fn new(depth: usize) -> Self {
+        History {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            depth,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn set_depth(&mut self, depth: usize) {
+        self.depth = depth;
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+
+        // the top of the redo stack is the nearest to the present.
+        if self.redo.len() > self.depth {
+            let far = self.redo.len() - self.depth;
+            self.redo.drain(..far);
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn record(&mut self, change: Change) {
+        if self.depth == 0 {
+            return;
+        }
+
+        self.redo.clear();
+        self.push_undo(change);
+    }
+
+    fn push_undo(&mut self, change: Change) {
+        self.undo.push_back(change);
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn record_command(&mut self, command: Command) {
+        self.record(Change::Command(command));
+    }
+
+    pub fn record_snapshot(&mut self, canvas: &Canvas) {
+        self.record(Change::Snapshot(canvas.clone()));
+    }
+
+    /// Change the canvases kept to undo and redo to,
+    /// for the changes which aren't recorded, like the reloaded images.
+    pub fn for_each_snapshot_mut<F: FnMut(&mut Canvas)>(&mut self, mut f: F) {
+        for change in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+            if let Change::Snapshot(canvas) = change {
+                f(canvas);
+            }
+        }
+    }
+
+    /// Revert the last change, returns false if there is nothing to undo.
+    pub fn undo(&mut self, canvas: &mut Canvas) -> bool {
+        match self.undo.pop_back() {
+            Some(Change::Command(command)) => {
+                command.revert(canvas);
+                self.redo.push(Change::Command(command));
+                true
+            }
+
+            Some(Change::Snapshot(snapshot)) => {
+                let current = std::mem::replace(canvas, snapshot);
+                self.redo.push(Change::Snapshot(current));
+                true
+            }
+
+            None => false,
+        }
+    }
+
+    /// Apply the last undone change again, returns false if there is nothing to redo.
+    pub fn redo(&mut self, canvas: &mut Canvas) -> bool {
+        match self.redo.pop() {
+            Some(Change::Command(command)) => {
+                command.apply(canvas);
+                self.push_undo(Change::Command(command));
+                true
+            }
+
+            Some(Change::Snapshot(snapshot)) => {
+                let current = std::mem::replace(canvas, snapshot);
+                self.push_undo(Change::Snapshot(current));
+                true
+            }
+
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resize(width: usize) -> Command {
+        Command::Resize {
+            before: (width - 1, 1),
+            after: (width, 1),
+        }
+    }
+
+    fn history(depth: usize, changes: usize) -> (History, Canvas) {
+        let mut history = History::new(depth);
+        let mut canvas = Canvas::new();
+        for width in 2..changes + 2 {
+            history.record_command(resize(width));
+            canvas.set_width(width);
+        }
+        (history, canvas)
+    }
+
+    #[test]
+    fn record_keeps_the_last_changes() {
+        let (mut history, mut canvas) = history(3, 5);
+        assert_eq!(history.undo.len(), 3);
+        while history.undo(&mut canvas) {}
+        assert_eq!(canvas.width(), 3);
+    }
+
+    #[test]
+    fn set_depth_keeps_the_nearest_redos() {
+        let (mut history, mut canvas) = history(5, 5);
+        while history.undo(&mut canvas) {}
+        assert_eq!(canvas.width(), 1);
+
+        history.set_depth(2);
+        assert_eq!(history.redo.len(), 2);
+        assert!(history.redo(&mut canvas));
+        assert_eq!(canvas.width(), 2);
+        assert!(history.redo(&mut canvas));
+        assert_eq!(canvas.width(), 3);
+        assert!(!history.redo(&mut canvas));
+    }
+
+    #[test]
+    fn redo_is_capped_by_the_depth() {
+        let (mut history, mut canvas) = history(3, 3);
+        while history.undo(&mut canvas) {}
+        history.depth = 2;
+        while history.redo(&mut canvas) {}
+        assert_eq!(canvas.width(), 4);
+        assert_eq!(history.undo.len(), 2);
+    }
+
+    #[test]
+    fn snapshots_are_changed_in_place() {
+        let mut history = History::new(5);
+        let mut canvas = Canvas::new();
+        history.record_snapshot(&canvas);
+        canvas.set_width(2);
+        history.record_snapshot(&canvas);
+        canvas.set_width(3);
+        history.undo(&mut canvas);
+
+        history.for_each_snapshot_mut(|canvas| canvas.set_height(7));
+        assert_eq!(canvas.height(), Canvas::new().height());
+        assert!(history.redo(&mut canvas));
+        assert_eq!((canvas.width(), canvas.height()), (3, 7));
+        assert!(history.undo(&mut canvas));
+        assert!(history.undo(&mut canvas));
+        assert_eq!(canvas.height(), 7);
+    }
+}
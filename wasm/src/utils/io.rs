@@ -2,79 +2,249 @@
  * @Author: IceyBlackTea
  * @Date: 2022-02-06 21:36:16
  * @LastEditors: IceyBlackTea
- * @LastEditTime: 2022-02-08 00:07:31
+ * @LastEditTime: 2022-02-20 16:31:57
  * @FilePath: /layer-painter/wasm/src/utils/io.rs
  * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
  */
 
-use crate::canvas::Image;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-use wasm_bindgen::{JsCast};
-use wasm_bindgen_futures::JsFuture;
-use web_sys::{ImageBitmap, Request, RequestInit, RequestMode, Response};
+use crate::canvas::{read_bundle, Canvas, Image, Mask, MaskMode};
+use crate::utils::{bitmap_to_pixmap, NativeFile};
 
-pub async fn fetch_image(file_path: &str) -> Result<ImageBitmap, String> {
-    let mut opts = RequestInit::new();
-    opts.method("GET");
-    opts.mode(RequestMode::Cors);
+use gloo_timers::callback::Timeout;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, DragEvent, ImageBitmap};
 
-    let request = Request::new_with_str_and_init(file_path, &opts).unwrap();
+pub async fn decode_image(name: &str, bytes: &[u8]) -> Result<ImageBitmap, String> {
+    let parts = js_sys::Array::new();
+    parts.push(&js_sys::Uint8Array::from(bytes));
+    let blob = Blob::new_with_u8_array_sequence(&parts)
+        .map_err(|err| format!("{} can't be loaded, {:#?}", name, err))?;
 
     let window = gloo_utils::window();
-    let resp_value = JsFuture::from(window.fetch_with_request(&request))
-        .await
-        .unwrap();
-    let resp = resp_value.dyn_into::<Response>().unwrap();
+    let image_bitmap_promise = window
+        .create_image_bitmap_with_blob(&blob)
+        .map_err(|err| format!("{} can't be loaded, {:#?}", name, err))?;
 
-    let blob = JsFuture::from(resp.blob().unwrap())
-        .await
-        .unwrap()
-        .dyn_into()
-        .unwrap();
-    
-    let image_bitmap_promise = window.create_image_bitmap_with_blob(&blob).unwrap();
-
-    let result = match JsFuture::from(image_bitmap_promise).await
-    {
-        Ok(blob) => {
-            Ok(blob.dyn_into::<ImageBitmap>().unwrap())
-        }
+    match JsFuture::from(image_bitmap_promise).await {
+        Ok(bitmap) => Ok(bitmap.dyn_into::<ImageBitmap>().unwrap()),
 
         Err(err) => {
-            let err_msg = format!("{} can't be loaded, {:#?}", file_path, err);
+            let err_msg = format!("{} can't be loaded, {:#?}", name, err);
             Err(err_msg)
         }
-    };
-
-    result
+    }
 }
 
 pub async fn load_images(files: Vec<gloo_file::File>) -> Result<Vec<Image>, String> {
+    let names = files.iter().map(|file| file.name()).collect();
+    load_images_as(files, names).await
+}
+
+/// Decode `files` as the images of `names`.
+pub async fn load_images_as(
+    files: Vec<gloo_file::File>,
+    names: Vec<String>,
+) -> Result<Vec<Image>, String> {
+    let mut images = Vec::new();
+
+    for (file, name) in files.into_iter().zip(names) {
+        let bytes = gloo_file::futures::read_as_bytes(&file)
+            .await
+            .map_err(|err| format!("{} can't be read, {:#?}", file.name(), err))?;
+
+        images.push(load_image(&name, bytes).await?);
+    }
+
+    Ok(images)
+}
+
+/// Decode the files read in the app as images with their paths.
+pub async fn load_native_images(files: Vec<NativeFile>) -> Result<Vec<Image>, String> {
     let mut images = Vec::new();
 
     for file in files.into_iter() {
-        let data = gloo_file::futures::read_as_data_url(&file).await.unwrap();
+        let mut image = load_image(&file.name, file.bytes).await?;
+        image.set_path(Some(file.path));
+        images.push(image);
+    }
 
-        match fetch_image(data.as_str()).await {
-            Ok(data) => {
-                images.push(Image::new(&file.name(), Some(data.clone()), data.width() as f64, data.height() as f64));
+    Ok(images)
+}
+
+/// Decode `bytes` as an image keeping them.
+pub async fn load_image(name: &str, bytes: Vec<u8>) -> Result<Image, String> {
+    let data = decode_image(name, &bytes).await?;
+    let mut image = Image::new(
+        name,
+        Some(data.clone()),
+        data.width() as f64,
+        data.height() as f64,
+    );
+    image.set_bytes(Rc::new(bytes));
+
+    Ok(image)
+}
+
+/// Decode the `files` found for the images named `names`, with their paths,
+/// returns the images, the names of the missing files and the errors.
+pub async fn load_found_images(
+    names: Vec<String>,
+    files: Vec<Option<NativeFile>>,
+) -> (Vec<Image>, Vec<String>, Vec<String>) {
+    let mut images = Vec::new();
+    let mut missing = Vec::new();
+    let mut errors = Vec::new();
+
+    for (name, file) in names.into_iter().zip(files) {
+        let file = match file {
+            Some(file) => file,
+            None => {
+                missing.push(name);
+                continue;
             }
-            Err(err) => {
-                return Err(err);
+        };
+
+        match load_image(&name, file.bytes).await {
+            Ok(mut image) => {
+                image.set_path(Some(file.path));
+                images.push(image);
             }
+            Err(err) => errors.push(err),
         }
     }
 
-    Ok(images)
+    (images, missing, errors)
 }
 
-pub async fn load_json(files: Vec<gloo_file::File>) -> Result<String, String> {
-    for file in files.into_iter() {
-        match gloo_file::futures::read_as_text(&file).await {
-            Ok(data) => return Ok(data),
-            Err(err) => return Err(format!("{:#?}", err))
-        };
+/// Read the first file as a layer mask.
+pub async fn load_mask(files: Vec<gloo_file::File>, mode: MaskMode) -> Result<Mask, String> {
+    let file = files
+        .first()
+        .ok_or_else(|| String::from("No mask image is selected."))?;
+
+    let bytes = gloo_file::futures::read_as_bytes(file)
+        .await
+        .map_err(|err| format!("{} can't be read, {:#?}", file.name(), err))?;
+
+    let data = decode_image(&file.name(), &bytes).await?;
+    let pixmap = bitmap_to_pixmap(&data)?;
+
+    Mask::from_rgba(pixmap.width(), pixmap.height(), pixmap.data(), mode)
+}
+
+pub async fn load_project(files: Vec<gloo_file::File>) -> Result<Vec<u8>, String> {
+    match files.first() {
+        Some(file) => gloo_file::futures::read_as_bytes(file)
+            .await
+            .map_err(|err| format!("{:#?}", err)),
+        None => Err(String::from("No project file is selected.")),
+    }
+}
+
+/// Read a bundle and decode the bitmaps of its images.
+pub async fn load_bundle(data: &[u8]) -> Result<Canvas, String> {
+    let mut canvas = read_bundle(data)?;
+
+    let mut decoded = HashMap::new();
+    for (_, layer) in canvas.walk_layers() {
+        for image in layer.images().iter() {
+            if decoded.contains_key(&image.name()) {
+                continue;
+            }
+            if let Some(bytes) = image.bytes() {
+                let data = decode_image(&image.name(), &bytes).await?;
+                decoded.insert(image.name(), data);
+            }
+        }
+    }
+
+    canvas.for_each_layer_mut(|layer| {
+        for image in layer.get_mut_images().iter_mut() {
+            if let Some(data) = decoded.get(&image.name()) {
+                image.set_data(data);
+            }
+        }
+    });
+
+    Ok(canvas)
+}
+
+fn has_extension(file_name: &str, extensions: &[&str]) -> bool {
+    match file_name.rsplit_once('.') {
+        Some((_, extension)) => extensions.contains(&extension.to_lowercase().as_str()),
+        None => false,
     }
+}
+
+/// A project json or bundle, by the extension.
+pub fn is_project_file(file_name: &str) -> bool {
+    has_extension(file_name, &["json", "zip"])
+}
 
-    Ok(String::from("Read Json Failded!"))
-}
\ No newline at end of file
+/// The extensions of the images, the same as `IMAGE_EXTENSIONS` of src-tauri.
+pub const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "webp", "gif", "bmp"];
+
+/// An image which can be uploaded, by the extension, for the paths dropped on the app.
+pub fn is_image_file(file_name: &str) -> bool {
+    has_extension(file_name, &IMAGE_EXTENSIONS)
+}
+
+/// An image which can be uploaded, by the type the browser gives the file.
+pub fn is_image_blob(file: &gloo_file::File) -> bool {
+    file.raw_mime_type().starts_with("image/")
+}
+
+/// The files dropped from the system, empty for the other drags.
+pub fn dropped_files(event: &DragEvent) -> Vec<gloo_file::File> {
+    let files = match event.data_transfer().and_then(|data| data.files()) {
+        Some(files) => files,
+        None => return Vec::new(),
+    };
+
+    (0..files.length())
+        .filter_map(|index| files.get(index))
+        .map(gloo_file::File::from)
+        .collect()
+}
+
+/// A blob of `bytes` with the type `mime`.
+pub fn bytes_blob(bytes: &[u8], mime: &str) -> Result<Blob, String> {
+    let parts = js_sys::Array::new();
+    parts.push(&js_sys::Uint8Array::from(bytes));
+
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime);
+
+    Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .map_err(|err| format!("{:#?}", err))
+}
+
+const REVOKE_DELAY_MS: u32 = 5000;
+
+/// Download `bytes` as a file through a hidden anchor.
+pub fn download_bytes(
+    anchor: &web_sys::HtmlAnchorElement,
+    bytes: &[u8],
+    mime: &str,
+    filename: &str,
+) -> Result<(), String> {
+    let blob = bytes_blob(bytes, mime)?;
+    let url =
+        web_sys::Url::create_object_url_with_blob(&blob).map_err(|err| format!("{:#?}", err))?;
+
+    anchor.set_href(url.as_str());
+    anchor.set_download(filename);
+    anchor.click();
+
+    // the browser may still be reading the blob after the click.
+    Timeout::new(REVOKE_DELAY_MS, move || {
+        let _ = web_sys::Url::revoke_object_url(url.as_str());
+    })
+    .forget();
+
+    Ok(())
+}
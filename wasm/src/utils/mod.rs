@@ -7,8 +7,20 @@
  * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
  */
 
-mod io;
 mod canvas;
+mod clipboard;
+mod crop;
+mod export;
+mod history;
+mod io;
+mod native;
+mod render;
 
-pub use io::*;
 pub use canvas::*;
+pub use clipboard::*;
+pub use crop::*;
+pub use export::*;
+pub use history::*;
+pub use io::*;
+pub use native::*;
+pub use render::*;
@@ -0,0 +1,253 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-03-06 21:05:42
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-03-06 23:31:16
+ * @FilePath: /layer-painter/wasm/src/utils/native.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+/// Emitted by the app with the `NativeFile` of a watched image which is changed.
+pub const IMAGE_CHANGED_EVENT: &str = "image-changed";
+/// Emitted by tauri with the paths of the files dropped on the window.
+pub const FILE_DROP_EVENT: &str = "tauri://file-drop";
+
+/// A file read by the app.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NativeFile {
+    pub path: String,
+    pub name: String,
+    #[serde(deserialize_with = "from_base64")]
+    pub bytes: Vec<u8>,
+}
+
+// the bytes go through json as base64, an array of numbers is a lot larger.
+fn from_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let data = String::deserialize(deserializer)?;
+    base64::decode(&data).map_err(de::Error::custom)
+}
+
+#[derive(Serialize)]
+struct NoArgs {}
+
+/// An image to look for by `relink_images`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MissingImage {
+    pub name: String,
+    pub hash: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SavePathArgs<'a> {
+    file_name: &'a str,
+    save_as: bool,
+}
+
+#[derive(Serialize)]
+struct SaveProjectArgs<'a> {
+    path: &'a str,
+    // base64
+    bytes: String,
+}
+
+#[derive(Serialize)]
+struct OpenProjectFileArgs<'a> {
+    path: &'a str,
+}
+
+#[derive(Serialize)]
+struct ReadImagesArgs<'a> {
+    paths: &'a [String],
+}
+
+#[derive(Serialize)]
+struct WatchImagesArgs<'a> {
+    paths: &'a [String],
+}
+
+#[derive(Serialize)]
+struct RelinkImagesArgs<'a> {
+    images: &'a [MissingImage],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SaveFileArgs<'a> {
+    // base64
+    bytes: String,
+    file_name: &'a str,
+}
+
+fn js_error(err: JsValue) -> String {
+    err.as_string().unwrap_or_else(|| format!("{:#?}", err))
+}
+
+/// Whether it runs in the tauri app, instead of a browser.
+pub fn is_tauri() -> bool {
+    js_sys::Reflect::has(&gloo_utils::window(), &JsValue::from_str("__TAURI__")).unwrap_or(false)
+}
+
+// the function `name` of the tauri api, under the `modules` of it, with its object.
+fn tauri_function(modules: &[&str], name: &str) -> Result<(JsValue, js_sys::Function), String> {
+    let mut this: JsValue = gloo_utils::window().into();
+    for key in ["__TAURI__"].iter().chain(modules.iter()) {
+        this = js_sys::Reflect::get(&this, &JsValue::from_str(key)).map_err(js_error)?;
+    }
+    let function = js_sys::Reflect::get(&this, &JsValue::from_str(name))
+        .map_err(js_error)?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| format!("the tauri api {} is not found", name))?;
+
+    Ok((this, function))
+}
+
+fn from_js<R: DeserializeOwned>(value: &JsValue) -> Result<R, String> {
+    // undefined isn't json.
+    let json = js_sys::JSON::stringify(value)
+        .ok()
+        .and_then(|json| json.as_string())
+        .unwrap_or_else(|| String::from("null"));
+    serde_json::from_str(json.as_str()).map_err(|err| format!("{}", err))
+}
+
+// call a command of src-tauri, the arguments and the result go through json.
+async fn invoke<A: Serialize, R: DeserializeOwned>(command: &str, args: &A) -> Result<R, String> {
+    let (tauri, invoke) = tauri_function(&[], "invoke")?;
+
+    let args = serde_json::to_string(args).map_err(|err| format!("{}", err))?;
+    let args = js_sys::JSON::parse(args.as_str()).map_err(js_error)?;
+    let promise = invoke
+        .call2(&tauri, &JsValue::from_str(command), &args)
+        .map_err(js_error)?
+        .dyn_into::<js_sys::Promise>()
+        .map_err(|_| format!("{} doesn't return a promise", command))?;
+
+    let result = JsFuture::from(promise).await.map_err(js_error)?;
+    from_js(&result)
+}
+
+/// Listening to an event of the app until it's dropped.
+pub struct NativeListener {
+    _callback: Closure<dyn FnMut(JsValue)>,
+    // resolved by `listen` later.
+    unlisten: Rc<RefCell<Option<js_sys::Function>>>,
+}
+
+impl Drop for NativeListener {
+    fn drop(&mut self) {
+        if let Some(unlisten) = self.unlisten.borrow_mut().take() {
+            unlisten.call0(&JsValue::NULL).ok();
+        }
+    }
+}
+
+/// Call `callback` with the payload of every `event` emitted by the app.
+pub fn native_listen<T, F>(event: &str, callback: F) -> Result<NativeListener, String>
+where
+    T: DeserializeOwned,
+    F: Fn(T) + 'static,
+{
+    let (event_api, listen) = tauri_function(&["event"], "listen")?;
+
+    let event_name = String::from(event);
+    let callback = Closure::wrap(Box::new(move |event: JsValue| {
+        let payload =
+            js_sys::Reflect::get(&event, &JsValue::from_str("payload")).unwrap_or(JsValue::NULL);
+        match from_js(&payload) {
+            Ok(payload) => callback(payload),
+            Err(err) => log::warn!("The payload of {} is broken: {}", event_name, err),
+        }
+    }) as Box<dyn FnMut(JsValue)>);
+
+    let promise = listen
+        .call2(
+            &event_api,
+            &JsValue::from_str(event),
+            callback.as_ref().unchecked_ref(),
+        )
+        .map_err(js_error)?
+        .dyn_into::<js_sys::Promise>()
+        .map_err(|_| format!("listening to {} doesn't return a promise", event))?;
+
+    let unlisten = Rc::new(RefCell::new(None));
+    let resolved = unlisten.clone();
+    spawn_local(async move {
+        if let Ok(function) = JsFuture::from(promise).await {
+            *resolved.borrow_mut() = function.dyn_into::<js_sys::Function>().ok();
+        }
+    });
+
+    Ok(NativeListener {
+        _callback: callback,
+        unlisten,
+    })
+}
+
+/// Pick a project through a dialog, `None` if it's canceled.
+pub async fn native_open_project() -> Result<Option<NativeFile>, String> {
+    invoke("open_project", &NoArgs {}).await
+}
+
+/// Read the project at `path`, which becomes the current project.
+pub async fn native_open_project_file(path: &str) -> Result<NativeFile, String> {
+    invoke("open_project_file", &OpenProjectFileArgs { path }).await
+}
+
+/// Pick images through a dialog.
+pub async fn native_open_images() -> Result<Vec<NativeFile>, String> {
+    invoke("open_images", &NoArgs {}).await
+}
+
+/// Read the images at `paths`, `None` for the missing files.
+pub async fn native_read_images(paths: &[String]) -> Result<Vec<Option<NativeFile>>, String> {
+    invoke("read_images", &ReadImagesArgs { paths }).await
+}
+
+/// Look for `images` in a folder picked through a dialog, by name or content hash,
+/// `None` if it's canceled.
+pub async fn native_relink_images(
+    images: &[MissingImage],
+) -> Result<Option<Vec<Option<NativeFile>>>, String> {
+    invoke("relink_images", &RelinkImagesArgs { images }).await
+}
+
+/// The current path of the project, or one picked through a dialog,
+/// `None` if it's canceled.
+pub async fn native_project_save_path(
+    file_name: &str,
+    save_as: bool,
+) -> Result<Option<String>, String> {
+    invoke("project_save_path", &SavePathArgs { file_name, save_as }).await
+}
+
+/// Write the project at `path`, which becomes the current project.
+pub async fn native_save_project(path: &str, bytes: &[u8]) -> Result<(), String> {
+    let bytes = base64::encode(bytes);
+    invoke("save_project", &SaveProjectArgs { path, bytes }).await
+}
+
+/// Save an export through a dialog, returns the path or `None` if it's canceled.
+pub async fn native_save_file(bytes: &[u8], file_name: &str) -> Result<Option<String>, String> {
+    let bytes = base64::encode(bytes);
+    invoke("save_file", &SaveFileArgs { bytes, file_name }).await
+}
+
+/// Watch the image files at `paths` for `IMAGE_CHANGED_EVENT`, instead of the watched ones.
+pub async fn native_watch_images(paths: &[String]) -> Result<(), String> {
+    invoke("watch_images", &WatchImagesArgs { paths }).await
+}
+
+/// Forget the current project of the app.
+pub async fn native_close_project() -> Result<(), String> {
+    invoke("close_project", &NoArgs {}).await
+}
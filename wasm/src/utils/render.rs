@@ -0,0 +1,288 @@
+/*
+ * @Author: IceyBlackTea
+ * @Date: 2022-02-17 21:02:51
+ * @LastEditors: IceyBlackTea
+ * @LastEditTime: 2022-02-18 23:58:20
+ * @FilePath: /layer-painter/wasm/src/utils/render.rs
+ * @Description: Copyright © 2021 IceyBlackTea. All rights reserved.
+ */
+
+use std::collections::HashMap;
+
+use crate::canvas::{Canvas, Text, TextAlign};
+use crate::compositor::{text_to_pixmap, Pixmap, Placement};
+use crate::utils::{CropRect, CropView};
+
+use gloo_events::EventListener;
+use wasm_bindgen::{Clamped, JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageBitmap, ImageData};
+use yew::Callback;
+
+/// Create a detached canvas element and its 2d context.
+pub fn create_canvas_element(
+    width: u32,
+    height: u32,
+) -> Result<(HtmlCanvasElement, CanvasRenderingContext2d), String> {
+    let element = gloo_utils::document()
+        .create_element("canvas")
+        .map_err(|err| format!("{:#?}", err))?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(|err| format!("{:#?}", err))?;
+
+    element.set_width(width);
+    element.set_height(height);
+
+    let cctx = element
+        .get_context("2d")
+        .map_err(|err| format!("{:#?}", err))?
+        .ok_or_else(|| String::from("2d context is not supported"))?
+        .dyn_into::<CanvasRenderingContext2d>()
+        .map_err(|err| format!("{:#?}", err))?;
+
+    Ok((element, cctx))
+}
+
+/// Decode the pixels of a bitmap by drawing it on a detached canvas.
+pub fn bitmap_to_pixmap(bitmap: &ImageBitmap) -> Result<Pixmap, String> {
+    let width = bitmap.width();
+    let height = bitmap.height();
+    let (_, cctx) = create_canvas_element(width, height)?;
+
+    cctx.draw_image_with_image_bitmap(bitmap, 0.0, 0.0)
+        .map_err(|err| format!("{:#?}", err))?;
+
+    let image_data = cctx
+        .get_image_data(0.0, 0.0, width as f64, height as f64)
+        .map_err(|err| format!("{:#?}", err))?;
+
+    Pixmap::from_rgba(width as usize, height as usize, image_data.data().0)
+}
+
+/// Draw the lines of `text` on a detached canvas in the box of the longest line,
+/// for the fonts which aren't bundled.
+pub fn canvas_text_to_pixmap(text: &Text) -> Result<Pixmap, String> {
+    let (_, measure) = create_canvas_element(1, 1)?;
+    measure.set_font(text.css_font().as_str());
+    let measure_width = |content: &str| {
+        measure
+            .measure_text(content)
+            .map(|metrics| metrics.width())
+            .map_err(|err| format!("{:#?}", err))
+    };
+
+    // the characters of a line and their x, every one is drawn alone when the letters are spaced.
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut glyphs = Vec::new();
+        let mut x = 0.0;
+        if text.letter_spacing == 0.0 {
+            glyphs.push((String::from(line), 0.0));
+            x = measure_width(line)?;
+        } else {
+            for (index, character) in line.chars().enumerate() {
+                if index > 0 {
+                    x += text.letter_spacing;
+                }
+                let character = character.to_string();
+                let width = measure_width(character.as_str())?;
+                glyphs.push((character, x));
+                x += width;
+            }
+        }
+        lines.push((glyphs, x.max(0.0)));
+    }
+
+    let box_width = lines
+        .iter()
+        .fold(0.0_f64, |width, (_, line_width)| width.max(*line_width));
+    let width = (box_width.ceil() as u32).max(1);
+    let height = ((lines.len() as f64 * text.line_pixels()).ceil() as u32).max(1);
+
+    let (_, cctx) = create_canvas_element(width, height)?;
+    cctx.set_font(text.css_font().as_str());
+    cctx.set_fill_style_str(text.color.as_str());
+    cctx.set_text_baseline("middle");
+
+    for (index, (glyphs, line_width)) in lines.iter().enumerate() {
+        let left = match text.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => (box_width - line_width) / 2.0,
+            TextAlign::Right => box_width - line_width,
+        };
+        let y = (index as f64 + 0.5) * text.line_pixels();
+        for (content, x) in glyphs.iter() {
+            cctx.fill_text(content, left + x, y)
+                .map_err(|err| format!("{:#?}", err))?;
+        }
+    }
+
+    let image_data = cctx
+        .get_image_data(0.0, 0.0, width as f64, height as f64)
+        .map_err(|err| format!("{:#?}", err))?;
+
+    Pixmap::from_rgba(width as usize, height as usize, image_data.data().0)
+}
+
+/// Decode every loaded image of `canvas` which is not in `pixmaps` yet,
+/// and draw the texts which are changed, in the bundled font if they can be.
+/// The others are kept going, and why they failed is returned.
+pub fn sync_pixmaps(canvas: &Canvas, pixmaps: &mut HashMap<String, Pixmap>) -> Vec<String> {
+    let mut failed = Vec::new();
+    let mut text_keys = Vec::new();
+    for (_, layer) in canvas.walk_layers() {
+        if let Some(text) = layer.get_text() {
+            let key = text.key();
+            if !pixmaps.contains_key(key.as_str()) {
+                match text_to_pixmap(text).or_else(|_| canvas_text_to_pixmap(text)) {
+                    Ok(pixmap) => {
+                        pixmaps.insert(key.clone(), pixmap);
+                    }
+                    Err(err) => failed.push(format!(
+                        "The text layer: {} can't be drawn, {}",
+                        layer.name(),
+                        err
+                    )),
+                }
+            }
+            text_keys.push(key);
+        }
+
+        for image in layer.images().iter() {
+            if pixmaps.contains_key(image.name().as_str()) {
+                continue;
+            }
+
+            if let Some(data) = image.data() {
+                match bitmap_to_pixmap(&data) {
+                    Ok(pixmap) => {
+                        pixmaps.insert(image.name(), pixmap);
+                    }
+                    Err(err) => {
+                        // the image may be in more layers.
+                        let message =
+                            format!("The image: {} can't be decoded, {}", image.name(), err);
+                        if !failed.contains(&message) {
+                            failed.push(message);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // the texts before the changes.
+    pixmaps.retain(|name, _| !Text::is_key(name) || text_keys.contains(name));
+
+    failed
+}
+
+/// Emit `callback` when the web fonts are loaded, the first ones by `document.fonts.ready`
+/// and the later ones by their `loadingdone` events.
+pub fn listen_fonts_loaded(callback: Callback<()>) -> EventListener {
+    let fonts = gloo_utils::document().fonts();
+    if let Ok(ready) = fonts.ready() {
+        let callback = callback.clone();
+        spawn_local(async move {
+            if JsFuture::from(ready).await.is_ok() {
+                callback.emit(());
+            }
+        });
+    }
+
+    EventListener::new(&fonts, "loadingdone", move |_| callback.emit(()))
+}
+
+/// The content hashes of the images of `canvas` by name.
+pub fn image_hashes(canvas: &Canvas) -> HashMap<String, Option<String>> {
+    let mut hashes = HashMap::new();
+    for (_, layer) in canvas.walk_layers() {
+        for image in layer.images().iter() {
+            hashes.insert(image.name(), image.hash());
+        }
+    }
+    hashes
+}
+
+/// Drop the cached pixmaps of the images which are missing or changed in `canvas`
+/// since `before`, images without a hash are always dropped.
+pub fn drop_changed_pixmaps(
+    before: &HashMap<String, Option<String>>,
+    canvas: &Canvas,
+    pixmaps: &mut HashMap<String, Pixmap>,
+) {
+    let after = image_hashes(canvas);
+    pixmaps.retain(|name, _| {
+        if Text::is_key(name) {
+            return true;
+        }
+
+        match (before.get(name), after.get(name)) {
+            (Some(Some(before)), Some(Some(after))) => before == after,
+            _ => false,
+        }
+    });
+}
+
+pub fn put_pixmap(cctx: &CanvasRenderingContext2d, pixmap: &Pixmap) -> Result<(), String> {
+    if pixmap.width() == 0 || pixmap.height() == 0 {
+        return Ok(());
+    }
+
+    let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+        Clamped(pixmap.data()),
+        pixmap.width() as u32,
+        pixmap.height() as u32,
+    )
+    .map_err(|err| format!("{:#?}", err))?;
+
+    cctx.put_image_data(&image_data, 0.0, 0.0)
+        .map_err(|err| format!("{:#?}", err))
+}
+
+/// Outline the transformed rect of a layer with a dashed line.
+pub fn draw_placement_outline(cctx: &CanvasRenderingContext2d, placement: &Placement) {
+    let corners = placement.corners();
+
+    cctx.save();
+    cctx.set_stroke_style_str("#3b82f6");
+    cctx.set_line_width(1.0);
+    let dash = js_sys::Array::of2(&JsValue::from(4.0), &JsValue::from(4.0));
+    cctx.set_line_dash(&dash).ok();
+
+    cctx.begin_path();
+    cctx.move_to(corners[0].0, corners[0].1);
+    for (x, y) in corners.iter().skip(1) {
+        cctx.line_to(*x, *y);
+    }
+    cctx.close_path();
+    cctx.stroke();
+    cctx.restore();
+}
+
+/// Dim the image outside of the crop rect and draw the rect with its handles.
+pub fn draw_crop_overlay(cctx: &CanvasRenderingContext2d, view: &CropView, rect: &CropRect) {
+    let (left, top) = view.canvas_point(0.0, 0.0);
+    let (right, bottom) = view.canvas_point(view.image_width, view.image_height);
+    let (x, y) = view.canvas_point(rect.x, rect.y);
+    let (w, h) = (rect.w * view.scale, rect.h * view.scale);
+
+    cctx.save();
+    cctx.set_fill_style_str("rgba(0, 0, 0, 0.5)");
+    cctx.fill_rect(left, top, right - left, y - top);
+    cctx.fill_rect(left, y + h, right - left, bottom - y - h);
+    cctx.fill_rect(left, y, x - left, h);
+    cctx.fill_rect(x + w, y, right - x - w, h);
+
+    cctx.set_stroke_style_str("#3b82f6");
+    cctx.set_line_width(1.0);
+    cctx.stroke_rect(x, y, w, h);
+
+    let radius = view.handle_radius();
+    cctx.set_fill_style_str("#ffffff");
+    for (_, hx, hy) in view.handles(rect) {
+        cctx.fill_rect(hx - radius, hy - radius, radius * 2.0, radius * 2.0);
+        cctx.stroke_rect(hx - radius, hy - radius, radius * 2.0, radius * 2.0);
+    }
+    cctx.restore();
+}